@@ -8,13 +8,268 @@
 use std::collections::HashMap;
 
 use labelswap_data::{
+    class_map::ClassMap,
     models::{
-        format::{ClassFormat, ClassMapping, ImagePath, SourceType},
-        Format,
+        format::{
+            BoxType, ClassFormat, ClassMapping, ImageDimensions, ImageId, ImagePath,
+            SegmentationType, SourceType,
+        },
+        Annotation, Format, ImageRecord, Winding,
     },
     parser::*,
     serializer::*,
 };
+use std::path::PathBuf;
+
+/// One of the parsers known by the registry. FormatParser is not object
+/// safe, so the registry hands out this enum instead of a trait object.
+pub enum RegistryParser {
+    Yolo5Obb(Yolo5ObbParser),
+    Yolo5Txt(Yolo5TxtParser),
+    YoloDarknet(YoloDarknetParser),
+    CocoJson(CocoJsonParser),
+    TfObjectDetection(TfObjectDetectionParser),
+    YoloSeg(YoloSegParser),
+    YoloPose(YoloPoseParser),
+    PascalVoc(PascalVocParser),
+}
+
+impl FormatParser for RegistryParser {
+    fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
+        match self {
+            Self::Yolo5Obb(parser) => parser.init(path),
+            Self::Yolo5Txt(parser) => parser.init(path),
+            Self::YoloDarknet(parser) => parser.init(path),
+            Self::CocoJson(parser) => parser.init(path),
+            Self::TfObjectDetection(parser) => parser.init(path),
+            Self::YoloSeg(parser) => parser.init(path),
+            Self::YoloPose(parser) => parser.init(path),
+            Self::PascalVoc(parser) => parser.init(path),
+        }
+    }
+
+    fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        match self {
+            Self::Yolo5Obb(parser) => parser.get_next(),
+            Self::Yolo5Txt(parser) => parser.get_next(),
+            Self::YoloDarknet(parser) => parser.get_next(),
+            Self::CocoJson(parser) => parser.get_next(),
+            Self::TfObjectDetection(parser) => parser.get_next(),
+            Self::YoloSeg(parser) => parser.get_next(),
+            Self::YoloPose(parser) => parser.get_next(),
+            Self::PascalVoc(parser) => parser.get_next(),
+        }
+    }
+
+    fn has_next(&mut self) -> bool {
+        match self {
+            Self::Yolo5Obb(parser) => parser.has_next(),
+            Self::Yolo5Txt(parser) => parser.has_next(),
+            Self::YoloDarknet(parser) => parser.has_next(),
+            Self::CocoJson(parser) => parser.has_next(),
+            Self::TfObjectDetection(parser) => parser.has_next(),
+            Self::YoloSeg(parser) => parser.has_next(),
+            Self::YoloPose(parser) => parser.has_next(),
+            Self::PascalVoc(parser) => parser.has_next(),
+        }
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        match self {
+            Self::Yolo5Obb(parser) => parser.current_source_file(),
+            Self::Yolo5Txt(parser) => parser.current_source_file(),
+            Self::YoloDarknet(parser) => parser.current_source_file(),
+            Self::CocoJson(parser) => parser.current_source_file(),
+            Self::TfObjectDetection(parser) => parser.current_source_file(),
+            Self::YoloSeg(parser) => parser.current_source_file(),
+            Self::YoloPose(parser) => parser.current_source_file(),
+            Self::PascalVoc(parser) => parser.current_source_file(),
+        }
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        match self {
+            Self::Yolo5Obb(parser) => parser.total_files(),
+            Self::Yolo5Txt(parser) => parser.total_files(),
+            Self::YoloDarknet(parser) => parser.total_files(),
+            Self::CocoJson(parser) => parser.total_files(),
+            Self::TfObjectDetection(parser) => parser.total_files(),
+            Self::YoloSeg(parser) => parser.total_files(),
+            Self::YoloPose(parser) => parser.total_files(),
+            Self::PascalVoc(parser) => parser.total_files(),
+        }
+    }
+
+    fn remaining_annotations(&self) -> Option<usize> {
+        match self {
+            Self::Yolo5Obb(parser) => parser.remaining_annotations(),
+            Self::Yolo5Txt(parser) => parser.remaining_annotations(),
+            Self::YoloDarknet(parser) => parser.remaining_annotations(),
+            Self::CocoJson(parser) => parser.remaining_annotations(),
+            Self::TfObjectDetection(parser) => parser.remaining_annotations(),
+            Self::YoloSeg(parser) => parser.remaining_annotations(),
+            Self::YoloPose(parser) => parser.remaining_annotations(),
+            Self::PascalVoc(parser) => parser.remaining_annotations(),
+        }
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        match self {
+            Self::Yolo5Obb(parser) => parser.take_empty_images(),
+            Self::Yolo5Txt(parser) => parser.take_empty_images(),
+            Self::YoloDarknet(parser) => parser.take_empty_images(),
+            Self::CocoJson(parser) => parser.take_empty_images(),
+            Self::TfObjectDetection(parser) => parser.take_empty_images(),
+            Self::YoloSeg(parser) => parser.take_empty_images(),
+            Self::YoloPose(parser) => parser.take_empty_images(),
+            Self::PascalVoc(parser) => parser.take_empty_images(),
+        }
+    }
+}
+
+impl RegistryParser {
+    /// The parser as a MultiFileParser, if its format stores annotations
+    /// across several files.
+    pub fn as_multi_file(&self) -> Option<&dyn MultiFileParser> {
+        match self {
+            Self::Yolo5Obb(parser) => Some(parser),
+            Self::Yolo5Txt(parser) => Some(parser),
+            Self::YoloDarknet(parser) => Some(parser),
+            Self::YoloSeg(parser) => Some(parser),
+            Self::YoloPose(parser) => Some(parser),
+            Self::PascalVoc(parser) => Some(parser),
+            Self::CocoJson(_) | Self::TfObjectDetection(_) => None,
+        }
+    }
+
+    /// Sets whether the keypoints read have a visibility. Returns false if
+    /// the format does not need to be told.
+    pub fn set_keypoint_visibility(&mut self, visibility: bool) -> bool {
+        match self {
+            Self::YoloPose(parser) => {
+                parser.set_visibility(visibility);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// One of the serializers known by the registry.
+pub enum RegistrySerializer {
+    Yolo5Obb(Yolo5ObbSerializer),
+    CocoJson(CocoJsonSerializer),
+    TfObjectDetection(Box<TfObjectDetectionSerializer>),
+    YoloSeg(YoloSegSerializer),
+    YoloPose(YoloPoseSerializer),
+    PascalVoc(PascalVocSerializer),
+    YoloDarknet(YoloDarknetSerializer),
+}
+
+impl FormatSerializer for RegistrySerializer {
+    fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
+        match self {
+            Self::Yolo5Obb(serializer) => serializer.init(path),
+            Self::CocoJson(serializer) => serializer.init(path),
+            Self::TfObjectDetection(serializer) => serializer.init(path),
+            Self::YoloSeg(serializer) => serializer.init(path),
+            Self::YoloPose(serializer) => serializer.init(path),
+            Self::PascalVoc(serializer) => serializer.init(path),
+            Self::YoloDarknet(serializer) => serializer.init(path),
+        }
+    }
+
+    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+        match self {
+            Self::Yolo5Obb(serializer) => serializer.push(annotation),
+            Self::CocoJson(serializer) => serializer.push(annotation),
+            Self::TfObjectDetection(serializer) => serializer.push(annotation),
+            Self::YoloSeg(serializer) => serializer.push(annotation),
+            Self::YoloPose(serializer) => serializer.push(annotation),
+            Self::PascalVoc(serializer) => serializer.push(annotation),
+            Self::YoloDarknet(serializer) => serializer.push(annotation),
+        }
+    }
+
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        match self {
+            Self::Yolo5Obb(serializer) => serializer.push_image(image),
+            Self::CocoJson(serializer) => serializer.push_image(image),
+            Self::TfObjectDetection(serializer) => serializer.push_image(image),
+            Self::YoloSeg(serializer) => serializer.push_image(image),
+            Self::YoloPose(serializer) => serializer.push_image(image),
+            Self::PascalVoc(serializer) => serializer.push_image(image),
+            Self::YoloDarknet(serializer) => serializer.push_image(image),
+        }
+    }
+
+    fn finish(self) -> SerializerResult<()> {
+        match self {
+            Self::Yolo5Obb(serializer) => serializer.finish(),
+            Self::CocoJson(serializer) => serializer.finish(),
+            Self::TfObjectDetection(serializer) => serializer.finish(),
+            Self::YoloSeg(serializer) => serializer.finish(),
+            Self::YoloPose(serializer) => serializer.finish(),
+            Self::PascalVoc(serializer) => serializer.finish(),
+            Self::YoloDarknet(serializer) => serializer.finish(),
+        }
+    }
+}
+
+impl RegistrySerializer {
+    /// Writes the corners of the annotations following `winding`. Returns
+    /// false if the format does not store corners.
+    pub fn set_winding(&mut self, winding: Winding) -> bool {
+        match self {
+            Self::Yolo5Obb(serializer) => {
+                serializer.set_winding(Some(winding));
+                true
+            }
+            Self::CocoJson(_)
+            | Self::TfObjectDetection(_)
+            | Self::YoloSeg(_)
+            | Self::YoloPose(_)
+            | Self::PascalVoc(_)
+            | Self::YoloDarknet(_) => false,
+        }
+    }
+
+    /// Sets whether the keypoints are written with their visibility.
+    /// Returns false if the format does not need to be told.
+    pub fn set_keypoint_visibility(&mut self, visibility: bool) -> bool {
+        match self {
+            Self::YoloPose(serializer) => {
+                serializer.set_visibility(visibility);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Sets the classes the format lists along with its annotations.
+    /// Returns false if the format does not list them.
+    pub fn set_class_map(&mut self, class_map: ClassMap) -> bool {
+        match self {
+            Self::YoloDarknet(serializer) => {
+                serializer.set_class_map(class_map);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Stops the format from writing the list of its classes, as another
+    /// component writes it. Returns false if the format does not list them.
+    pub fn skip_class_list(&mut self) -> bool {
+        match self {
+            Self::YoloDarknet(serializer) => {
+                serializer.set_write_labels(false);
+                true
+            }
+            _ => false,
+        }
+    }
+}
 
 pub struct FormatRegistry {
     formats: HashMap<String, Format>,
@@ -37,6 +292,10 @@ impl FormatRegistry {
                 file_extension: None,
                 is_normalized: false,
                 image_path: ImagePath::NoPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::NoDimensions,
+                box_type: BoxType::Oriented,
+                segmentation_type: SegmentationType::NoSegmentation,
                 class_mapping: ClassMapping::NoMapping,
                 class_format: ClassFormat::Name,
                 source_type: SourceType::MultipleFiles,
@@ -50,11 +309,117 @@ impl FormatRegistry {
                 file_extension: Some(String::from("json")),
                 is_normalized: false,
                 image_path: ImagePath::ContainsPath,
+                image_id: ImageId::ContainsId,
+                image_dimensions: ImageDimensions::ContainsDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::PolygonsAndMasks,
                 class_mapping: ClassMapping::ContainsMapping,
                 class_format: ClassFormat::Both,
                 source_type: SourceType::SingleFile,
             },
         );
+        table.insert(
+            String::from("yolo5txt"),
+            Format {
+                name: String::from("YOLO v5 TXT"),
+                id: String::from("yolo5txt"),
+                file_extension: None,
+                is_normalized: true,
+                image_path: ImagePath::NoPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::NoDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::NoSegmentation,
+                class_mapping: ClassMapping::NoMapping,
+                class_format: ClassFormat::Id,
+                source_type: SourceType::MultipleFiles,
+            },
+        );
+        table.insert(
+            String::from("yolodarknet"),
+            Format {
+                name: String::from("YOLO Darknet TXT"),
+                id: String::from("yolodarknet"),
+                file_extension: None,
+                is_normalized: true,
+                image_path: ImagePath::NoPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::NoDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::NoSegmentation,
+                class_mapping: ClassMapping::ContainsMapping,
+                class_format: ClassFormat::Both,
+                source_type: SourceType::MultipleFiles,
+            },
+        );
+        table.insert(
+            String::from("tfcsv"),
+            Format {
+                name: String::from("Tensorflow Object Detection CSV"),
+                id: String::from("tfcsv"),
+                file_extension: Some(String::from("csv")),
+                is_normalized: false,
+                image_path: ImagePath::ContainsPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::ContainsDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::NoSegmentation,
+                class_mapping: ClassMapping::NoMapping,
+                class_format: ClassFormat::Name,
+                source_type: SourceType::SingleFile,
+            },
+        );
+        table.insert(
+            String::from("yoloseg"),
+            Format {
+                name: String::from("YOLO Segmentation TXT"),
+                id: String::from("yoloseg"),
+                file_extension: None,
+                is_normalized: true,
+                image_path: ImagePath::NoPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::NoDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::Polygons,
+                class_mapping: ClassMapping::NoMapping,
+                class_format: ClassFormat::Id,
+                source_type: SourceType::MultipleFiles,
+            },
+        );
+        table.insert(
+            String::from("yolopose"),
+            Format {
+                name: String::from("YOLO Pose TXT"),
+                id: String::from("yolopose"),
+                file_extension: None,
+                is_normalized: true,
+                image_path: ImagePath::NoPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::NoDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::NoSegmentation,
+                class_mapping: ClassMapping::NoMapping,
+                class_format: ClassFormat::Id,
+                source_type: SourceType::MultipleFiles,
+            },
+        );
+        table.insert(
+            String::from("pascalvoc"),
+            Format {
+                name: String::from("Pascal VOC XML"),
+                id: String::from("pascalvoc"),
+                file_extension: None,
+                is_normalized: false,
+                image_path: ImagePath::ContainsPath,
+                image_id: ImageId::NoId,
+                image_dimensions: ImageDimensions::ContainsDimensions,
+                box_type: BoxType::AxisAligned,
+                segmentation_type: SegmentationType::NoSegmentation,
+                class_mapping: ClassMapping::NoMapping,
+                class_format: ClassFormat::Name,
+                source_type: SourceType::MultipleFiles,
+            },
+        );
         table
     }
 
@@ -62,31 +427,42 @@ impl FormatRegistry {
         self.formats.get(id)
     }
 
-    pub fn get_format_parser(&self, id: &str) -> Option<impl FormatParser> {
-        match self.formats.get(id) {
-            Some(format) => format,
-            None => return None,
-        };
+    pub fn get_format_parser(&self, id: &str) -> Option<RegistryParser> {
+        self.formats.get(id)?;
 
         match id {
-            "yolo5obb" => Some(Yolo5ObbParser::new()),
+            "yolo5obb" => Some(RegistryParser::Yolo5Obb(Yolo5ObbParser::new())),
+            "yolo5txt" => Some(RegistryParser::Yolo5Txt(Yolo5TxtParser::new())),
+            "yolodarknet" => Some(RegistryParser::YoloDarknet(YoloDarknetParser::new())),
+            "cocojson" => Some(RegistryParser::CocoJson(CocoJsonParser::new())),
+            "tfcsv" => Some(RegistryParser::TfObjectDetection(
+                TfObjectDetectionParser::new(),
+            )),
+            "yoloseg" => Some(RegistryParser::YoloSeg(YoloSegParser::new())),
+            "yolopose" => Some(RegistryParser::YoloPose(YoloPoseParser::new())),
+            "pascalvoc" => Some(RegistryParser::PascalVoc(PascalVocParser::new())),
             _ => None,
         }
     }
 
-    pub fn get_format_serializer(&self, id: &str) -> Option<impl FormatSerializer> {
-        match self.formats.get(id) {
-            Some(format) => format,
-            None => return None,
-        };
+    pub fn get_format_serializer(&self, id: &str) -> Option<RegistrySerializer> {
+        self.formats.get(id)?;
 
         match id {
-            "yolo5obb" => Some(Yolo5ObbSerializer::new()),
+            "yolo5obb" => Some(RegistrySerializer::Yolo5Obb(Yolo5ObbSerializer::new())),
+            "cocojson" => Some(RegistrySerializer::CocoJson(CocoJsonSerializer::new())),
+            "tfcsv" => Some(RegistrySerializer::TfObjectDetection(Box::new(
+                TfObjectDetectionSerializer::new(),
+            ))),
+            "yoloseg" => Some(RegistrySerializer::YoloSeg(YoloSegSerializer::new())),
+            "yolopose" => Some(RegistrySerializer::YoloPose(YoloPoseSerializer::new())),
+            "pascalvoc" => Some(RegistrySerializer::PascalVoc(PascalVocSerializer::new())),
+            "yolodarknet" => Some(RegistrySerializer::YoloDarknet(YoloDarknetSerializer::new())),
             _ => None,
         }
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<String, Format> {
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, Format> {
         self.formats.iter()
     }
 }
@@ -7,11 +7,25 @@
 
 mod format_registry;
 
-use std::{ffi::OsString, path::{PathBuf, Path}};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use format_registry::FormatRegistry;
-use labelswap_data::{conversion_pipeline::ConversionPipeline, models::Format, transforms::RequiredTransformations};
+use indicatif::{ProgressBar, ProgressStyle};
+use labelswap_data::{
+    class_map::load_class_map,
+    conversion_pipeline::{ConversionPipeline, ErrorPolicy},
+    conversion_progress::{CancellationToken, ConversionProgress},
+    conversion_report::ConversionReport,
+    models::{format::SourceType, Format, Winding},
+    parser::FormatParser,
+    serializer::FormatSerializer,
+    transforms::{
+        AnnotationFilter, BoxPolicy, ClassOrder, ClassRemap, ClassRules, FilterAction,
+        MappingGenerator, OrderCorners, Sanitize, TransformStage,
+    },
+};
 
 #[derive(Debug, Parser)]
 #[command(name="bounding-marker")]
@@ -27,22 +41,122 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    Convert {
-        #[arg(long)]
-        source_format: String,
-        #[arg(long)]
-        target_format: String,
-
-        #[arg(long)]
-        image_directory: Option<PathBuf>,
-        #[arg(long)]
-        class_mappings: Option<PathBuf>,
-
-        #[arg(long)]
-        source: PathBuf,
-        #[arg(long)]
-        target: PathBuf,
-    }
+    Convert(ConvertArgs),
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    #[arg(long)]
+    source_format: String,
+    #[arg(long)]
+    target_format: String,
+
+    #[arg(long)]
+    image_directory: Option<PathBuf>,
+    /// A class mapping file: a darknet .labels or .names file, a data.yaml
+    /// file, or a CSV or JSON file from ids to names
+    #[arg(long)]
+    class_mappings: Option<PathBuf>,
+    /// Assigns class ids from the class names in the source when the
+    /// conversion needs them and no mapping was given
+    #[arg(long, value_enum)]
+    generate_classes: Option<ClassOrderArg>,
+    /// A file with one class name per line, whose classes get the first ids
+    /// when generating them
+    #[arg(long)]
+    class_seed: Option<PathBuf>,
+    /// Where to write the generated or remapped classes. Defaults to
+    /// classes.txt, or darknet.labels for YOLO Darknet, next to the target
+    #[arg(long)]
+    classes_output: Option<PathBuf>,
+    /// A TOML or JSON file with rules to rename, merge and drop classes.
+    /// The remaining classes get new contiguous ids
+    #[arg(long)]
+    class_rules: Option<PathBuf>,
+    /// Clips annotations to the image bounds, fixes NaN and infinite
+    /// coordinates and drops boxes with a zero width or height
+    #[arg(long)]
+    sanitize: bool,
+    /// Drops boxes narrower or shorter than this many pixels. Implies
+    /// --sanitize
+    #[arg(long)]
+    min_box_size: Option<f64>,
+    /// Puts the corners of every annotation in the canonical order:
+    /// clockwise from the top-left-most corner
+    #[arg(long)]
+    order_corners: bool,
+    /// The order in which oriented formats write the corners, starting from
+    /// the top-left-most one. By default they are written as read
+    #[arg(long, value_enum)]
+    winding: Option<WindingArg>,
+    /// The number of values per keypoint in YOLO pose labels: 3 for x, y and
+    /// visibility, the default, or 2 for x and y only
+    #[arg(long, value_parser = clap::value_parser!(u8).range(2..=3))]
+    keypoint_dims: Option<u8>,
+    /// Keeps only the annotations matching an expression, such as
+    /// "class in [person, dog] and area_px > 64 and not difficult"
+    #[arg(long)]
+    keep_if: Option<String>,
+    /// Drops the annotations matching an expression
+    #[arg(long)]
+    drop_if: Option<String>,
+    /// Keeps the image ids of this COCO JSON file when the target format
+    /// needs ids the source format does not have
+    #[arg(long)]
+    image_ids: Option<PathBuf>,
+    /// What to do with oriented boxes when the target format can only
+    /// store axis aligned boxes
+    #[arg(long, value_enum, default_value_t = BoxPolicyArg::Enclosing)]
+    box_policy: BoxPolicyArg,
+
+    #[arg(long)]
+    source: PathBuf,
+    #[arg(long)]
+    target: PathBuf,
+
+    /// What to do when an annotation can not be converted
+    #[arg(long, value_enum, default_value_t = OnError::Abort)]
+    on_error: OnError,
+    /// Number of errors tolerated when using --on-error=collect
+    #[arg(long, default_value_t = 100)]
+    max_errors: usize,
+    /// Writes the conversion report to the given JSON file
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Prints the transformations the conversion needs without converting
+    #[arg(long)]
+    dry_run: bool,
+    /// Reads source files and images using this many threads, 0 uses one
+    /// per CPU. Only formats stored across several files can be read in
+    /// parallel
+    #[arg(short, long)]
+    jobs: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ClassOrderArg {
+    Alphabetical,
+    FirstAppearance,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BoxPolicyArg {
+    Enclosing,
+    Inscribed,
+    Refuse,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum WindingArg {
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OnError {
+    Abort,
+    Skip,
+    Collect,
 }
 
 fn main() -> Result<(), String> {
@@ -57,50 +171,338 @@ fn main() -> Result<(), String> {
     }
 
     match args.commands {
-        Commands::Convert { 
-            source_format, 
-            target_format, 
-            image_directory, 
-            class_mappings, 
-            source, 
-            target 
-        } => {
-            handle_convert(
-                source_format, 
-                target_format, 
-                image_directory, 
-                class_mappings, 
-                source, 
-                target,
-                &registry
-            )?;
-        }
+        Commands::Convert(convert_args) => handle_convert(convert_args, &registry)?,
     }
     Ok(())
 }
 
-fn handle_convert(
-    source_format: String,
-    target_format: String,
-    image_directory: Option<PathBuf>,
-    class_mappings: Option<PathBuf>,
-    source: PathBuf,
-    target: PathBuf,
-    registry: &FormatRegistry,
-) -> Result<(), String> {
-    let source_format = match registry.lookup_format(&source_format) {
+fn handle_convert(args: ConvertArgs, registry: &FormatRegistry) -> Result<(), String> {
+    let source_format = match registry.lookup_format(&args.source_format) {
         Some(format) => format,
-        None => return Err(format!("{source_format} is not a valid format")),
+        None => return Err(format!("{} is not a valid format", args.source_format)),
     };
-    let target_format = match registry.lookup_format(&target_format) {
+    let target_format = match registry.lookup_format(&args.target_format) {
         Some(format) => format,
-        None => return Err(format!("{target_format} is not a valid format")),
+        None => return Err(format!("{} is not a valid format", args.target_format)),
     };
 
     let mut pipeline = ConversionPipeline::new(source_format, target_format);
-    pipeline.set_image_directory(image_directory);
+    pipeline.set_image_directory(args.image_directory);
+    pipeline.set_image_id_source(args.image_ids);
+    pipeline.set_box_policy(match args.box_policy {
+        BoxPolicyArg::Enclosing => BoxPolicy::Enclosing,
+        BoxPolicyArg::Inscribed => BoxPolicy::Inscribed,
+        BoxPolicyArg::Refuse => BoxPolicy::Refuse,
+    });
+    let mapping = match args.class_mappings.as_ref() {
+        Some(class_mappings) => Some(
+            load_class_map(class_mappings)
+                .map_err(|e| format!("Failed to read {}: {e}", class_mappings.display()))?,
+        ),
+        None => None,
+    };
+    // Class rules renumber the classes, so the mapping given no longer
+    // matches the classes written
+    let mut listed_classes = mapping.clone();
+    if let Some(class_rules) = args.class_rules.as_ref() {
+        let remap = ClassRules::load(class_rules)
+            .and_then(|rules| ClassRemap::new(rules, mapping.as_ref()))
+            .map_err(|e| format!("Failed to read {}: {e}", class_rules.display()))?;
+        listed_classes = Some(remap.classes().clone());
+        pipeline.add_transform(Box::new(remap), TransformStage::AfterRequired);
+    }
+    pipeline.set_mapping(mapping);
+    if args.sanitize || args.min_box_size.is_some() {
+        let mut sanitize = Sanitize::new();
+        sanitize.set_min_size(args.min_box_size.unwrap_or_default());
+        pipeline.add_transform(Box::new(sanitize), TransformStage::AfterRequired);
+    }
+    if args.order_corners {
+        pipeline.add_transform(Box::new(OrderCorners::default()), TransformStage::AfterRequired);
+    }
+    let filters = [
+        (args.keep_if.as_deref(), FilterAction::Keep),
+        (args.drop_if.as_deref(), FilterAction::Drop),
+    ];
+    for (expression, action) in filters {
+        if let Some(expression) = expression {
+            let filter = AnnotationFilter::new(expression, action)
+                .map_err(|e| format!("Invalid filter {expression}: {e}"))?;
+            pipeline.add_transform(Box::new(filter), TransformStage::AfterRequired);
+        }
+    }
+    let default_output = default_classes_output(&args.target, target_format);
+    let classes_output = args.classes_output.clone().unwrap_or(default_output.clone());
+    if args.generate_classes.is_some() || args.class_seed.is_some() {
+        let mut generator = MappingGenerator::new(class_order(
+            args.generate_classes,
+            args.class_seed.as_deref(),
+        )?);
+        // With class rules, the remapped classes are written instead
+        if args.class_rules.is_none() {
+            generator.set_output(Some(classes_output.clone()));
+        }
+        pipeline.set_mapping_generator(Some(generator));
+    }
+    pipeline.set_error_policy(match args.on_error {
+        OnError::Abort => ErrorPolicy::Abort,
+        OnError::Skip => ErrorPolicy::Skip,
+        OnError::Collect => ErrorPolicy::Collect(args.max_errors),
+    });
+
+    let plan = pipeline.plan().map_err(|e| e.to_string())?;
+    if args.dry_run {
+        print!("{plan}");
+        return Ok(());
+    }
+    if !plan.is_ready() {
+        eprint!("{plan}");
+        let missing: Vec<String> = plan.missing_inputs().iter().map(|i| i.to_string()).collect();
+        return Err(format!("The conversion requires {}", missing.join(" and ")));
+    }
+
+    let mut parser = match registry.get_format_parser(&source_format.id) {
+        Some(parser) => parser,
+        None => return Err(format!("{} can not be read yet", source_format.name)),
+    };
+    let mut serializer = match registry.get_format_serializer(&target_format.id) {
+        Some(serializer) => serializer,
+        None => return Err(format!("{} can not be written yet", target_format.name)),
+    };
+
+    if let Some(winding) = args.winding {
+        let winding = match winding {
+            WindingArg::Clockwise => Winding::Clockwise,
+            WindingArg::CounterClockwise => Winding::CounterClockwise,
+        };
+        if !serializer.set_winding(winding) {
+            eprintln!("{} does not store corners, --winding is ignored", target_format.name);
+        }
+    }
+
+    // Formats listing their classes write them to the default output, which
+    // must have a single writer. Generated classes are written by the
+    // generator, remapped ones by the format or after converting.
+    let generates_classes = pipeline
+        .needs_generated_mapping()
+        .map_err(|e| e.to_string())?;
+    if generates_classes && args.class_rules.is_none() && classes_output == default_output {
+        serializer.skip_class_list();
+    }
+    let serializer_lists_classes = match listed_classes {
+        Some(class_map) => serializer.set_class_map(class_map),
+        None => false,
+    };
+
+    if let Some(keypoint_dims) = args.keypoint_dims {
+        let visibility = keypoint_dims == 3;
+        let parser_set = parser.set_keypoint_visibility(visibility);
+        let serializer_set = serializer.set_keypoint_visibility(visibility);
+        if !parser_set && !serializer_set {
+            eprintln!("Neither format stores YOLO pose keypoints, --keypoint-dims is ignored");
+        }
+    }
+
+    parser
+        .init(&args.source)
+        .map_err(|e| format!("Failed to read {}: {e}", args.source.display()))?;
+    serializer
+        .init(&args.target)
+        .map_err(|e| format!("Failed to write {}: {e}", args.target.display()))?;
+
+    let progress_bar = ProgressBar::new_spinner();
+    let bar = progress_bar.clone();
+    pipeline.set_progress_callback(move |progress| show_progress(&bar, progress));
+
+    // Ctrl+C stops the conversion, keeping what was written so far
+    let token = CancellationToken::new();
+    let handler_token = token.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_token.cancel()) {
+        eprintln!("Conversions can not be cancelled: {e}");
+    }
+    pipeline.set_cancellation_token(Some(token));
+
+    let report = match (args.jobs, parser.as_multi_file()) {
+        (Some(jobs), Some(multi_file_parser)) => {
+            pipeline.set_threads(jobs);
+            pipeline.convert_parallel(multi_file_parser, serializer)
+        }
+        (Some(_), None) => {
+            eprintln!("{} can not be read in parallel", source_format.name);
+            pipeline.convert(&mut parser, serializer)
+        }
+        (None, _) => pipeline.convert(&mut parser, serializer),
+    }
+    .map_err(|e| format!("Conversion failed: {e}"));
+    progress_bar.finish_and_clear();
+    let report = report?;
+
+    print_report(&report);
+
+    // Formats that list their classes already wrote the remapped ones
+    let lists_remapped_classes = serializer_lists_classes && classes_output == default_output;
+    if args.class_rules.is_some() && !lists_remapped_classes {
+        let mut classes = report.remapped_classes.join("\n");
+        classes.push('\n');
+        std::fs::write(&classes_output, classes)
+            .map_err(|e| format!("Failed to write {}: {e}", classes_output.display()))?;
+    }
+
+    if let Some(report_path) = args.report {
+        let json = report
+            .to_json()
+            .map_err(|e| format!("Failed to serialize the report: {e}"))?;
+        std::fs::write(&report_path, json)
+            .map_err(|e| format!("Failed to write {}: {e}", report_path.display()))?;
+    }
     Ok(())
 }
 
+fn class_order(order: Option<ClassOrderArg>, seed: Option<&Path>) -> Result<ClassOrder, String> {
+    if let Some(seed) = seed {
+        let classes = std::fs::read_to_string(seed)
+            .map_err(|e| format!("Failed to read {}: {e}", seed.display()))?;
+        let classes = classes
+            .lines()
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+            .map(String::from)
+            .collect();
+        return Ok(ClassOrder::Seeded(classes));
+    }
+
+    Ok(match order {
+        Some(ClassOrderArg::FirstAppearance) => ClassOrder::FirstAppearance,
+        Some(ClassOrderArg::Alphabetical) | None => ClassOrder::Alphabetical,
+    })
+}
+
+/// Generated classes are written inside the target if it is a directory,
+/// or next to it otherwise.
+fn default_classes_output(target: &Path, target_format: &Format) -> PathBuf {
+    let file_name = match target_format.id.as_str() {
+        "yolodarknet" => "darknet.labels",
+        _ => "classes.txt",
+    };
+    match target_format.source_type {
+        SourceType::MultipleFiles => target.join(file_name),
+        SourceType::SingleFile => target.with_file_name(file_name),
+    }
+}
+
+fn show_progress(bar: &ProgressBar, progress: &ConversionProgress) {
+    let (position, length) = match (progress.total_annotations, progress.total_files) {
+        (Some(total), _) => (progress.annotations_processed, total),
+        (None, Some(total)) => (progress.files_processed, total),
+        (None, None) => {
+            bar.set_message(format!("{} annotations", progress.annotations_processed));
+            bar.tick();
+            return;
+        }
+    };
+
+    if bar.length() != Some(length as u64) {
+        bar.set_length(length as u64);
+        bar.set_style(
+            ProgressStyle::with_template("{wide_bar} {pos}/{len} {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+    }
+    bar.set_position(position as u64);
+    bar.set_message(format!("{} annotations", progress.annotations_processed));
+}
+
+fn print_report(report: &ConversionReport) {
+    println!("Annotations read:        {}", report.annotations_read);
+    println!("Annotations transformed: {}", report.annotations_transformed);
+    if report.annotations_replaced > 0 {
+        println!("Annotations replaced:    {}", report.annotations_replaced);
+    }
+    println!("Annotations dropped:     {}", report.annotations_dropped);
+    println!("Annotations filtered:    {}", report.annotations_filtered);
+    println!("Annotations written:     {}", report.annotations_written);
+    if report.empty_images_written > 0 {
+        println!("Empty images written:    {}", report.empty_images_written);
+    }
+    if report.empty_images_dropped > 0 {
+        println!("Empty images dropped:    {}", report.empty_images_dropped);
+    }
+    if report.cancelled {
+        println!("The conversion was cancelled before reading every annotation");
+    }
+
+    if !report.generated_classes.is_empty() {
+        println!("Generated classes: {}", report.generated_classes.join(", "));
+    }
+    if !report.remapped_classes.is_empty() {
+        println!("Remapped classes: {}", report.remapped_classes.join(", "));
+    }
+    for rule_count in report.class_rule_counts.iter() {
+        println!("Class rule {}: {} annotations", rule_count.rule, rule_count.annotations);
+    }
+    if !report.applied_transformations.is_empty() {
+        println!("Transformations: {:?}", report.applied_transformations);
+    }
+
+    for (class, count) in report.class_counts.iter() {
+        println!("  {class}: {count}");
+    }
+
+    for (reason, count) in report.filter_reasons.iter() {
+        println!("Filtered {count}: {reason}");
+    }
+
+    let mut geometry_changes = BTreeMap::new();
+    for fix in report.geometry_fixes.iter() {
+        *geometry_changes.entry(fix.change).or_insert(0) += 1;
+    }
+    for (change, count) in geometry_changes {
+        println!("Geometry {change:?}: {count}");
+    }
+
+    if let Some(alignment) = report.box_alignment.as_ref().filter(|a| a.boxes > 0) {
+        let change = alignment.area_change_percent();
+        eprintln!(
+            "Warning: {} oriented boxes replaced by {:?} axis aligned boxes, {} {:.1}% of their area",
+            alignment.boxes,
+            alignment.policy,
+            if change >= 0.0 { "gaining" } else { "losing" },
+            change.abs()
+        );
+    }
+
+    if report.dropped_segmentations > 0 {
+        eprintln!(
+            "Warning: {} segmentations replaced by their bounding boxes",
+            report.dropped_segmentations
+        );
+    }
+
+    for source in report.unmatched_images.iter() {
+        eprintln!("No image found for {}", source.display());
+    }
+
+    for error in report.errors.iter() {
+        match error.source_file.as_ref() {
+            Some(source) => eprintln!(
+                "{} error in annotation {} ({}): {}",
+                error.stage,
+                error.position,
+                source.display(),
+                error.message
+            ),
+            None => eprintln!(
+                "{} error in annotation {}: {}",
+                error.stage, error.position, error.message
+            ),
+        }
+    }
+}
+
 fn print_formats_in_registry(registry: &FormatRegistry) {
-}
\ No newline at end of file
+    let mut formats: Vec<_> = registry.iter().collect();
+    formats.sort_by_key(|(id, _)| id.as_str());
+    for (id, format) in formats {
+        println!("{id}\t{}", format.name);
+    }
+}
@@ -0,0 +1,283 @@
+/* class_map.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde_json::Value as JsonValue;
+use serde_yaml::Value as YamlValue;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClassMapError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("YAML Error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("JSON Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("CSV Error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Wrong format: {0}")]
+    WrongFormat(String),
+    #[error("Class mapping files with the .{0} extension are not supported")]
+    UnsupportedExtension(String),
+    #[error("The class id '{0}' is not a non-negative integer")]
+    InvalidId(String),
+    #[error("The class name '{0}' appears more than once")]
+    DuplicateName(String),
+    #[error("The class id {0} appears more than once")]
+    DuplicateId(u32),
+}
+
+/// ClassMap: a one-to-one mapping between class names and numeric class
+/// ids. Names and ids are looked up in separate indexes, so a class named
+/// "1" is never mistaken for the class with id 1.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassMap {
+    ids_by_name: HashMap<String, u32>,
+    names_by_id: BTreeMap<u32, String>,
+}
+
+impl ClassMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A map in which the id of every class is its position in `names`.
+    pub fn from_names<I, S>(names: I) -> Result<Self, ClassMapError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut map = Self::new();
+        for (id, name) in names.into_iter().enumerate() {
+            let id = u32::try_from(id).map_err(|_| ClassMapError::InvalidId(id.to_string()))?;
+            map.insert(id, name)?;
+        }
+        Ok(map)
+    }
+
+    /// Adds a class. Fails if the name is empty, or if the name or the id
+    /// already belong to a class.
+    pub fn insert(&mut self, id: u32, name: impl Into<String>) -> Result<(), ClassMapError> {
+        let name = name.into();
+        if name.is_empty() {
+            return Err(ClassMapError::WrongFormat(format!(
+                "Class {id} has an empty name"
+            )));
+        }
+        if self.names_by_id.contains_key(&id) {
+            return Err(ClassMapError::DuplicateId(id));
+        }
+        if self.ids_by_name.contains_key(&name) {
+            return Err(ClassMapError::DuplicateName(name));
+        }
+
+        self.ids_by_name.insert(name.clone(), id);
+        self.names_by_id.insert(id, name);
+        Ok(())
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids_by_name.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.names_by_id.get(&id).map(String::as_str)
+    }
+
+    /// The classes as (id, name) pairs, sorted by id.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.names_by_id
+            .iter()
+            .map(|(id, name)| (*id, name.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names_by_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names_by_id.is_empty()
+    }
+}
+
+/// Reads a class mapping file.
+///
+/// The format is chosen from the file extension:
+/// - `.labels`, `.names` and `.txt`: one class name per line, the line
+///   number (starting at 0) being its id, as used by YOLO Darknet.
+/// - `.yaml` and `.yml`: the `names` entry of an Ultralytics `data.yaml`
+///   file, either a list of names or a map from ids to names.
+/// - `.csv`: two columns, the id and the name. A header row with `id` and
+///   `name` may give them in the other order.
+/// - `.json`: an object from ids to names or from names to ids, or a list
+///   of names.
+///
+/// Fails if an id is not a non-negative integer, or if a name or an id
+/// appears more than once.
+pub fn load_class_map(path: impl AsRef<Path>) -> Result<ClassMap, ClassMapError> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let parse = match extension.as_str() {
+        "labels" | "names" | "txt" => parse_lines,
+        "yaml" | "yml" => parse_yaml,
+        "csv" => parse_csv,
+        "json" => parse_json,
+        _ => return Err(ClassMapError::UnsupportedExtension(extension)),
+    };
+
+    let contents = std::fs::read_to_string(path)?;
+    let mut map = ClassMap::new();
+    for (id, name) in parse(&contents)? {
+        let id = id
+            .trim()
+            .parse()
+            .map_err(|_| ClassMapError::InvalidId(id.clone()))?;
+        map.insert(id, name)?;
+    }
+    Ok(map)
+}
+
+fn parse_lines(contents: &str) -> Result<Vec<(String, String)>, ClassMapError> {
+    let lines: Vec<&str> = contents.trim_end().lines().map(str::trim).collect();
+    if lines.iter().all(|line| line.is_empty()) {
+        return Ok(Vec::new());
+    }
+
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if name.is_empty() {
+                return Err(ClassMapError::WrongFormat(format!(
+                    "Line {} is empty, expected a class name",
+                    index + 1
+                )));
+            }
+            Ok((index.to_string(), name.to_string()))
+        })
+        .collect()
+}
+
+fn parse_yaml(contents: &str) -> Result<Vec<(String, String)>, ClassMapError> {
+    let document: YamlValue = serde_yaml::from_str(contents)?;
+    let names = document.get("names").ok_or(ClassMapError::WrongFormat(
+        "Expected a 'names' entry".into(),
+    ))?;
+
+    let yaml_string = |value: &YamlValue| match value {
+        YamlValue::String(string) => Some(string.trim().to_string()),
+        YamlValue::Number(number) => Some(number.to_string()),
+        _ => None,
+    };
+
+    match names {
+        YamlValue::Sequence(names) => names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                let name = yaml_string(name).ok_or(ClassMapError::WrongFormat(format!(
+                    "Expected class {id} to be a string"
+                )))?;
+                Ok((id.to_string(), name))
+            })
+            .collect(),
+        YamlValue::Mapping(names) => names
+            .iter()
+            .map(|(id, name)| match (yaml_string(id), yaml_string(name)) {
+                (Some(id), Some(name)) => Ok((id, name)),
+                _ => Err(ClassMapError::WrongFormat(
+                    "Expected 'names' to map ids to names".into(),
+                )),
+            })
+            .collect(),
+        _ => Err(ClassMapError::WrongFormat(
+            "Expected 'names' to be a list or a map".into(),
+        )),
+    }
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<(String, String)>, ClassMapError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(contents.as_bytes());
+
+    let mut records = reader.records().peekable();
+
+    // (id, name) column positions
+    let mut columns = (0, 1);
+    if let Some(Ok(header)) = records.peek() {
+        let header: Vec<String> = header.iter().map(str::to_lowercase).collect();
+        let header: Vec<&str> = header.iter().map(String::as_str).collect();
+        match header.as_slice() {
+            ["id", "name"] => {
+                records.next();
+            }
+            ["name", "id"] => {
+                columns = (1, 0);
+                records.next();
+            }
+            _ => {}
+        }
+    }
+
+    records
+        .map(|record| {
+            let record = record?;
+            if record.len() != 2 {
+                return Err(ClassMapError::WrongFormat(format!(
+                    "Expected 2 columns, but got {} in line {}",
+                    record.len(),
+                    record.position().map(|p| p.line()).unwrap_or_default()
+                )));
+            }
+            Ok((record[columns.0].to_string(), record[columns.1].to_string()))
+        })
+        .collect()
+}
+
+fn parse_json(contents: &str) -> Result<Vec<(String, String)>, ClassMapError> {
+    let document: JsonValue = serde_json::from_str(contents)?;
+    match document {
+        JsonValue::Array(names) => names
+            .into_iter()
+            .enumerate()
+            .map(|(id, name)| match name {
+                JsonValue::String(name) => Ok((id.to_string(), name.trim().to_string())),
+                _ => Err(ClassMapError::WrongFormat(format!(
+                    "Expected class {id} to be a string"
+                ))),
+            })
+            .collect(),
+        JsonValue::Object(map) => {
+            // Numeric values are ids, so the keys are the names
+            let names_to_ids = !map.is_empty() && map.values().all(JsonValue::is_u64);
+            map.into_iter()
+                .map(|(key, value)| match value {
+                    JsonValue::String(name) if !names_to_ids => {
+                        Ok((key.trim().to_string(), name.trim().to_string()))
+                    }
+                    JsonValue::Number(id) if names_to_ids => {
+                        Ok((id.to_string(), key.trim().to_string()))
+                    }
+                    _ => Err(ClassMapError::WrongFormat(format!(
+                        "Expected every value to be a class name or every value to be an id, got {value}"
+                    ))),
+                })
+                .collect()
+        }
+        _ => Err(ClassMapError::WrongFormat(
+            "Expected an object or a list".into(),
+        )),
+    }
+}
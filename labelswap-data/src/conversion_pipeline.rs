@@ -5,30 +5,250 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::models::Format;
+use crate::class_map::ClassMap;
+use crate::conversion_plan::{ConversionPlan, PipelineInput};
+use crate::conversion_progress::{CancellationToken, ConversionProgress};
+use crate::conversion_report::ConversionReport;
+use crate::models::{Annotation, Format, ImageRecord};
+use crate::models::annotation::ClassRepresentation;
+use crate::parser::{FormatParser, MultiFileParser, ParserError};
+use crate::serializer::FormatSerializer;
+use crate::transforms::scheduler::schedule;
 use crate::transforms::*;
-use std::cell::Cell;
-use std::collections::HashMap;
+use crate::worker_pool::WorkerPool;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Result};
 
+/// What the pipeline does when a single annotation fails to be parsed,
+/// transformed or serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop the conversion on the first error.
+    #[default]
+    Abort,
+    /// Add the error to the report and continue with the next annotation.
+    Skip,
+    /// Skip up to the given number of failing annotations. The conversion
+    /// fails on the next error, returning every error that was collected.
+    Collect(usize),
+}
+
+/// The step of the conversion in which an annotation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ConversionStage {
+    Parse,
+    Transform,
+    Serialize,
+}
+
+impl Display for ConversionStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stage = match self {
+            Self::Parse => "Parse",
+            Self::Transform => "Transform",
+            Self::Serialize => "Serialize",
+        };
+        f.write_str(stage)
+    }
+}
+
+/// An error that affected a single annotation.
+#[derive(thiserror::Error, Debug)]
+#[error("{stage} error in annotation {position} ({}): {error}", display_source(.source_file))]
+pub struct AnnotationError {
+    pub stage: ConversionStage,
+    /// The file the annotation comes from, if the parser knows it
+    pub source_file: Option<PathBuf>,
+    /// Position of the annotation in the parser stream, starting at 0
+    pub position: usize,
+    pub error: anyhow::Error,
+}
+
+fn display_source(source_file: &Option<PathBuf>) -> String {
+    match source_file {
+        Some(source_file) => source_file.display().to_string(),
+        None => String::from("unknown source"),
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConversionError {
+    #[error("{0}")]
+    Annotation(AnnotationError),
+    #[error("Conversion stopped after {} errors", .0.len())]
+    TooManyErrors(Vec<AnnotationError>),
+    #[error("The conversion is missing inputs:\n{0}")]
+    IncompletePlan(ConversionPlan),
+    #[error("No valid order exists for the transforms: {}", .0.join(", "))]
+    UnorderedTransforms(Vec<String>),
+}
+
+/// A transform in the chain run by the pipeline.
+struct ChainedTransform {
+    transform: Box<dyn Transform>,
+    stage: TransformStage,
+    /// The transformation this transform implements, if it was added by the
+    /// pipeline itself
+    required: Option<RequiredTransformations>,
+}
+
+impl ChainedTransform {
+    /// Position of the transform's group in the chain: transforms running
+    /// before the required ones, the required ones and the ones after them.
+    fn group(&self) -> usize {
+        match (self.required, self.stage) {
+            (Some(_), _) => 1,
+            (None, TransformStage::BeforeRequired) => 0,
+            (None, TransformStage::AfterRequired) => 2,
+        }
+    }
+}
+
+/// The annotations resulting from running the chain on a single
+/// annotation.
+#[derive(Default)]
+struct ChainOutput {
+    annotations: Vec<Annotation>,
+    /// Reasons of the annotations that were dropped
+    filter_reasons: Vec<String>,
+    /// Number of annotations that were replaced
+    replaced: usize,
+}
+
+/// Annotations transformed together by convert_parallel, along with their
+/// position in the parser stream.
+#[derive(Default)]
+struct Batch {
+    positions: Vec<usize>,
+    annotations: Vec<Annotation>,
+}
+
+impl Batch {
+    fn push(&mut self, position: usize, annotation: Annotation) {
+        self.positions.push(position);
+        self.annotations.push(annotation);
+    }
+}
+
+/// A parser whose annotations were all read ahead of the conversion, e.g.
+/// to find the classes of the source. It returns them again, along with
+/// the file each one came from.
+struct ReadAhead<'p, P: ?Sized> {
+    parser: &'p mut P,
+    buffer: VecDeque<(Result<Annotation, ParserError>, Option<PathBuf>)>,
+    current_source_file: Option<PathBuf>,
+}
+
+impl<'p, P: FormatParser + ?Sized> ReadAhead<'p, P> {
+    /// Reads every annotation of `parser`.
+    fn read_all(parser: &'p mut P) -> Self {
+        let mut buffer = VecDeque::new();
+        while parser.has_next() {
+            let result = parser.get_next();
+            buffer.push_back((result, parser.current_source_file()));
+        }
+        Self {
+            parser,
+            buffer,
+            current_source_file: None,
+        }
+    }
+
+    fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        self.buffer.iter().filter_map(|(result, _)| result.as_ref().ok())
+    }
+}
+
+impl<P: FormatParser + ?Sized> FormatParser for ReadAhead<'_, P> {
+    fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
+        self.buffer.clear();
+        self.parser.init(path)
+    }
+
+    fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        let (result, source_file) = self.buffer.pop_front().ok_or(ParserError::OutOfElements)?;
+        self.current_source_file = source_file;
+        result
+    }
+
+    fn has_next(&mut self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_source_file.clone()
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.parser.total_files()
+    }
+
+    fn remaining_annotations(&self) -> Option<usize> {
+        Some(self.buffer.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        self.parser.take_empty_images()
+    }
+}
+
+/// The class names of `annotations`, in the order they are first found.
+fn class_names<'i>(annotations: impl Iterator<Item = &'i Annotation>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut names = Vec::new();
+    for annotation in annotations {
+        if let ClassRepresentation::ClassName(name) = annotation.class.as_ref() {
+            if seen.insert(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+type ProgressCallback<'a> = Box<dyn FnMut(&ConversionProgress) + 'a>;
+
+/// Number of source files read at the same time by convert_parallel
+const FILES_PER_BATCH: usize = 256;
+
 pub struct ConversionPipeline<'a> {
-    transforms: Vec<Box<dyn Transform>>,
+    transforms: Vec<ChainedTransform>,
+    applied_transformations: Vec<RequiredTransformations>,
     source_format: &'a Format,
     target_format: &'a Format,
     image_directory: Option<PathBuf>,
-    mapping: Cell<Option<HashMap<String, String>>>,
+    mapping: Option<ClassMap>,
+    mapping_generator: Option<MappingGenerator>,
+    /// The mapping built by mapping_generator for the current conversion
+    generated_mapping: Option<ClassMap>,
+    image_id_source: Option<PathBuf>,
+    box_policy: BoxPolicy,
+    error_policy: ErrorPolicy,
+    threads: usize,
+    progress_callback: Option<ProgressCallback<'a>>,
+    cancellation_token: Option<CancellationToken>,
 }
 
 impl<'a> ConversionPipeline<'a> {
     pub fn new(source_format: &'a Format, target_format: &'a Format) -> Self {
         Self {
             transforms: Vec::new(),
+            applied_transformations: Vec::new(),
             source_format,
             target_format,
             image_directory: None,
-            mapping: Cell::new(None),
+            mapping: None,
+            mapping_generator: None,
+            generated_mapping: None,
+            image_id_source: None,
+            box_policy: BoxPolicy::default(),
+            error_policy: ErrorPolicy::default(),
+            threads: 0,
+            progress_callback: None,
+            cancellation_token: None,
         }
     }
 
@@ -40,57 +260,773 @@ impl<'a> ConversionPipeline<'a> {
         self.image_directory = image_directory;
     }
 
-    pub fn set_mapping(&mut self, mapping: Option<HashMap<String, String>>) {
-        self.mapping.set(mapping);
+    pub fn set_mapping(&mut self, mapping: Option<ClassMap>) {
+        self.mapping = mapping;
+    }
+
+    /// Sets how to build a class mapping from the source when the
+    /// conversion needs class ids and no mapping was set. The classes are
+    /// found by reading the whole source before converting it.
+    pub fn set_mapping_generator(&mut self, generator: Option<MappingGenerator>) {
+        self.mapping_generator = generator;
+    }
+
+    /// Sets a COCO JSON file whose image ids are kept when the conversion
+    /// has to assign ids to images. Images that are not in it get new ids.
+    pub fn set_image_id_source(&mut self, image_id_source: Option<PathBuf>) {
+        self.image_id_source = image_id_source;
+    }
+
+    /// Sets what to do with oriented boxes when the target format can only
+    /// store axis aligned ones.
+    pub fn set_box_policy(&mut self, box_policy: BoxPolicy) {
+        self.box_policy = box_policy;
     }
 
-    pub fn convert(&mut self) -> Result<()> {
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+
+    pub fn set_error_policy(&mut self, error_policy: ErrorPolicy) {
+        self.error_policy = error_policy;
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets the number of threads used by convert_parallel. If it is 0, one
+    /// thread per CPU is used.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads;
+    }
+
+    /// Sets a function called as the conversion advances: after every
+    /// annotation with convert, and after every batch of files with
+    /// convert_parallel. It runs on the thread doing the conversion, so it
+    /// should return quickly.
+    pub fn set_progress_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&ConversionProgress) + 'a,
+    {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    /// Sets a token that stops the conversion once it is cancelled. The
+    /// annotations written until then are kept and the serializer is
+    /// finished, so that the output is valid.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation_token = token;
+    }
+
+    /// Adds a transform to the chain, besides the ones required by the
+    /// conversion. Transforms added to the same stage run in the order they
+    /// were added. The transform is told its stage through
+    /// Transform::set_stage.
+    pub fn add_transform(&mut self, mut transform: Box<dyn Transform>, stage: TransformStage) {
+        transform.set_stage(stage);
+        self.transforms.push(ChainedTransform {
+            transform,
+            stage,
+            required: None,
+        });
+    }
+
+    /// Lists the transformations the conversion needs, in the order they
+    /// run, along with the inputs that still have to be set for them.
+    /// Nothing is read from disk.
+    ///
+    /// Fails with a ConversionError if the transformations depend on each
+    /// other in a way that no order satisfies.
+    pub fn plan(&self) -> Result<ConversionPlan> {
+        let mut available_inputs = Vec::new();
+        if self.image_directory.is_some() {
+            available_inputs.push(PipelineInput::ImageDirectory);
+        }
+        if self.mapping.is_some() || self.can_generate_mapping() {
+            available_inputs.push(PipelineInput::ClassMapping);
+        }
+        let plan = ConversionPlan::new(self.source_format, self.target_format, &available_inputs)?;
+        Ok(plan)
+    }
+
+    /// Converts every annotation returned by `parser` and writes it using
+    /// `serializer`. Both of them must have been initialized beforehand.
+    ///
+    /// Each annotation goes through the transforms required to go from the
+    /// source format to the target format, along with the ones added with
+    /// add_transform. A transform may drop an annotation, in which case it is
+    /// not written. Once the parser runs out of annotations, the images
+    /// without annotations it found are written and the serializer is
+    /// finished.
+    ///
+    /// Errors affecting a single annotation are handled according to the
+    /// ErrorPolicy. When the conversion stops because of them, the returned
+    /// error is a ConversionError.
+    pub fn convert<P, S>(&mut self, parser: &mut P, serializer: S) -> Result<ConversionReport>
+    where
+        P: FormatParser,
+        S: FormatSerializer,
+    {
+        self.generated_mapping = None;
+        if !self.needs_generated_mapping()? {
+            return self.convert_stream(parser, serializer);
+        }
+
+        let mut parser = ReadAhead::read_all(parser);
+        let classes = self.generate_mapping(class_names(parser.annotations()))?;
+        let mut report = self.convert_stream(&mut parser, serializer)?;
+        report.generated_classes = classes;
+        Ok(report)
+    }
+
+    fn convert_stream<P, S>(&mut self, parser: &mut P, mut serializer: S) -> Result<ConversionReport>
+    where
+        P: FormatParser + ?Sized,
+        S: FormatSerializer,
+    {
         self.configure()?;
-        todo!();
+
+        let mut report = ConversionReport {
+            applied_transformations: self.applied_transformations.clone(),
+            ..ConversionReport::default()
+        };
+        let mut collected = Vec::new();
+        let mut progress = ConversionProgress {
+            total_files: parser.total_files(),
+            total_annotations: parser.remaining_annotations(),
+            ..ConversionProgress::default()
+        };
+        let mut current_file = None;
+
+        let mut position = 0;
+        while parser.has_next() {
+            if self.is_cancelled() {
+                report.cancelled = true;
+                break;
+            }
+
+            let current_position = position;
+            position += 1;
+
+            let result = parser.get_next();
+            let source_file = parser.current_source_file();
+            if source_file != current_file {
+                if current_file.is_some() {
+                    progress.files_processed += 1;
+                }
+                current_file = source_file.clone();
+            }
+
+            match result {
+                Ok(annotation) => {
+                    report.annotations_read += 1;
+                    self.convert_annotation(
+                        &mut serializer,
+                        current_position,
+                        annotation,
+                        &mut report,
+                        &mut collected,
+                    )?;
+                }
+                Err(e) => {
+                    let error = AnnotationError {
+                        stage: ConversionStage::Parse,
+                        source_file,
+                        position: current_position,
+                        error: e.into(),
+                    };
+                    self.handle_error(&mut report, &mut collected, error)?;
+                }
+            }
+
+            progress.annotations_processed += 1;
+            self.notify_progress(&progress);
+        }
+
+        if !report.cancelled {
+            // The last file is only complete if the parser ran out of
+            // annotations, files without any are only known by then
+            let empty_images = parser.take_empty_images();
+            let finished_files: HashSet<&PathBuf> = current_file
+                .iter()
+                .chain(empty_images.iter().filter_map(|image| image.source_file.as_ref()))
+                .collect();
+            if !finished_files.is_empty() {
+                progress.files_processed += finished_files.len();
+                self.notify_progress(&progress);
+            }
+
+            self.write_empty_images(
+                &mut serializer,
+                position,
+                empty_images,
+                &mut report,
+                &mut collected,
+            )?;
+        }
+
+        serializer.finish()?;
+
+        for chained in self.transforms.iter() {
+            chained.transform.report(&mut report);
+        }
+        Ok(report)
     }
 
-    fn configure(&mut self) -> Result<()> {
-        let transformations = self.source_format.check_compatibility(&self.target_format);
-        if transformations.contains(&RequiredTransformations::Denormalize) {
-            match self.image_directory.as_ref() {
-                Some(image_directory) => {
-                    let denorm = Denormalize::new(PathBuf::from(image_directory))?;
-                    self.transforms.push(Box::new(denorm));
+    /// Runs the chain on an annotation and writes the result.
+    fn convert_annotation<S: FormatSerializer>(
+        &mut self,
+        serializer: &mut S,
+        position: usize,
+        annotation: Annotation,
+        report: &mut ConversionReport,
+        collected: &mut Vec<AnnotationError>,
+    ) -> Result<(), ConversionError> {
+        let source_file = annotation.source_file.clone();
+        let output = match self.apply_transforms(annotation) {
+            Ok(output) => output,
+            Err(e) => {
+                let error = AnnotationError {
+                    stage: ConversionStage::Transform,
+                    source_file,
+                    position,
+                    error: e,
+                };
+                return self.handle_error(report, collected, error);
+            }
+        };
+
+        report.annotations_replaced += output.replaced;
+        for reason in output.filter_reasons {
+            report.count_filtered(reason);
+        }
+        report.annotations_transformed += output.annotations.len();
+
+        for annotation in output.annotations {
+            self.write(serializer, position, annotation, report, collected)?;
+        }
+        Ok(())
+    }
+
+    /// Converts the annotations in every file of `parser` like convert does,
+    /// reading the files and the images they need using several threads.
+    /// The parser must have been initialized.
+    ///
+    /// Files are converted in batches. Each transform is applied to every
+    /// annotation in the batch before the next transform runs, which lets
+    /// them read images in parallel. The annotations are written in the same
+    /// order convert would write them. Files without annotations are written
+    /// last, as images without annotations.
+    pub fn convert_parallel<P, S>(
+        &mut self,
+        parser: &P,
+        mut serializer: S,
+    ) -> Result<ConversionReport>
+    where
+        P: MultiFileParser + ?Sized,
+        S: FormatSerializer,
+    {
+        let pool = WorkerPool::new(self.threads)?;
+        let files = parser.source_files()?;
+
+        self.generated_mapping = None;
+        let mut classes = Vec::new();
+        if self.needs_generated_mapping()? {
+            let found = pool.map(&files, |file| {
+                let annotations = parser.parse_file(file);
+                class_names(annotations.iter().filter_map(|result| result.as_ref().ok()))
+            });
+            let mut seen = HashSet::new();
+            let found = found
+                .into_iter()
+                .flatten()
+                .filter(|name| seen.insert(name.clone()))
+                .collect();
+            classes = self.generate_mapping(found)?;
+        }
+
+        self.configure()?;
+
+        let mut report = ConversionReport {
+            applied_transformations: self.applied_transformations.clone(),
+            ..ConversionReport::default()
+        };
+        let mut collected = Vec::new();
+        report.generated_classes = classes;
+
+        let mut progress = ConversionProgress {
+            total_files: Some(files.len()),
+            ..ConversionProgress::default()
+        };
+
+        let mut position = 0;
+        let mut empty_images = Vec::new();
+        'batches: for files in files.chunks(FILES_PER_BATCH) {
+            if self.is_cancelled() {
+                report.cancelled = true;
+                break;
+            }
+
+            // Workers skip their files once the conversion is cancelled, and
+            // the rest of the batch is dropped
+            let token = self.cancellation_token.clone();
+            let parsed = pool.map(files, |file| {
+                if token.as_ref().is_some_and(|token| token.is_cancelled()) {
+                    return Vec::new();
+                }
+                parser.parse_file(file)
+            });
+            if self.is_cancelled() {
+                report.cancelled = true;
+                break;
+            }
+            let parsed_count: usize = parsed.iter().map(Vec::len).sum();
+
+            let mut batch = Batch::default();
+            for (file, results) in files.iter().zip(parsed) {
+                if results.is_empty() {
+                    empty_images.push(parser.empty_image(file));
                 }
-                None => return Err(anyhow!("Expected image directory to be Some")),
+
+                for result in results {
+                    let current_position = position;
+                    position += 1;
+
+                    match result {
+                        Ok(annotation) => {
+                            report.annotations_read += 1;
+                            batch.push(current_position, annotation);
+                        }
+                        Err(e) => {
+                            let error = AnnotationError {
+                                stage: ConversionStage::Parse,
+                                source_file: Some(file.clone()),
+                                position: current_position,
+                                error: e.into(),
+                            };
+                            self.handle_error(&mut report, &mut collected, error)?;
+                        }
+                    }
+                }
+            }
+
+            let batch = self.transform_batch(batch, &pool, &mut report, &mut collected)?;
+            if self.is_cancelled() {
+                report.cancelled = true;
+                break;
+            }
+            report.annotations_transformed += batch.annotations.len();
+            for (position, annotation) in batch.positions.into_iter().zip(batch.annotations) {
+                if self.is_cancelled() {
+                    report.cancelled = true;
+                    break 'batches;
+                }
+                self.write(&mut serializer, position, annotation, &mut report, &mut collected)?;
+            }
+
+            progress.files_processed += files.len();
+            progress.annotations_processed += parsed_count;
+            self.notify_progress(&progress);
+        }
+
+        if !report.cancelled {
+            self.write_empty_images(
+                &mut serializer,
+                position,
+                empty_images,
+                &mut report,
+                &mut collected,
+            )?;
+        }
+
+        serializer.finish()?;
+
+        for chained in self.transforms.iter() {
+            chained.transform.report(&mut report);
+        }
+        Ok(report)
+    }
+
+    /// Whether the mapping generator can provide the mapping the conversion
+    /// needs. Class names can not be generated from class ids.
+    fn can_generate_mapping(&self) -> bool {
+        self.mapping_generator.is_some()
+            && !self
+                .source_format
+                .check_compatibility(self.target_format)
+                .contains(&RequiredTransformations::MapToName)
+    }
+
+    /// Whether the conversion needs a class mapping that has to be
+    /// generated before converting, in which case the mapping generator
+    /// writes the classes to its output.
+    pub fn needs_generated_mapping(&self) -> Result<bool> {
+        if self.mapping.is_some() || !self.can_generate_mapping() {
+            return Ok(false);
+        }
+        let plan = self.plan()?;
+        Ok(plan
+            .steps
+            .iter()
+            .any(|step| step.transformation == RequiredTransformations::MapToId))
+    }
+
+    /// Builds the mapping from the class names found in the source and
+    /// writes it to the generator's output. Returns the classes ordered by
+    /// id.
+    fn generate_mapping(&mut self, found: Vec<String>) -> Result<Vec<String>> {
+        let generator = self
+            .mapping_generator
+            .as_ref()
+            .ok_or(anyhow!("Expected mapping generator to be Some"))?;
+        let classes = generator.class_list(found);
+        generator
+            .write(&classes)
+            .map_err(|e| anyhow!("Failed to write the generated classes: {e}"))?;
+        self.generated_mapping = Some(MappingGenerator::mapping(&classes)?);
+        Ok(classes)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    fn notify_progress(&mut self, progress: &ConversionProgress) {
+        if let Some(callback) = self.progress_callback.as_mut() {
+            callback(progress);
+        }
+    }
+
+    /// Runs every transform on the whole batch, one transform after the
+    /// other, keeping the annotations in order. An empty batch is returned
+    /// once the conversion is cancelled.
+    fn transform_batch(
+        &mut self,
+        mut batch: Batch,
+        pool: &WorkerPool,
+        report: &mut ConversionReport,
+        collected: &mut Vec<AnnotationError>,
+    ) -> Result<Batch, ConversionError> {
+        for index in 0..self.transforms.len() {
+            if self.is_cancelled() {
+                return Ok(Batch::default());
             }
-        } else if transformations.contains(&RequiredTransformations::Normalize) {
-            match self.image_directory.as_ref() {
-                Some(image_directory) => {
-                    let norm = Normalize::new(PathBuf::from(image_directory))?;
-                    self.transforms.push(Box::new(norm));
+            self.transforms[index]
+                .transform
+                .prepare(&batch.annotations, pool);
+
+            let mut output = Batch::default();
+            for (position, mut annotation) in batch.positions.into_iter().zip(batch.annotations) {
+                if self.is_cancelled() {
+                    return Ok(Batch::default());
                 }
-                None => return Err(anyhow!("Expected image directory to be Some")),
+                let outcome = self.transforms[index].transform.apply(
+                    &mut annotation,
+                    self.source_format,
+                    self.target_format,
+                );
+                match outcome {
+                    Ok(TransformOutcome::Keep) => output.push(position, annotation),
+                    Ok(TransformOutcome::Drop(reason)) => report.count_filtered(reason),
+                    Ok(TransformOutcome::Replace(replacements)) => {
+                        report.annotations_replaced += 1;
+                        for replacement in replacements {
+                            output.push(position, replacement);
+                        }
+                    }
+                    Err(e) => {
+                        let error = AnnotationError {
+                            stage: ConversionStage::Transform,
+                            source_file: annotation.source_file,
+                            position,
+                            error: e,
+                        };
+                        self.handle_error(report, collected, error)?;
+                    }
+                }
+            }
+            batch = output;
+        }
+        Ok(batch)
+    }
+
+    /// Pushes an annotation, along with its position in the parser stream,
+    /// to the serializer.
+    fn write<S: FormatSerializer>(
+        &self,
+        serializer: &mut S,
+        position: usize,
+        annotation: Annotation,
+        report: &mut ConversionReport,
+        collected: &mut Vec<AnnotationError>,
+    ) -> Result<(), ConversionError> {
+        let source_file = annotation.source_file.clone();
+        let class = ConversionReport::class_key(&annotation);
+        if let Err(e) = serializer.push(annotation) {
+            let error = AnnotationError {
+                stage: ConversionStage::Serialize,
+                source_file,
+                position,
+                error: e.into(),
+            };
+            return self.handle_error(report, collected, error);
+        }
+        report.count_written(class);
+        Ok(())
+    }
+
+    /// Pushes the images that have no annotations to the serializer, after
+    /// every annotation. Each image goes through the chain first, so that
+    /// the transforms fill in what the target format needs, such as the
+    /// image path or dimensions. Their positions follow the last one in the
+    /// parser stream, starting at `position`.
+    fn write_empty_images<S: FormatSerializer>(
+        &mut self,
+        serializer: &mut S,
+        position: usize,
+        images: Vec<ImageRecord>,
+        report: &mut ConversionReport,
+        collected: &mut Vec<AnnotationError>,
+    ) -> Result<(), ConversionError> {
+        for (position, mut image) in (position..).zip(images) {
+            let source_file = image.source_file.clone();
+            let result = self
+                .transforms
+                .iter_mut()
+                .try_for_each(|chained| {
+                    chained
+                        .transform
+                        .apply_image(&mut image, self.source_format, self.target_format)
+                })
+                .map_err(|e| (ConversionStage::Transform, e))
+                .and_then(|_| {
+                    serializer
+                        .push_image(image)
+                        .map_err(|e| (ConversionStage::Serialize, e.into()))
+                });
+
+            if let Err((stage, error)) = result {
+                let error = AnnotationError {
+                    stage,
+                    source_file,
+                    position,
+                    error,
+                };
+                self.apply_error_policy(report, collected, error)?;
+                report.empty_images_dropped += 1;
+                continue;
             }
+            report.empty_images_written += 1;
         }
+        Ok(())
+    }
+
+    /// Runs the chain on an annotation. Annotations that replace another one
+    /// continue from the transform after the one that replaced it. Nothing is
+    /// returned if any of the transforms fails.
+    fn apply_transforms(&mut self, annotation: Annotation) -> Result<ChainOutput> {
+        let mut output = ChainOutput::default();
 
-        if transformations.contains(&RequiredTransformations::LookupImage) {
-            match self.image_directory.as_ref() {
-                Some(image_directory) => {
-                    let lookup = LookupImage::new(PathBuf::from(image_directory))?;
-                    self.transforms.push(Box::new(lookup));
+        // Annotations are taken from the end, replacements are pushed in
+        // reverse so that they come out in the order they were returned.
+        let mut pending = vec![(0, annotation)];
+        'pending: while let Some((start, mut annotation)) = pending.pop() {
+            for (index, chained) in self.transforms.iter_mut().enumerate().skip(start) {
+                let outcome =
+                    chained
+                        .transform
+                        .apply(&mut annotation, self.source_format, self.target_format)?;
+                match outcome {
+                    TransformOutcome::Keep => {}
+                    TransformOutcome::Drop(reason) => {
+                        output.filter_reasons.push(reason);
+                        continue 'pending;
+                    }
+                    TransformOutcome::Replace(replacements) => {
+                        output.replaced += 1;
+                        pending.extend(replacements.into_iter().rev().map(|a| (index + 1, a)));
+                        continue 'pending;
+                    }
                 }
-                None => return Err(anyhow!("Expected image directory to be Some")),
             }
+            output.annotations.push(annotation);
         }
 
-        if transformations.contains(&RequiredTransformations::MapToId)
-            || transformations.contains(&RequiredTransformations::MapToName)
-        {
-            match self.mapping.take() {
-                Some(map) => {
-                    let map = ClassMapping::new(map);
-                    self.transforms.push(Box::new(map));
+        Ok(output)
+    }
+
+    /// Handles an error according to the error policy, counting the
+    /// annotation as dropped unless it could not even be parsed.
+    fn handle_error(
+        &self,
+        report: &mut ConversionReport,
+        collected: &mut Vec<AnnotationError>,
+        error: AnnotationError,
+    ) -> Result<(), ConversionError> {
+        let stage = error.stage;
+        self.apply_error_policy(report, collected, error)?;
+        if stage != ConversionStage::Parse {
+            report.annotations_dropped += 1;
+        }
+        Ok(())
+    }
+
+    /// Errors that do not stop the conversion are added to the report,
+    /// `collected` keeps them around in case the conversion fails later on.
+    fn apply_error_policy(
+        &self,
+        report: &mut ConversionReport,
+        collected: &mut Vec<AnnotationError>,
+        error: AnnotationError,
+    ) -> Result<(), ConversionError> {
+        match self.error_policy {
+            ErrorPolicy::Abort => return Err(ConversionError::Annotation(error)),
+            ErrorPolicy::Skip => report.errors.push((&error).into()),
+            ErrorPolicy::Collect(limit) => {
+                if collected.len() >= limit {
+                    let mut errors = std::mem::take(collected);
+                    errors.push(error);
+                    return Err(ConversionError::TooManyErrors(errors));
                 }
-                None => return Err(anyhow!("Expected mapping in the transformations")),
+                report.errors.push((&error).into());
+                collected.push(error);
             }
         }
+        Ok(())
+    }
+
+    fn configure(&mut self) -> Result<()> {
+        let plan = self.plan()?;
+        if !plan.is_ready() {
+            return Err(ConversionError::IncompletePlan(plan).into());
+        }
+
+        // Transforms required by a previous conversion are built again
+        let mut transforms: Vec<ChainedTransform> = std::mem::take(&mut self.transforms)
+            .into_iter()
+            .filter(|chained| chained.required.is_none())
+            .collect();
+        for step in plan.steps {
+            transforms.push(ChainedTransform {
+                transform: self.build_transform(step.transformation)?,
+                stage: TransformStage::default(),
+                required: Some(step.transformation),
+            });
+        }
+
+        let order = Self::schedule_chain(&transforms)?;
+        let mut transforms: Vec<Option<ChainedTransform>> =
+            transforms.into_iter().map(Some).collect();
+        self.transforms = order
+            .iter()
+            .filter_map(|&index| transforms[index].take())
+            .collect();
+        self.applied_transformations = self
+            .transforms
+            .iter()
+            .filter_map(|chained| chained.required)
+            .collect();
 
         Ok(())
     }
+
+    /// Orders the required transforms from the fields they declare, as what
+    /// they declare may be more precise than what the plan assumed. The
+    /// transforms added by the user run in the order they were added, before
+    /// or after the required ones. A transform can not require a field that
+    /// is only provided by a later group.
+    fn schedule_chain(transforms: &[ChainedTransform]) -> Result<Vec<usize>, ConversionError> {
+        let name = |index: usize| transforms[index].transform.name().to_string();
+        let mut order = Vec::with_capacity(transforms.len());
+
+        for group in 0..3 {
+            let members: Vec<usize> = (0..transforms.len())
+                .filter(|&index| transforms[index].group() == group)
+                .collect();
+            if group != 1 {
+                order.extend(members);
+                continue;
+            }
+
+            let dependencies: Vec<_> = members
+                .iter()
+                .map(|&index| {
+                    let transform = &transforms[index].transform;
+                    (transform.requires(), transform.provides())
+                })
+                .collect();
+
+            let group_order = schedule(&dependencies).map_err(|error| {
+                let names = error
+                    .unscheduled
+                    .iter()
+                    .map(|&position| name(members[position]))
+                    .collect();
+                ConversionError::UnorderedTransforms(names)
+            })?;
+            order.extend(group_order.into_iter().map(|position| members[position]));
+        }
+
+        for (position, &index) in order.iter().enumerate() {
+            let requires = transforms[index].transform.requires();
+            let provider = order[position + 1..].iter().find(|&&later| {
+                transforms[later].group() > transforms[index].group()
+                    && transforms[later]
+                        .transform
+                        .provides()
+                        .iter()
+                        .any(|field| requires.contains(field))
+            });
+            if let Some(&provider) = provider {
+                return Err(ConversionError::UnorderedTransforms(vec![
+                    name(index),
+                    name(provider),
+                ]));
+            }
+        }
+
+        Ok(order)
+    }
+
+    fn build_transform(
+        &self,
+        transformation: RequiredTransformations,
+    ) -> Result<Box<dyn Transform>> {
+        let image_directory = || {
+            self.image_directory
+                .clone()
+                .ok_or(anyhow!("Expected image directory to be Some"))
+        };
+
+        let transform: Box<dyn Transform> = match transformation {
+            RequiredTransformations::Normalize => {
+                Box::new(Normalize::new(self.image_directory.clone())?)
+            }
+            RequiredTransformations::Denormalize => {
+                Box::new(Denormalize::new(self.image_directory.clone())?)
+            }
+            RequiredTransformations::ReadDimensions => {
+                Box::new(ReadDimensions::new(image_directory()?)?)
+            }
+            RequiredTransformations::LookupImage => {
+                Box::new(LookupImage::new(image_directory()?)?)
+            }
+            RequiredTransformations::AssignImageId => match self.image_id_source.as_ref() {
+                Some(path) => Box::new(AssignImageId::from_coco(path)?),
+                None => Box::new(AssignImageId::new()),
+            },
+            RequiredTransformations::AlignBoxes => Box::new(AlignBoxes::new(self.box_policy)),
+            RequiredTransformations::DropSegmentation => Box::new(DropSegmentation::new()),
+            RequiredTransformations::MapToId | RequiredTransformations::MapToName => {
+                match self.mapping.as_ref().or(self.generated_mapping.as_ref()) {
+                    Some(map) => Box::new(ClassMapping::new(map.clone())),
+                    None => return Err(anyhow!("Expected mapping in the transformations")),
+                }
+            }
+        };
+        Ok(transform)
+    }
 }
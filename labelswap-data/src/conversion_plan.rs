@@ -0,0 +1,212 @@
+/* conversion_plan.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::fmt::Display;
+
+use crate::conversion_pipeline::ConversionError;
+use crate::models::format::{Format, ImageDimensions};
+use crate::transforms::scheduler::schedule;
+use crate::transforms::RequiredTransformations;
+
+/// An input that has to be given to the ConversionPipeline for a
+/// transformation to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineInput {
+    ImageDirectory,
+    ClassMapping,
+}
+
+impl Display for PipelineInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let input = match self {
+            Self::ImageDirectory => "an image directory",
+            Self::ClassMapping => "a class mapping",
+        };
+        f.write_str(input)
+    }
+}
+
+/// A transformation the ConversionPipeline will run, why it is needed and
+/// what is still missing to run it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedTransform {
+    pub transformation: RequiredTransformations,
+    pub reason: String,
+    pub missing_inputs: Vec<PipelineInput>,
+}
+
+impl Display for PlannedTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.transformation, self.reason)?;
+        if !self.missing_inputs.is_empty() {
+            let inputs: Vec<String> = self.missing_inputs.iter().map(|i| i.to_string()).collect();
+            write!(f, " (requires {})", inputs.join(" and "))?;
+        }
+        Ok(())
+    }
+}
+
+/// ConversionPlan: the ordered list of transformations needed to convert
+/// from one format to another. It can be built without touching any file,
+/// so it can be shown to the user before converting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConversionPlan {
+    pub steps: Vec<PlannedTransform>,
+}
+
+impl ConversionPlan {
+    /// Fails with ConversionError::UnorderedTransforms if no order satisfies
+    /// the fields the transformations read and fill in.
+    pub fn new(
+        source_format: &Format,
+        target_format: &Format,
+        available_inputs: &[PipelineInput],
+    ) -> Result<Self, ConversionError> {
+        let transformations = source_format.check_compatibility(target_format);
+
+        // Normalization and denormalization are mutually exclusive, just
+        // like MapToId and MapToName. This order only breaks ties, the
+        // fields each transformation reads and fills in decide the rest.
+        let transformations: Vec<RequiredTransformations> = [
+            RequiredTransformations::Denormalize,
+            RequiredTransformations::Normalize,
+            RequiredTransformations::ReadDimensions,
+            RequiredTransformations::LookupImage,
+            RequiredTransformations::AssignImageId,
+            RequiredTransformations::MapToId,
+            RequiredTransformations::MapToName,
+            RequiredTransformations::AlignBoxes,
+            RequiredTransformations::DropSegmentation,
+        ]
+        .into_iter()
+        .filter(|transformation| transformations.contains(transformation))
+        .collect();
+
+        let dependencies: Vec<_> = transformations
+            .iter()
+            .map(|transformation| (transformation.requires(), transformation.provides()))
+            .collect();
+        let order = schedule(&dependencies).map_err(|error| {
+            let names = error
+                .unscheduled
+                .iter()
+                .map(|&index| format!("{:?}", transformations[index]))
+                .collect();
+            ConversionError::UnorderedTransforms(names)
+        })?;
+
+        let steps = order
+            .into_iter()
+            .map(|index| transformations[index])
+            .map(|transformation| PlannedTransform {
+                transformation,
+                reason: reason(transformation, source_format, target_format),
+                missing_inputs: required_inputs(transformation, source_format)
+                    .iter()
+                    .filter(|input| !available_inputs.contains(input))
+                    .copied()
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Self { steps })
+    }
+
+    /// Whether every input needed by the plan has been given.
+    pub fn is_ready(&self) -> bool {
+        self.steps.iter().all(|step| step.missing_inputs.is_empty())
+    }
+
+    /// Every missing input, without repetitions.
+    pub fn missing_inputs(&self) -> Vec<PipelineInput> {
+        let mut missing = Vec::new();
+        for input in self.steps.iter().flat_map(|step| step.missing_inputs.iter()) {
+            if !missing.contains(input) {
+                missing.push(*input);
+            }
+        }
+        missing
+    }
+}
+
+impl Display for ConversionPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.steps.is_empty() {
+            return f.write_str("No transformations are needed");
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            writeln!(f, "{}. {step}", index + 1)?;
+        }
+        Ok(())
+    }
+}
+
+fn required_inputs(
+    transformation: RequiredTransformations,
+    source_format: &Format,
+) -> &'static [PipelineInput] {
+    match transformation {
+        // The images are only read when the source does not store their
+        // dimensions
+        RequiredTransformations::Normalize | RequiredTransformations::Denormalize => {
+            match source_format.image_dimensions {
+                ImageDimensions::ContainsDimensions => &[],
+                ImageDimensions::NoDimensions => &[PipelineInput::ImageDirectory],
+            }
+        }
+        RequiredTransformations::ReadDimensions | RequiredTransformations::LookupImage => {
+            &[PipelineInput::ImageDirectory]
+        }
+        RequiredTransformations::MapToId | RequiredTransformations::MapToName => {
+            &[PipelineInput::ClassMapping]
+        }
+        RequiredTransformations::AssignImageId
+        | RequiredTransformations::AlignBoxes
+        | RequiredTransformations::DropSegmentation => &[],
+    }
+}
+
+fn reason(transformation: RequiredTransformations, source: &Format, target: &Format) -> String {
+    match transformation {
+        RequiredTransformations::Normalize => format!(
+            "{} uses normalized coordinates, {} does not",
+            target.name, source.name
+        ),
+        RequiredTransformations::Denormalize => format!(
+            "{} uses normalized coordinates, {} does not",
+            source.name, target.name
+        ),
+        RequiredTransformations::ReadDimensions => format!(
+            "{} needs image dimensions, {} does not store them",
+            target.name, source.name
+        ),
+        RequiredTransformations::LookupImage => format!(
+            "{} needs image paths, {} does not store them",
+            target.name, source.name
+        ),
+        RequiredTransformations::AssignImageId => format!(
+            "{} needs image ids, {} does not store them",
+            target.name, source.name
+        ),
+        RequiredTransformations::MapToId => format!(
+            "{} needs class ids, {} only stores class names",
+            target.name, source.name
+        ),
+        RequiredTransformations::MapToName => format!(
+            "{} needs class names, {} only stores class ids",
+            target.name, source.name
+        ),
+        RequiredTransformations::AlignBoxes => format!(
+            "{} can not store the oriented boxes of {}",
+            target.name, source.name
+        ),
+        RequiredTransformations::DropSegmentation => format!(
+            "{} can not store the segmentation of {}, only its bounding box is kept",
+            target.name, source.name
+        ),
+    }
+}
@@ -0,0 +1,66 @@
+/* conversion_progress.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// How far a conversion has gone. It is given to the progress callback of
+/// the ConversionPipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConversionProgress {
+    /// Source files whose annotations have all been converted
+    pub files_processed: usize,
+    /// Number of source files, if the parser knows it beforehand
+    pub total_files: Option<usize>,
+    /// Annotations returned by the parser so far, including the ones that
+    /// failed or were dropped
+    pub annotations_processed: usize,
+    /// Number of annotations, if the parser knows it beforehand
+    pub total_annotations: Option<usize>,
+}
+
+impl ConversionProgress {
+    /// The fraction of the conversion that is done, between 0 and 1, if the
+    /// totals are known. Annotations are preferred over files.
+    pub fn fraction(&self) -> Option<f64> {
+        let (done, total) = match (self.total_annotations, self.total_files) {
+            (Some(total), _) => (self.annotations_processed, total),
+            (None, Some(total)) => (self.files_processed, total),
+            (None, None) => return None,
+        };
+        if total == 0 {
+            return Some(1.0);
+        }
+        Some((done as f64 / total as f64).min(1.0))
+    }
+}
+
+/// CancellationToken: asks a running conversion to stop. Clones share the
+/// same state, so it can be cancelled from another thread while the
+/// conversion runs.
+///
+/// The conversion stops before reading the next annotation, finishes the
+/// serializer with what was written so far and reports that it was
+/// cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
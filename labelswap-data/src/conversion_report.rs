@@ -0,0 +1,166 @@
+/* conversion_report.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::conversion_pipeline::{AnnotationError, ConversionStage};
+use crate::models::Annotation;
+use crate::transforms::{BoxPolicy, RequiredTransformations};
+
+/// ConversionReport: what happened during a conversion. It is filled in by
+/// the ConversionPipeline and the transforms it runs, and can be exported
+/// as JSON.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversionReport {
+    /// Annotations successfully returned by the parser
+    pub annotations_read: usize,
+    /// Annotations that went through every transform. It includes the ones
+    /// created by a transform to replace another one.
+    pub annotations_transformed: usize,
+    /// Annotations a transform replaced by zero or more annotations
+    pub annotations_replaced: usize,
+    /// Annotations that were read, but never written because of an error
+    pub annotations_dropped: usize,
+    /// Annotations dropped on purpose by a transform
+    pub annotations_filtered: usize,
+    /// Annotations pushed to the serializer
+    pub annotations_written: usize,
+    /// Images without annotations pushed to the serializer
+    pub empty_images_written: usize,
+    /// Images without annotations that were never written because of an
+    /// error
+    pub empty_images_dropped: usize,
+    /// Written annotations per class name, or class id if it has no name
+    pub class_counts: BTreeMap<String, usize>,
+    /// Filtered annotations per reason given by the transform
+    pub filter_reasons: BTreeMap<String, usize>,
+    /// Source files for which LookupImage could not find an image
+    pub unmatched_images: Vec<PathBuf>,
+    /// Classes of the mapping generated for the conversion, ordered by id
+    pub generated_classes: Vec<String>,
+    /// Annotations renamed or dropped by each class rule, in rule order
+    pub class_rule_counts: Vec<RuleCount>,
+    /// Classes left after applying the class rules, ordered by their new id
+    pub remapped_classes: Vec<String>,
+    /// Oriented boxes replaced by axis aligned ones, if the target format
+    /// can not store them
+    pub box_alignment: Option<BoxAlignment>,
+    /// Segmentations replaced by their bounding box, if the target format
+    /// can not store them
+    pub dropped_segmentations: usize,
+    /// Changes made by the Sanitize transform, one per change
+    pub geometry_fixes: Vec<GeometryFix>,
+    /// The transformations applied to every annotation, in order
+    pub applied_transformations: Vec<RequiredTransformations>,
+    /// Errors that were skipped according to the ErrorPolicy
+    pub errors: Vec<ReportedError>,
+    /// Whether the conversion was stopped with a CancellationToken before
+    /// every annotation was read
+    pub cancelled: bool,
+}
+
+/// A serializable copy of an AnnotationError.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportedError {
+    pub stage: ConversionStage,
+    pub source_file: Option<PathBuf>,
+    pub position: usize,
+    pub message: String,
+}
+
+/// A change made to the geometry of an annotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum GeometryChange {
+    /// Some corners were outside of the image
+    Clipped,
+    /// NaN or infinite coordinates were replaced
+    FixedNonFinite,
+    /// The annotation was dropped as its coordinates could not be fixed
+    DroppedNonFinite,
+    /// The annotation was dropped for having a zero width or height
+    DroppedDegenerate,
+    /// The annotation was dropped for being below the minimum size
+    DroppedTooSmall,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeometryFix {
+    pub source_file: Option<PathBuf>,
+    pub image: Option<PathBuf>,
+    pub change: GeometryChange,
+}
+
+/// Oriented boxes replaced by axis aligned boxes. The areas are added in
+/// the units of the coordinates when the boxes were replaced.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BoxAlignment {
+    pub policy: BoxPolicy,
+    /// Number of boxes replaced
+    pub boxes: usize,
+    /// Area of the boxes before being replaced
+    pub original_area: f64,
+    /// Area of the boxes that replaced them
+    pub aligned_area: f64,
+}
+
+impl BoxAlignment {
+    /// The area gained, or lost if negative, as a percentage of the
+    /// original area.
+    pub fn area_change_percent(&self) -> f64 {
+        if self.original_area == 0.0 {
+            return 0.0;
+        }
+        (self.aligned_area - self.original_area) / self.original_area * 100.0
+    }
+}
+
+/// How many annotations a class rule renamed or dropped.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RuleCount {
+    pub rule: String,
+    pub annotations: usize,
+}
+
+impl From<&AnnotationError> for ReportedError {
+    fn from(error: &AnnotationError) -> Self {
+        Self {
+            stage: error.stage,
+            source_file: error.source_file.clone(),
+            position: error.position,
+            message: error.error.to_string(),
+        }
+    }
+}
+
+impl ConversionReport {
+    /// The key used in class_counts for an annotation.
+    pub(crate) fn class_key(annotation: &Annotation) -> Option<String> {
+        let class = annotation.class.name().or(annotation.class.id());
+        class.map(String::from)
+    }
+
+    /// Counts a written annotation whose class_key is `class`.
+    pub(crate) fn count_written(&mut self, class: Option<String>) {
+        self.annotations_written += 1;
+        if let Some(class) = class {
+            *self.class_counts.entry(class).or_default() += 1;
+        }
+    }
+
+    /// Counts an annotation dropped by a transform.
+    pub(crate) fn count_filtered(&mut self, reason: String) {
+        self.annotations_filtered += 1;
+        *self.filter_reasons.entry(reason).or_default() += 1;
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
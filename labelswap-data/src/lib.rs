@@ -1,15 +1,18 @@
 use std::{
-    io::{self, BufRead, BufReader, Read},
+    io,
     path::{Path, PathBuf},
 };
 
-use anyhow::Result;
-
+pub mod class_map;
 pub mod conversion_pipeline;
+pub mod conversion_plan;
+pub mod conversion_progress;
+pub mod conversion_report;
 pub mod models;
 pub mod parser;
 pub mod serializer;
 pub mod transforms;
+pub mod worker_pool;
 
 pub fn resolve_relative_path<P, R>(path: P, relative_path: R)-> Result<PathBuf, io::Error>
 where P: AsRef<Path>,
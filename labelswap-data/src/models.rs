@@ -1,7 +1,15 @@
 pub mod annotation;
+pub mod dataset;
 pub mod format;
 pub mod image;
+pub mod keypoints;
+pub mod rotated_box;
+pub mod segmentation;
 
-pub use annotation::Annotation;
+pub use annotation::{Annotation, Winding};
+pub use dataset::{Dataset, ImageRecord};
 pub use format::Format;
 pub use image::Image;
+pub use keypoints::{Keypoint, Keypoints, Skeleton, Visibility};
+pub use rotated_box::{AngleRange, AngleUnit, RotatedBox};
+pub use segmentation::{RleCounts, RleMask, Segmentation};
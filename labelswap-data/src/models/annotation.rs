@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use super::Image;
+use super::{AngleRange, Image, Keypoints, RotatedBox, Segmentation};
 
 #[derive(Debug)]
 pub enum ClassRepresentation<S>
@@ -41,8 +41,8 @@ impl ClassRepresentation<String> {
             Self::ClassName(ref s) => ClassRepresentation::ClassName(s),
             Self::ClassId(ref s) => ClassRepresentation::ClassId(s),
             Self::Both { ref name, ref id } => ClassRepresentation::Both {
-                name: &name,
-                id: &id,
+                name,
+                id,
             },
         }
     }
@@ -76,11 +76,20 @@ impl ClassRepresentation<String> {
             ClassRepresentation::None => None,
             ClassRepresentation::ClassName(_) => None,
             ClassRepresentation::ClassId(id) => Some(id),
-            ClassRepresentation::Both { id, .. } => Some(&id),
+            ClassRepresentation::Both { id, .. } => Some(id),
         }
     }
 }
 
+/// The direction in which the corners of an annotation follow each other,
+/// as seen on the image, with the y axis pointing down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Winding {
+    #[default]
+    Clockwise,
+    CounterClockwise,
+}
+
 ///
 /// The Annotation struct represents an annotation in a image. It uses oriented
 /// bounding boxes to represent its contents. The current bounding box
@@ -96,6 +105,14 @@ impl ClassRepresentation<String> {
 /// └────────────────────────────────┘   
 /// x4, y4                        x3, y3   
 ///
+/// This is the canonical order: the corners go clockwise, as seen on the
+/// image, starting from the top-left-most one. Parsers of oriented formats
+/// keep the order of the file, Annotation::order_corners restores it.
+///
+/// Formats with instance segmentation also store the outline of the object
+/// in `segmentation`, the corners are then its bounding box. Pose formats
+/// store the keypoints of the object in `keypoints`.
+///
 /// The Annotation struct is usually returned by implementations of FormatParser
 /// and used as inputs for FormatSerializer implementations.
 #[derive(Debug, PartialEq)]
@@ -103,6 +120,9 @@ pub struct Annotation {
     pub class: ClassRepresentation<String>,
     pub source_file: Option<PathBuf>,
     pub difficulty: bool,
+    /// Whether the object extends beyond the image, as marked by the
+    /// annotator. Only Pascal VOC stores it.
+    pub truncated: bool,
     pub image: Image,
 
     pub x1: f64,
@@ -113,9 +133,13 @@ pub struct Annotation {
     pub y2: f64,
     pub y3: f64,
     pub y4: f64,
+
+    pub segmentation: Option<Segmentation>,
+    pub keypoints: Option<Keypoints>,
 }
 
 impl Annotation {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         x1: f64,
         x2: f64,
@@ -138,7 +162,10 @@ impl Annotation {
             class: ClassRepresentation::None,
             source_file: None,
             difficulty: false,
+            truncated: false,
             image: Image::new(),
+            segmentation: None,
+            keypoints: None,
         }
     }
 
@@ -152,8 +179,8 @@ impl Annotation {
         let distance_y = height / 2.0;
         let x1 = center_x - distance_x;
         let x2 = center_x + distance_x;
-        let x3 = center_x - distance_x;
-        let x4 = center_x + distance_x;
+        let x3 = center_x + distance_x;
+        let x4 = center_x - distance_x;
 
         let y1 = center_y - distance_y;
         let y2 = center_y - distance_y;
@@ -167,31 +194,130 @@ impl Annotation {
         Self::new(x_min, x_max, x_max, x_min, y_min, y_min, y_max, y_max)
     }
 
+    /// Creates an annotation with the corners of `rotated_box`, in the
+    /// canonical order.
+    pub fn from_rotated_box(rotated_box: &RotatedBox) -> Annotation {
+        let mut annotation = Self::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        annotation.set_corners(rotated_box.corners());
+        annotation.order_corners(Winding::Clockwise);
+        annotation
+    }
+
+    /// Creates an annotation holding `segmentation`, with its bounding box
+    /// as corners. It is None for empty segmentations.
+    pub fn from_segmentation(segmentation: Segmentation) -> Option<Annotation> {
+        let (x_min, x_max, y_min, y_max) = segmentation.bounding_box()?;
+        Some(Annotation {
+            segmentation: Some(segmentation),
+            ..Self::from_min_max(x_min, x_max, y_min, y_max)
+        })
+    }
+
+    /// The annotation as a rotated box with its angle in `range`. Corners
+    /// that do not form a rectangle are replaced by the rectangle of
+    /// minimum area containing them.
+    pub fn to_rotated_box(&self, range: AngleRange) -> RotatedBox {
+        RotatedBox::min_area(&self.corners()).normalized(range)
+    }
+
+    /// The corners as (x, y) pairs, from the first to the fourth.
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        [
+            (self.x1, self.y1),
+            (self.x2, self.y2),
+            (self.x3, self.y3),
+            (self.x4, self.y4),
+        ]
+    }
+
+    pub fn set_corners(&mut self, corners: [(f64, f64); 4]) {
+        [
+            (self.x1, self.y1),
+            (self.x2, self.y2),
+            (self.x3, self.y3),
+            (self.x4, self.y4),
+        ] = corners;
+    }
+
+    /// Twice the area enclosed by the corners, using the shoelace formula.
+    /// It is positive if they go clockwise.
+    fn signed_double_area(&self) -> f64 {
+        let corners = self.corners();
+        (0..4)
+            .map(|i| {
+                let (x1, y1) = corners[i];
+                let (x2, y2) = corners[(i + 1) % 4];
+                x1 * y2 - x2 * y1
+            })
+            .sum()
+    }
+
+    /// The area enclosed by the corners, which for rotated boxes is smaller
+    /// than the one between the minimum and maximum coordinates.
+    pub fn area(&self) -> f64 {
+        self.signed_double_area().abs() / 2.0
+    }
+
+    /// The winding of the corners, from the sign of the area they enclose.
+    /// It is None for boxes without area.
+    pub fn winding(&self) -> Option<Winding> {
+        let signed_area = self.signed_double_area();
+
+        // The y axis points down, so a positive area goes clockwise
+        if signed_area > 0.0 {
+            Some(Winding::Clockwise)
+        } else if signed_area < 0.0 {
+            Some(Winding::CounterClockwise)
+        } else {
+            None
+        }
+    }
+
+    /// Reorders the corners so that they follow `winding` starting from the
+    /// top-left-most corner: the one with the smallest x + y, or the
+    /// smallest y among those. The corners are sorted around their center,
+    /// so corners stored in a crossed order are fixed too.
+    pub fn order_corners(&mut self, winding: Winding) {
+        let corners = self.corners();
+        let center_x = corners.iter().map(|(x, _)| x).sum::<f64>() / 4.0;
+        let center_y = corners.iter().map(|(_, y)| y).sum::<f64>() / 4.0;
+        let angle = |&(x, y): &(f64, f64)| (y - center_y).atan2(x - center_x);
+
+        // With the y axis pointing down, growing angles go clockwise
+        let mut sorted = corners;
+        sorted.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        if winding == Winding::CounterClockwise {
+            sorted.reverse();
+        }
+
+        let start = (0..4)
+            .min_by(|&a, &b| {
+                let (ax, ay) = sorted[a];
+                let (bx, by) = sorted[b];
+                (ax + ay)
+                    .total_cmp(&(bx + by))
+                    .then(ay.total_cmp(&by))
+            })
+            .unwrap_or_default();
+        sorted.rotate_left(start);
+        self.set_corners(sorted);
+    }
+
+    /// The smallest x coordinate of the corners. This and the other
+    /// extents ignore NaN coordinates, unless all four are NaN.
     pub fn get_xmin(&self) -> f64 {
-        *[self.x1, self.x2, self.x3, self.x4]
-            .iter()
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap()
+        [self.x2, self.x3, self.x4].into_iter().fold(self.x1, f64::min)
     }
 
     pub fn get_xmax(&self) -> f64 {
-        *[self.x1, self.x2, self.x3, self.x4]
-            .iter()
-            .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap()
+        [self.x2, self.x3, self.x4].into_iter().fold(self.x1, f64::max)
     }
 
     pub fn get_ymin(&self) -> f64 {
-        *[self.y1, self.y2, self.y3, self.y4]
-            .iter()
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap()
+        [self.y2, self.y3, self.y4].into_iter().fold(self.y1, f64::min)
     }
 
     pub fn get_ymax(&self) -> f64 {
-        *[self.y1, self.y2, self.y3, self.y4]
-            .iter()
-            .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap()
+        [self.y2, self.y3, self.y4].into_iter().fold(self.y1, f64::max)
     }
 }
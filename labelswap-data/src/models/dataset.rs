@@ -0,0 +1,172 @@
+/* dataset.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::{Annotation, Image};
+use crate::parser::{FormatParser, ParserError};
+use crate::serializer::{FormatSerializer, SerializerResult};
+
+/// ImageRecord: an image along with every annotation on it. An image may
+/// have no annotations at all, e.g. a negative sample.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImageRecord {
+    pub image: Image,
+    /// The file the annotations of the image were read from, such as a YOLO
+    /// label file.
+    pub source_file: Option<PathBuf>,
+    pub annotations: Vec<Annotation>,
+}
+
+impl ImageRecord {
+    /// An image without annotations.
+    pub fn new(image: Image, source_file: Option<PathBuf>) -> Self {
+        Self {
+            image,
+            source_file,
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+
+    fn key(&self) -> Option<ImageKey> {
+        ImageKey::new(&self.image, self.source_file.as_deref())
+    }
+}
+
+/// What identifies an image: its id, its path, or the file its annotations
+/// come from, in that order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ImageKey {
+    Id(u32),
+    Path(PathBuf),
+    SourceFile(PathBuf),
+}
+
+impl ImageKey {
+    fn new(image: &Image, source_file: Option<&Path>) -> Option<Self> {
+        if let Some(id) = image.id {
+            return Some(Self::Id(id));
+        }
+        if let Some(path) = image.path.as_ref() {
+            return Some(Self::Path(path.clone()));
+        }
+        source_file.map(|source_file| Self::SourceFile(source_file.to_path_buf()))
+    }
+}
+
+/// Dataset: the images of a dataset with their annotations, in the order
+/// they were added. Unlike a stream of annotations, it keeps images that
+/// have no annotations.
+///
+/// Annotations are grouped with the other annotations of their image. Those
+/// that can not be related to an image get a record of their own.
+#[derive(Debug, Default)]
+pub struct Dataset {
+    records: Vec<ImageRecord>,
+    index: HashMap<ImageKey, usize>,
+}
+
+impl Dataset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Groups a stream of annotations by image.
+    pub fn from_annotations(annotations: impl IntoIterator<Item = Annotation>) -> Self {
+        let mut dataset = Self::new();
+        for annotation in annotations {
+            dataset.push_annotation(annotation);
+        }
+        dataset
+    }
+
+    /// Reads every annotation and empty image returned by `parser`, which
+    /// must have been initialized. Fails on the first parsing error.
+    pub fn read<P: FormatParser>(parser: &mut P) -> Result<Self, ParserError> {
+        let mut dataset = Self::new();
+        while parser.has_next() {
+            dataset.push_annotation(parser.get_next()?);
+        }
+        for record in parser.take_empty_images() {
+            dataset.push_image(record);
+        }
+        Ok(dataset)
+    }
+
+    /// Writes every image, including the ones without annotations, and
+    /// finishes `serializer`, which must have been initialized.
+    pub fn write<S: FormatSerializer>(self, mut serializer: S) -> SerializerResult<()> {
+        for record in self.records {
+            serializer.push_image(record)?;
+        }
+        serializer.finish()
+    }
+
+    /// Adds an annotation to the record of its image, creating it if needed.
+    pub fn push_annotation(&mut self, annotation: Annotation) {
+        let key = ImageKey::new(&annotation.image, annotation.source_file.as_deref());
+        if let Some(&index) = key.as_ref().and_then(|key| self.index.get(key)) {
+            self.records[index].annotations.push(annotation);
+            return;
+        }
+
+        let mut record = ImageRecord::new(
+            annotation.image.clone(),
+            annotation.source_file.clone(),
+        );
+        record.annotations.push(annotation);
+        self.insert(key, record);
+    }
+
+    /// Adds an image. If the dataset already has a record for it, the
+    /// annotations are added to that record.
+    pub fn push_image(&mut self, mut record: ImageRecord) {
+        let key = record.key();
+        if let Some(&index) = key.as_ref().and_then(|key| self.index.get(key)) {
+            self.records[index].annotations.append(&mut record.annotations);
+            return;
+        }
+        self.insert(key, record);
+    }
+
+    fn insert(&mut self, key: Option<ImageKey>, record: ImageRecord) {
+        if let Some(key) = key {
+            self.index.insert(key, self.records.len());
+        }
+        self.records.push(record);
+    }
+
+    pub fn images(&self) -> &[ImageRecord] {
+        &self.records
+    }
+
+    pub fn into_images(self) -> Vec<ImageRecord> {
+        self.records
+    }
+
+    /// Images that do not have any annotation.
+    pub fn empty_images(&self) -> impl Iterator<Item = &ImageRecord> {
+        self.records.iter().filter(|record| record.is_empty())
+    }
+
+    pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        self.records.iter().flat_map(|record| record.annotations.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
@@ -7,9 +7,10 @@
 
 use std::{
     collections::HashSet,
-    fmt::{Display, Write},
+    fmt::Display,
 };
 
+use crate::models::Segmentation;
 use crate::transforms::transform::RequiredTransformations;
 
 #[derive(PartialEq, Debug)]
@@ -56,6 +57,51 @@ pub enum ImagePath {
     NoPath,
 }
 
+#[derive(PartialEq, Eq, Debug)]
+pub enum ImageId {
+    ContainsId,
+    NoId,
+}
+
+/// Whether a format stores the width and height of the images, which
+/// spares reading them from the image files.
+#[derive(PartialEq, Eq, Debug)]
+pub enum ImageDimensions {
+    ContainsDimensions,
+    NoDimensions,
+}
+
+/// The kind of boxes a format can store.
+#[derive(PartialEq, Eq, Debug)]
+pub enum BoxType {
+    /// Boxes with four arbitrary corners, which can be rotated
+    Oriented,
+    /// Boxes aligned with the image axes
+    AxisAligned,
+}
+
+/// The kind of segmentation a format can store along with its boxes.
+#[derive(PartialEq, Eq, Debug)]
+pub enum SegmentationType {
+    /// Boxes only
+    NoSegmentation,
+    /// Polygons around the objects
+    Polygons,
+    /// Polygons and run length encoded masks
+    PolygonsAndMasks,
+}
+
+impl SegmentationType {
+    /// Whether a format with this segmentation type can store
+    /// `segmentation`.
+    pub fn can_store(&self, segmentation: &Segmentation) -> bool {
+        matches!(
+            (self, segmentation),
+            (Self::PolygonsAndMasks, _) | (Self::Polygons, Segmentation::Polygons(_))
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct Format {
     pub name: String,
@@ -63,12 +109,37 @@ pub struct Format {
     pub file_extension: Option<String>,
     pub is_normalized: bool,
     pub image_path: ImagePath,
+    pub image_id: ImageId,
+    pub image_dimensions: ImageDimensions,
+    pub box_type: BoxType,
+    pub segmentation_type: SegmentationType,
     pub class_mapping: ClassMapping,
     pub class_format: ClassFormat,
     pub source_type: SourceType,
 }
 
 impl Format {
+    /// Creates a single file format that stores axis aligned boxes in
+    /// pixels with class names, and nothing else. Formats storing more
+    /// change the other fields with the struct update syntax, which keeps
+    /// them building when new fields are added.
+    pub fn new(name: impl Into<String>, id: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            id: id.into(),
+            file_extension: None,
+            is_normalized: false,
+            image_path: ImagePath::NoPath,
+            image_id: ImageId::NoId,
+            image_dimensions: ImageDimensions::NoDimensions,
+            box_type: BoxType::AxisAligned,
+            segmentation_type: SegmentationType::NoSegmentation,
+            class_mapping: ClassMapping::NoMapping,
+            class_format: ClassFormat::Name,
+            source_type: SourceType::SingleFile,
+        }
+    }
+
     pub fn check_compatibility(&self, other: &Format) -> HashSet<RequiredTransformations> {
         let mut transformations = HashSet::new();
 
@@ -78,6 +149,15 @@ impl Format {
             transformations.insert(RequiredTransformations::Normalize);
         }
 
+        // Normalizing and denormalizing read the dimensions anyway, without
+        // them the target gets them from the images
+        if self.image_dimensions == ImageDimensions::NoDimensions
+            && other.image_dimensions == ImageDimensions::ContainsDimensions
+            && self.is_normalized == other.is_normalized
+        {
+            transformations.insert(RequiredTransformations::ReadDimensions);
+        }
+
         if !self.class_mapping.has_mapping() {
             let mapping = match (&self.class_format, &other.class_format) {
                 (ClassFormat::Name, ClassFormat::Id) => Some(RequiredTransformations::MapToId),
@@ -95,6 +175,26 @@ impl Format {
             transformations.insert(RequiredTransformations::LookupImage);
         }
 
+        if self.image_id == ImageId::NoId && other.image_id == ImageId::ContainsId {
+            transformations.insert(RequiredTransformations::AssignImageId);
+        }
+
+        if self.box_type == BoxType::Oriented && other.box_type == BoxType::AxisAligned {
+            transformations.insert(RequiredTransformations::AlignBoxes);
+        }
+
+        // The segmentation is kept if the target stores at least the same
+        // kinds as the source
+        let keeps_segmentation = matches!(
+            (&self.segmentation_type, &other.segmentation_type),
+            (SegmentationType::NoSegmentation, _)
+                | (_, SegmentationType::PolygonsAndMasks)
+                | (SegmentationType::Polygons, SegmentationType::Polygons)
+        );
+        if !keeps_segmentation {
+            transformations.insert(RequiredTransformations::DropSegmentation);
+        }
+
         transformations
     }
 }
@@ -15,7 +15,7 @@ use std::path::PathBuf;
 /// opened to obtain them (usually for the purposes of normalization/
 /// denormalization)
 ///
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Image {
     pub width: Option<u32>,
     pub height: Option<u32>,
@@ -30,12 +30,7 @@ pub struct Image {
 
 impl Image {
     pub fn new() -> Self {
-        Self {
-            width: None,
-            height: None,
-            path: None,
-            id: None,
-        }
+        Self::default()
     }
 
     pub fn empty() -> Self {
@@ -0,0 +1,98 @@
+/* keypoints.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::sync::Arc;
+
+/// Whether a keypoint can be seen, with the values used by COCO and YOLO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    /// The keypoint was not labeled, its coordinates are meaningless
+    #[default]
+    NotLabeled,
+    /// The keypoint was labeled, but it is hidden
+    Occluded,
+    Visible,
+}
+
+impl Visibility {
+    pub fn from_value(value: u64) -> Option<Self> {
+        match value {
+            0 => Some(Self::NotLabeled),
+            1 => Some(Self::Occluded),
+            2 => Some(Self::Visible),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::NotLabeled => 0,
+            Self::Occluded => 1,
+            Self::Visible => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keypoint {
+    pub x: f64,
+    pub y: f64,
+    pub visibility: Visibility,
+}
+
+impl Keypoint {
+    pub fn new(x: f64, y: f64, visibility: Visibility) -> Self {
+        Self { x, y, visibility }
+    }
+}
+
+/// Skeleton: the names of the keypoints of a class and the pairs of them
+/// that are connected. Edges use indices starting from 0, COCO files start
+/// from 1.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skeleton {
+    pub names: Vec<String>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+/// Keypoints: the keypoints of an annotation, in the order of the names of
+/// its skeleton. Formats without skeletons, such as YOLO pose, leave it
+/// empty. Annotations of the same class share their skeleton.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keypoints {
+    pub points: Vec<Keypoint>,
+    pub skeleton: Option<Arc<Skeleton>>,
+}
+
+impl Keypoints {
+    pub fn new(points: Vec<Keypoint>, skeleton: Option<Arc<Skeleton>>) -> Self {
+        Self { points, skeleton }
+    }
+
+    /// The number of keypoints that were labeled.
+    pub fn labeled(&self) -> usize {
+        self.points
+            .iter()
+            .filter(|point| point.visibility != Visibility::NotLabeled)
+            .count()
+    }
+
+    /// The keypoint with the given name in the skeleton.
+    pub fn get(&self, name: &str) -> Option<&Keypoint> {
+        let skeleton = self.skeleton.as_ref()?;
+        let index = skeleton.names.iter().position(|n| n == name)?;
+        self.points.get(index)
+    }
+
+    /// Multiplies the coordinates of every keypoint by `x` and `y`.
+    pub fn scale(&mut self, x: f64, y: f64) {
+        for point in self.points.iter_mut() {
+            point.x *= x;
+            point.y *= y;
+        }
+    }
+}
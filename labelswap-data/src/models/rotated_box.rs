@@ -0,0 +1,238 @@
+/* rotated_box.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+/// The unit of the angle of a RotatedBox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleUnit {
+    Degrees,
+    #[default]
+    Radians,
+}
+
+impl AngleUnit {
+    pub fn to_radians(&self, angle: f64) -> f64 {
+        match self {
+            Self::Degrees => angle.to_radians(),
+            Self::Radians => angle,
+        }
+    }
+
+    pub fn from_radians(&self, angle: f64) -> f64 {
+        match self {
+            Self::Degrees => angle.to_degrees(),
+            Self::Radians => angle,
+        }
+    }
+}
+
+/// The range of angles of a RotatedBox, as defined by OpenCV and MMRotate.
+/// A rectangle can be described by several (width, height, angle)
+/// triplets, the range picks one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AngleRange {
+    /// OpenCV's minAreaRect convention: the angle is in (0°, 90°] and the
+    /// width may be smaller than the height
+    Oc,
+    /// The width is the longest side and the angle is in [-90°, 90°)
+    #[default]
+    Le90,
+    /// The width is the longest side and the angle is in [-45°, 135°)
+    Le135,
+}
+
+/// RotatedBox: an oriented box described by its center, its size and the
+/// angle between its width and the x axis. The angle is stored in radians
+/// and grows clockwise, as seen on the image with the y axis pointing down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RotatedBox {
+    pub center_x: f64,
+    pub center_y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub angle: f64,
+}
+
+impl RotatedBox {
+    pub fn new(
+        center_x: f64,
+        center_y: f64,
+        width: f64,
+        height: f64,
+        angle: f64,
+        unit: AngleUnit,
+    ) -> Self {
+        Self {
+            center_x,
+            center_y,
+            width,
+            height,
+            angle: unit.to_radians(angle),
+        }
+    }
+
+    /// The angle in the given unit.
+    pub fn angle(&self, unit: AngleUnit) -> f64 {
+        unit.from_radians(self.angle)
+    }
+
+    /// The same box, with its angle in `range`. The width and height are
+    /// swapped if the range requires it.
+    pub fn normalized(self, range: AngleRange) -> Self {
+        let mut normalized = self;
+        match range {
+            AngleRange::Oc => {
+                normalized.angle = self.angle.rem_euclid(PI);
+                if normalized.angle > FRAC_PI_2 || normalized.angle == 0.0 {
+                    normalized.angle = if normalized.angle == 0.0 {
+                        FRAC_PI_2
+                    } else {
+                        normalized.angle - FRAC_PI_2
+                    };
+                    normalized.swap_sides();
+                }
+            }
+            AngleRange::Le90 | AngleRange::Le135 => {
+                if self.width < self.height {
+                    normalized.swap_sides();
+                    normalized.angle += FRAC_PI_2;
+                }
+                let start = match range {
+                    AngleRange::Le135 => FRAC_PI_4,
+                    _ => FRAC_PI_2,
+                };
+                normalized.angle = (normalized.angle + start).rem_euclid(PI) - start;
+            }
+        }
+        normalized
+    }
+
+    fn swap_sides(&mut self) {
+        std::mem::swap(&mut self.width, &mut self.height);
+    }
+
+    /// The corners of the box, clockwise from the one that is the top-left
+    /// corner when the angle is 0.
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        let (sin, cos) = self.angle.sin_cos();
+        let half_width = self.width / 2.0;
+        let half_height = self.height / 2.0;
+        [
+            (-half_width, -half_height),
+            (half_width, -half_height),
+            (half_width, half_height),
+            (-half_width, half_height),
+        ]
+        .map(|(x, y)| {
+            (
+                self.center_x + x * cos - y * sin,
+                self.center_y + x * sin + y * cos,
+            )
+        })
+    }
+
+    /// The rectangle of minimum area containing `points`, found by trying
+    /// every edge of their convex hull as a side. Rectangles are returned as
+    /// they are, other shapes are approximated.
+    pub fn min_area(points: &[(f64, f64)]) -> Self {
+        let hull = convex_hull(points);
+        let mut best: Option<(f64, Self)> = None;
+
+        for i in 0..hull.len() {
+            let (x1, y1) = hull[i];
+            let (x2, y2) = hull[(i + 1) % hull.len()];
+            let angle = (y2 - y1).atan2(x2 - x1);
+            let (sin, cos) = angle.sin_cos();
+
+            // Coordinates of the points along the edge and its normal
+            let (mut min_u, mut max_u) = (f64::INFINITY, f64::NEG_INFINITY);
+            let (mut min_v, mut max_v) = (f64::INFINITY, f64::NEG_INFINITY);
+            for &(x, y) in hull.iter() {
+                let u = x * cos + y * sin;
+                let v = -x * sin + y * cos;
+                min_u = min_u.min(u);
+                max_u = max_u.max(u);
+                min_v = min_v.min(v);
+                max_v = max_v.max(v);
+            }
+
+            let width = max_u - min_u;
+            let height = max_v - min_v;
+            let area = width * height;
+            if best
+                .as_ref()
+                .is_some_and(|(best_area, _)| *best_area <= area)
+            {
+                continue;
+            }
+
+            let center_u = (min_u + max_u) / 2.0;
+            let center_v = (min_v + max_v) / 2.0;
+            let rotated_box = Self {
+                center_x: center_u * cos - center_v * sin,
+                center_y: center_u * sin + center_v * cos,
+                width,
+                height,
+                angle,
+            };
+            best = Some((area, rotated_box));
+        }
+
+        match best {
+            Some((_, rotated_box)) => rotated_box,
+            // A single point, or no points at all
+            None => {
+                let (center_x, center_y) = hull.first().copied().unwrap_or_default();
+                Self {
+                    center_x,
+                    center_y,
+                    width: 0.0,
+                    height: 0.0,
+                    angle: 0.0,
+                }
+            }
+        }
+    }
+}
+
+/// The convex hull of `points` using the monotone chain algorithm, without
+/// repeated or collinear points. A hull with a single edge is returned for
+/// collinear points.
+fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    // The lower and upper halves, each one ending where the other begins
+    let mut hull = half_hull(points.iter());
+    hull.pop();
+    let mut upper = half_hull(points.iter().rev());
+    upper.pop();
+    hull.extend(upper);
+    hull
+}
+
+/// The part of the convex hull that turns left when walking the points in
+/// order.
+fn half_hull<'a>(points: impl Iterator<Item = &'a (f64, f64)>) -> Vec<(f64, f64)> {
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+
+    let mut hull: Vec<(f64, f64)> = Vec::new();
+    for &point in points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    hull
+}
@@ -0,0 +1,212 @@
+/* segmentation.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/// The outline of an annotated object, for formats that store more than its
+/// box.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segmentation {
+    /// One or more polygons, each one a ring of (x, y) points. Objects split
+    /// in several parts, e.g. by occlusion, use more than one polygon
+    Polygons(Vec<Vec<(f64, f64)>>),
+    /// A run length encoded mask, as defined by COCO
+    Rle(RleMask),
+}
+
+/// RleMask: a binary mask of height x width pixels, stored as the lengths
+/// of alternating runs of background and foreground pixels. The pixels are
+/// read column by column, and the first run is always background, even if
+/// it is empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RleMask {
+    pub height: u32,
+    pub width: u32,
+    pub counts: RleCounts,
+}
+
+/// How the runs of an RleMask are stored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RleCounts {
+    /// The runs as a list of numbers
+    Uncompressed(Vec<u64>),
+    /// The runs as a string, using the compact encoding of the COCO API
+    Compressed(String),
+}
+
+impl RleCounts {
+    /// Encodes `runs` with the compact encoding of the COCO API: each run
+    /// but the first two is stored as the difference with the one two places
+    /// before it, in groups of 5 bits offset to printable characters.
+    pub fn compress(runs: &[u64]) -> Self {
+        let mut encoded = String::new();
+        for (i, &run) in runs.iter().enumerate() {
+            let mut value = run as i64;
+            if i > 2 {
+                value -= runs[i - 2] as i64;
+            }
+
+            loop {
+                let mut c = value & 0x1f;
+                value >>= 5;
+                let more = if c & 0x10 != 0 {
+                    value != -1
+                } else {
+                    value != 0
+                };
+                if more {
+                    c |= 0x20;
+                }
+                encoded.push(char::from(c as u8 + 48));
+                if !more {
+                    break;
+                }
+            }
+        }
+        Self::Compressed(encoded)
+    }
+
+    /// The lengths of the runs. Characters outside of the compact encoding
+    /// end the decoding of a compressed string.
+    pub fn runs(&self) -> Vec<u64> {
+        let encoded = match self {
+            Self::Uncompressed(runs) => return runs.clone(),
+            Self::Compressed(encoded) => encoded.as_bytes(),
+        };
+
+        let mut runs: Vec<i64> = Vec::new();
+        let mut position = 0;
+        'runs: while position < encoded.len() {
+            let mut value: i64 = 0;
+            let mut shift = 0;
+            loop {
+                let Some(c) = encoded.get(position).and_then(|c| c.checked_sub(48)) else {
+                    break 'runs;
+                };
+                if c > 0x3f || shift > 60 {
+                    break 'runs;
+                }
+                let c = i64::from(c);
+                value |= (c & 0x1f) << shift;
+                position += 1;
+                shift += 5;
+                if c & 0x20 == 0 {
+                    if c & 0x10 != 0 {
+                        value |= -1 << shift;
+                    }
+                    break;
+                }
+            }
+
+            if runs.len() > 2 {
+                value += runs[runs.len() - 2];
+            }
+            runs.push(value);
+        }
+        runs.into_iter().map(|run| run.max(0) as u64).collect()
+    }
+}
+
+impl RleMask {
+    /// The number of foreground pixels.
+    pub fn area(&self) -> u64 {
+        self.counts.runs().iter().skip(1).step_by(2).sum()
+    }
+
+    /// The smallest box containing every foreground pixel, as (x_min, x_max,
+    /// y_min, y_max). The maximum values are the edges of the last pixels.
+    pub fn bounding_box(&self) -> Option<(f64, f64, f64, f64)> {
+        let height = u64::from(self.height);
+        if height == 0 {
+            return None;
+        }
+
+        let mut bounds: Option<(u64, u64, u64, u64)> = None;
+        let mut start = 0;
+        for (i, run) in self.counts.runs().into_iter().enumerate() {
+            let end = start + run;
+            if i % 2 == 1 && run > 0 {
+                let (first_x, first_y) = (start / height, start % height);
+                let (last_x, last_y) = ((end - 1) / height, (end - 1) % height);
+                // A run that spans several columns covers them from top to
+                // bottom
+                let (y_min, y_max) = if first_x == last_x {
+                    (first_y, last_y)
+                } else {
+                    (0, height - 1)
+                };
+
+                bounds = Some(match bounds {
+                    None => (first_x, last_x, y_min, y_max),
+                    Some((x0, x1, y0, y1)) => (
+                        x0.min(first_x),
+                        x1.max(last_x),
+                        y0.min(y_min),
+                        y1.max(y_max),
+                    ),
+                });
+            }
+            start = end;
+        }
+
+        bounds.map(|(x_min, x_max, y_min, y_max)| {
+            (
+                x_min as f64,
+                (x_max + 1) as f64,
+                y_min as f64,
+                (y_max + 1) as f64,
+            )
+        })
+    }
+}
+
+impl Segmentation {
+    /// The area covered by the segmentation. Polygons are measured with the
+    /// shoelace formula and masks by counting their pixels.
+    pub fn area(&self) -> f64 {
+        match self {
+            Self::Polygons(polygons) => polygons
+                .iter()
+                .map(|polygon| {
+                    let double_area: f64 = (0..polygon.len())
+                        .map(|i| {
+                            let (x1, y1) = polygon[i];
+                            let (x2, y2) = polygon[(i + 1) % polygon.len()];
+                            x1 * y2 - x2 * y1
+                        })
+                        .sum();
+                    double_area.abs() / 2.0
+                })
+                .sum(),
+            Self::Rle(mask) => mask.area() as f64,
+        }
+    }
+
+    /// The smallest axis aligned box containing the segmentation, as (x_min,
+    /// x_max, y_min, y_max). It is None for empty segmentations.
+    pub fn bounding_box(&self) -> Option<(f64, f64, f64, f64)> {
+        match self {
+            Self::Polygons(polygons) => {
+                let mut points = polygons.iter().flatten();
+                let &(x, y) = points.next()?;
+                Some(points.fold((x, x, y, y), |(x0, x1, y0, y1), &(x, y)| {
+                    (x0.min(x), x1.max(x), y0.min(y), y1.max(y))
+                }))
+            }
+            Self::Rle(mask) => mask.bounding_box(),
+        }
+    }
+
+    /// Multiplies the coordinates of polygons by `x` and `y`. Masks are
+    /// always stored in pixels, so they are left as they are.
+    pub fn scale(&mut self, x: f64, y: f64) {
+        if let Self::Polygons(polygons) = self {
+            for point in polygons.iter_mut().flatten() {
+                point.0 *= x;
+                point.1 *= y;
+            }
+        }
+    }
+}
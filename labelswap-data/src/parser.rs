@@ -1,22 +1,33 @@
 mod cocojson_parser;
 mod format_parser;
+mod multi_file_parser;
+mod pascalvoc_parser;
 mod tfobjectdetection;
 mod yolo5obb_parser;
 mod yolo5txt_parser;
 mod yolodarknet_parser;
 mod yolo8obb_parser;
+mod yolopose_parser;
+mod yoloseg_parser;
 
-use std::io::{BufRead, BufReader, Read};
+use std::fs::{DirEntry, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 
 pub use cocojson_parser::CocoJsonParser;
 pub use format_parser::FormatParser;
+pub use multi_file_parser::MultiFileParser;
+pub use pascalvoc_parser::PascalVocParser;
 pub use tfobjectdetection::TfObjectDetectionParser;
 pub use yolo5obb_parser::Yolo5ObbParser;
 pub use yolo5txt_parser::Yolo5TxtParser;
 pub use yolodarknet_parser::YoloDarknetParser;
 pub use yolo8obb_parser::Yolo8ObbParser;
+pub use yolopose_parser::YoloPoseParser;
+pub use yoloseg_parser::YoloSegParser;
 
 use crate::models::format::SourceType;
+use crate::models::{Annotation, Image, ImageRecord};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -38,12 +49,85 @@ pub enum ParserError {
     OutOfElements,
 }
 
+/// Directory entries sorted by file name, so that directory based parsers
+/// return their annotations in the same order regardless of the file system.
+pub(crate) type DirEntries = std::vec::IntoIter<io::Result<DirEntry>>;
+
+pub(crate) fn read_dir_sorted(path: &Path) -> io::Result<DirEntries> {
+    let mut entries: Vec<DirEntry> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    Ok(entries.into_iter().map(Ok).collect::<Vec<_>>().into_iter())
+}
+
+/// The label files in a directory, `.txt` files sorted by file name.
+fn label_files(directory: Option<&Path>) -> Result<Vec<PathBuf>, ParserError> {
+    let directory = directory.ok_or(ParserError::Other(String::from(
+        "The parser has not been initialized",
+    )))?;
+    let files = read_dir_sorted(directory)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    Ok(files)
+}
+
+/// Parses every line of a file with `parse_line`.
+fn parse_lines<F>(path: &Path, parse_line: F) -> Vec<Result<Annotation, ParserError>>
+where
+    F: Fn(&str) -> Result<Annotation, ParserError>,
+{
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => return vec![Err(e.into())],
+    };
+
+    BufReader::new(file)
+        .lines()
+        .map(|line| parse_line(&line?))
+        .collect()
+}
+
+/// Opens the next label file in `entries` that contains annotations. Empty
+/// label files are images without annotations, they are added to
+/// `empty_files` instead. Files that can not be opened are returned with
+/// the error, for the parser to report it.
+fn open_next_label_file(
+    entries: &mut DirEntries,
+    empty_files: &mut Vec<PathBuf>,
+) -> Option<(DirEntry, Result<BufReader<File>, ParserError>)> {
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.extension().map_or(true, |extension| extension != "txt") {
+            continue;
+        }
+
+        let mut reader = match File::open(&path) {
+            Ok(file) => BufReader::new(file),
+            Err(e) => return Some((entry, Err(e.into()))),
+        };
+
+        if reader_has_data_left(&mut reader) {
+            return Some((entry, Ok(reader)));
+        }
+        empty_files.push(path);
+    }
+    None
+}
+
+/// Turns the empty label files found by a parser into image records.
+fn take_empty_label_files(empty_files: &mut Vec<PathBuf>) -> Vec<ImageRecord> {
+    empty_files
+        .drain(..)
+        .map(|path| ImageRecord::new(Image::empty(), Some(path)))
+        .collect()
+}
+
 fn reader_has_data_left<R>(reader: &mut BufReader<R>) -> bool
 where
     R: ?Sized + Read,
 {
-    match reader.fill_buf().map(|b| !b.is_empty()) {
-        Ok(result) => result,
-        Err(_) => false,
-    }
+    reader.fill_buf().map(|b| !b.is_empty()).unwrap_or_default()
 }
@@ -6,28 +6,47 @@
  */
 
 use super::{FormatParser, ParserError};
+use crate::class_map::ClassMap;
 use crate::models::format::SourceType;
-use crate::models::{annotation::ClassRepresentation, Annotation, Image};
+use crate::models::{
+    annotation::ClassRepresentation, Annotation, Image, ImageRecord, Keypoint, Keypoints,
+    RleCounts, RleMask, Segmentation, Skeleton, Visibility,
+};
 use serde_json::value::Value;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::{fs::File, io::BufReader, path::PathBuf};
 
 pub struct CocoJsonParser {
-    category_map: HashMap<i64, String>,
-    image_map: HashMap<i64, PathBuf>,
+    source_file: PathBuf,
+    category_map: ClassMap,
+    /// Skeletons of the categories with keypoints
+    skeletons: HashMap<u32, Arc<Skeleton>>,
+    image_map: HashMap<i64, Image>,
+    /// Ids of the images in the order they appear in the file
+    image_ids: Vec<i64>,
     annotation_array: VecDeque<Value>,
 }
 
 impl CocoJsonParser {
     pub fn new() -> Self {
         Self {
-            category_map: HashMap::new(),
+            source_file: PathBuf::new(),
+            category_map: ClassMap::new(),
+            skeletons: HashMap::new(),
             image_map: HashMap::new(),
+            image_ids: Vec::new(),
             annotation_array: VecDeque::new(),
         }
     }
 }
 
+impl Default for CocoJsonParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormatParser for CocoJsonParser {
     fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
         let path: PathBuf = path.into();
@@ -48,6 +67,7 @@ impl FormatParser for CocoJsonParser {
         }
 
         let buf_reader = BufReader::new(File::open(&path)?);
+        self.source_file = path;
 
         let mut map: serde_json::Map<String, Value> = match serde_json::from_reader(buf_reader) {
             Ok(map) => map,
@@ -89,7 +109,16 @@ impl FormatParser for CocoJsonParser {
             }
         };
 
-        self.parse_image_map(&image_array)?;
+        self.parse_image_map(image_array)?;
+
+        // Images that are not referenced by any annotation are kept
+        // aside so that they can be returned by take_empty_images
+        let annotated_images: HashSet<i64> = self
+            .annotation_array
+            .iter()
+            .filter_map(|annotation| annotation.get("image_id")?.as_i64())
+            .collect();
+        self.image_ids.retain(|id| !annotated_images.contains(id));
 
         Ok(())
     }
@@ -111,20 +140,20 @@ impl FormatParser for CocoJsonParser {
         let category_id = match map.remove("category_id").ok_or(ParserError::WrongFormat(
             "Expected category_id element".into(),
         ))? {
-            Value::Number(num) => num.as_i64().unwrap(),
+            Value::Number(num) => num,
             _ => {
                 return Err(ParserError::WrongFormat(
                     "Expected category_id element to be a number".into(),
                 ))
             }
         };
-        let category_name = self
-            .category_map
-            .get(&category_id)
+        let category = category_id.as_u64().and_then(|id| u32::try_from(id).ok());
+        let category_name = category
+            .and_then(|id| self.category_map.name(id))
             .ok_or(ParserError::WrongFormat(format!(
                 "Category id {category_id} not found in category map"
             )))?
-            .clone();
+            .to_string();
 
         let image = match map
             .get("image_id")
@@ -141,9 +170,9 @@ impl FormatParser for CocoJsonParser {
         let image = self
             .image_map
             .get(&image)
-            .ok_or(ParserError::WrongFormat(
-                "Image id {image} not found in image map".into(),
-            ))?
+            .ok_or(ParserError::WrongFormat(format!(
+                "Image id {image} not found in image map"
+            )))?
             .clone();
 
         let bbox = match map
@@ -159,6 +188,17 @@ impl FormatParser for CocoJsonParser {
         };
 
         let (x, y, width, height) = Self::parse_bbox_array(&bbox)?;
+        let segmentation = match map.remove("segmentation") {
+            Some(segmentation) => Self::parse_segmentation(segmentation)?,
+            None => None,
+        };
+        let keypoints = match map.remove("keypoints") {
+            Some(keypoints) => {
+                let skeleton = category.and_then(|id| self.skeletons.get(&id)).cloned();
+                Self::parse_keypoints(keypoints, skeleton)?
+            }
+            None => None,
+        };
         let class = ClassRepresentation::Both {
             name: category_name,
             id: category_id.to_string(),
@@ -166,17 +206,42 @@ impl FormatParser for CocoJsonParser {
 
         Ok(Annotation {
             class,
-            image: Image::new_with_path(image),
+            image,
+            segmentation,
+            keypoints,
             ..Annotation::from_top_left_corner(x, y, width, height)
         })
     }
 
     fn has_next(&mut self) -> bool {
-        self.annotation_array.len() > 0
+        !self.annotation_array.is_empty()
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        Some(self.source_file.clone())
+    }
+
+    fn remaining_annotations(&self) -> Option<usize> {
+        Some(self.annotation_array.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        self.image_ids
+            .drain(..)
+            .filter_map(|id| self.image_map.remove(&id))
+            .map(|image| ImageRecord::new(image, Some(self.source_file.clone())))
+            .collect()
     }
 }
 
 impl CocoJsonParser {
+    /// Every image in the file, once the parser has been initialized.
+    /// Images without annotations are no longer listed after
+    /// take_empty_images.
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.image_map.values()
+    }
+
     fn parse_category_array(&mut self, array: &[Value]) -> Result<(), ParserError> {
         for value in array {
             let image_object = match value {
@@ -198,9 +263,12 @@ impl CocoJsonParser {
             };
 
             let id = match id {
-                Value::Number(num) => num.as_i64().ok_or(ParserError::WrongFormat(
-                    "Expected id to be an integer".into(),
-                ))?,
+                Value::Number(num) => num
+                    .as_u64()
+                    .and_then(|id| u32::try_from(id).ok())
+                    .ok_or(ParserError::WrongFormat(
+                        "Expected category id to be a non-negative integer".into(),
+                    ))?,
                 _ => {
                     return Err(ParserError::WrongFormat(
                         "Expected id to be a number".into(),
@@ -221,7 +289,13 @@ impl CocoJsonParser {
                 }
             };
 
-            self.category_map.insert(id, category_name.clone());
+            self.category_map
+                .insert(id, category_name.clone())
+                .map_err(|e| ParserError::WrongFormat(format!("Invalid category: {e}")))?;
+
+            if let Some(skeleton) = Self::parse_skeleton(image_object)? {
+                self.skeletons.insert(id, Arc::new(skeleton));
+            }
         }
 
         Ok(())
@@ -266,7 +340,21 @@ impl CocoJsonParser {
                 }
             };
 
-            self.image_map.insert(id, PathBuf::from(filename));
+            let dimension = |name: &str| {
+                image_object
+                    .get(name)
+                    .and_then(Value::as_u64)
+                    .and_then(|value| u32::try_from(value).ok())
+            };
+
+            let image = Image {
+                width: dimension("width"),
+                height: dimension("height"),
+                path: Some(PathBuf::from(filename)),
+                id: u32::try_from(id).ok(),
+            };
+            self.image_ids.push(id);
+            self.image_map.insert(id, image);
         }
 
         Ok(())
@@ -293,4 +381,172 @@ impl CocoJsonParser {
 
         Ok((array[0], array[1], array[2], array[3]))
     }
+
+    /// Reads a list of polygons, given as flat lists of coordinates, or a
+    /// run length encoded mask. An empty list means there is no
+    /// segmentation.
+    fn parse_segmentation(value: Value) -> Result<Option<Segmentation>, ParserError> {
+        let mut mask = match value {
+            Value::Array(polygons) if polygons.is_empty() => return Ok(None),
+            Value::Array(polygons) => {
+                let polygons = polygons
+                    .iter()
+                    .map(Self::parse_polygon)
+                    .collect::<Result<_, _>>()?;
+                return Ok(Some(Segmentation::Polygons(polygons)));
+            }
+            Value::Object(mask) => mask,
+            _ => {
+                return Err(ParserError::WrongFormat(
+                    "Expected segmentation to be an array or an object".into(),
+                ))
+            }
+        };
+
+        let size: Vec<u32> = match mask.get("size") {
+            Some(Value::Array(size)) => size
+                .iter()
+                .filter_map(|value| value.as_u64().and_then(|value| u32::try_from(value).ok()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if size.len() != 2 {
+            return Err(ParserError::WrongFormat(
+                "Expected segmentation size to be a [height, width] array".into(),
+            ));
+        }
+
+        let counts = match mask.remove("counts") {
+            Some(Value::String(counts)) => RleCounts::Compressed(counts),
+            Some(Value::Array(counts)) => RleCounts::Uncompressed(
+                counts
+                    .iter()
+                    .map(|value| {
+                        value.as_u64().ok_or(ParserError::WrongFormat(format!(
+                            "Expected segmentation counts to be non-negative integers, got {value}"
+                        )))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => {
+                return Err(ParserError::WrongFormat(
+                    "Expected segmentation counts to be a string or an array".into(),
+                ))
+            }
+        };
+
+        Ok(Some(Segmentation::Rle(RleMask {
+            height: size[0],
+            width: size[1],
+            counts,
+        })))
+    }
+
+    fn parse_polygon(value: &Value) -> Result<Vec<(f64, f64)>, ParserError> {
+        let coordinates: Vec<f64> = match value {
+            Value::Array(coordinates) => coordinates
+                .iter()
+                .map(Value::as_f64)
+                .collect::<Option<_>>()
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+
+        if coordinates.is_empty() || coordinates.len() % 2 != 0 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected segmentation polygon to be a list of x, y coordinates, got {value}"
+            )));
+        }
+
+        Ok(coordinates
+            .chunks_exact(2)
+            .map(|point| (point[0], point[1]))
+            .collect())
+    }
+
+    /// Reads the keypoint names and the skeleton of a category, if it has
+    /// keypoints. Skeleton edges are numbered from 1 in COCO files.
+    fn parse_skeleton(
+        category: &serde_json::Map<String, Value>,
+    ) -> Result<Option<Skeleton>, ParserError> {
+        let names: Vec<String> = match category.get("keypoints") {
+            Some(Value::Array(names)) => names
+                .iter()
+                .map(|name| name.as_str().map(String::from))
+                .collect::<Option<_>>()
+                .ok_or(ParserError::WrongFormat(
+                    "Expected category keypoints to be a list of names".into(),
+                ))?,
+            Some(_) => {
+                return Err(ParserError::WrongFormat(
+                    "Expected category keypoints to be an array".into(),
+                ))
+            }
+            None => return Ok(None),
+        };
+
+        let edges = match category.get("skeleton") {
+            Some(Value::Array(edges)) => edges
+                .iter()
+                .map(|edge| {
+                    let ends: Vec<usize> = edge
+                        .as_array()?
+                        .iter()
+                        .map(|end| end.as_u64().and_then(|end| usize::try_from(end).ok()))
+                        .collect::<Option<_>>()?;
+                    let valid = |end: usize| (1..=names.len()).contains(&end);
+                    match ends[..] {
+                        [from, to] if valid(from) && valid(to) => Some((from - 1, to - 1)),
+                        _ => None,
+                    }
+                })
+                .collect::<Option<_>>()
+                .ok_or(ParserError::WrongFormat(
+                    "Expected category skeleton to be pairs of keypoint numbers".into(),
+                ))?,
+            _ => Vec::new(),
+        };
+
+        Ok(Some(Skeleton { names, edges }))
+    }
+
+    /// Reads a flat list of x, y, visibility triplets. An empty list means
+    /// there are no keypoints.
+    fn parse_keypoints(
+        value: Value,
+        skeleton: Option<Arc<Skeleton>>,
+    ) -> Result<Option<Keypoints>, ParserError> {
+        let values: Vec<f64> = match value {
+            Value::Array(values) => values.iter().map(Value::as_f64).collect(),
+            _ => None,
+        }
+        .ok_or(ParserError::WrongFormat(
+            "Expected keypoints to be an array of numbers".into(),
+        ))?;
+        if values.is_empty() {
+            return Ok(None);
+        }
+        if values.len() % 3 != 0 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected keypoints to be x, y, visibility triplets, got {} values",
+                values.len()
+            )));
+        }
+
+        let points = values
+            .chunks_exact(3)
+            .map(|point| {
+                let visibility = Some(point[2])
+                    .filter(|value| value.fract() == 0.0 && *value >= 0.0)
+                    .and_then(|value| Visibility::from_value(value as u64))
+                    .ok_or(ParserError::WrongFormat(format!(
+                        "Invalid keypoint visibility {}",
+                        point[2]
+                    )))?;
+                Ok(Keypoint::new(point[0], point[1], visibility))
+            })
+            .collect::<Result<_, ParserError>>()?;
+
+        Ok(Some(Keypoints::new(points, skeleton)))
+    }
 }
@@ -1,4 +1,4 @@
-use crate::models::Annotation;
+use crate::models::{Annotation, ImageRecord};
 use std::path::PathBuf;
 
 use super::ParserError;
@@ -7,4 +7,29 @@ pub trait FormatParser {
     fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError>;
     fn get_next(&mut self) -> Result<Annotation, ParserError>;
     fn has_next(&mut self) -> bool;
+
+    /// The file the parser is currently reading, used to tell where an
+    /// annotation that failed to parse comes from.
+    fn current_source_file(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// How many source files the parser reads, if it knows it beforehand.
+    /// It is used to report progress.
+    fn total_files(&self) -> Option<usize> {
+        None
+    }
+
+    /// How many annotations are left to read, if the parser knows it
+    /// without reading them. It is used to report progress.
+    fn remaining_annotations(&self) -> Option<usize> {
+        None
+    }
+
+    /// Takes the images found so far that do not have any annotation, such
+    /// as empty label files. Every one of them is known once has_next
+    /// returns false.
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        Vec::new()
+    }
 }
@@ -0,0 +1,23 @@
+use crate::models::{Annotation, Image, ImageRecord};
+use std::path::{Path, PathBuf};
+
+use super::ParserError;
+
+/// A parser for formats that store their annotations across several files,
+/// each of which can be parsed on its own. It lets the ConversionPipeline
+/// read many files at the same time.
+pub trait MultiFileParser: Sync {
+    /// Every file that contains annotations, in the order get_next would
+    /// read them. The parser must have been initialized.
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError>;
+
+    /// Parses every annotation in `path`, in order. A file that can not be
+    /// read results in a single error.
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>>;
+
+    /// The image of `path`, a file without annotations. Formats that describe
+    /// the image in its label file fill it in.
+    fn empty_image(&self, path: &Path) -> ImageRecord {
+        ImageRecord::new(Image::empty(), Some(path.to_path_buf()))
+    }
+}
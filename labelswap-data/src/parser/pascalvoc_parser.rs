@@ -0,0 +1,212 @@
+/* pascalvoc_parser.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use super::{read_dir_sorted, FormatParser, MultiFileParser, ParserError};
+use crate::models::{
+    annotation::ClassRepresentation, format::SourceType, Annotation, Image, ImageRecord,
+};
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+/// PascalVocParser: reads Pascal VOC labels, one XML file per image with its
+/// size and an `<object>` element per annotation. VOC boxes use 1-based
+/// pixel indices, with `xmax` and `ymax` being the last pixels of the box,
+/// so `xmin` and `ymin` are moved back by one pixel.
+pub struct PascalVocParser {
+    source_directory: Option<PathBuf>,
+    files: std::vec::IntoIter<PathBuf>,
+    current_file: Option<PathBuf>,
+    pending: VecDeque<Result<Annotation, ParserError>>,
+    empty_images: Vec<ImageRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Document {
+    filename: String,
+    size: Option<Size>,
+    #[serde(default, rename = "object")]
+    objects: Vec<Object>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Size {
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Object {
+    name: String,
+    #[serde(default)]
+    truncated: Option<u8>,
+    #[serde(default)]
+    difficult: Option<u8>,
+    bndbox: BndBox,
+}
+
+#[derive(Debug, Deserialize)]
+struct BndBox {
+    xmin: f64,
+    ymin: f64,
+    xmax: f64,
+    ymax: f64,
+}
+
+impl PascalVocParser {
+    pub fn new() -> Self {
+        Self {
+            source_directory: None,
+            files: Vec::new().into_iter(),
+            current_file: None,
+            pending: VecDeque::new(),
+            empty_images: Vec::new(),
+        }
+    }
+
+    /// The label files in a directory, `.xml` files sorted by file name.
+    fn xml_files(directory: Option<&Path>) -> Result<Vec<PathBuf>, ParserError> {
+        let directory = directory.ok_or(ParserError::Other(String::from(
+            "The parser has not been initialized",
+        )))?;
+        let files = read_dir_sorted(directory)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "xml"))
+            .collect();
+        Ok(files)
+    }
+
+    fn read_document(path: &Path) -> Result<(Image, Vec<Object>), ParserError> {
+        let contents = std::fs::read_to_string(path)?;
+        let document: Document = quick_xml::de::from_str(&contents)
+            .map_err(|e| ParserError::WrongFormat(format!("{}: {e}", path.display())))?;
+
+        let mut image = Image::new_with_path(document.filename);
+        // Some tools write a size of 0 when they do not know it
+        if let Some(size) = document
+            .size
+            .filter(|size| size.width > 0 && size.height > 0)
+        {
+            image.width = Some(size.width);
+            image.height = Some(size.height);
+        }
+        Ok((image, document.objects))
+    }
+
+    fn to_annotation(object: Object, image: &Image, source_file: &Path) -> Annotation {
+        let bndbox = object.bndbox;
+        Annotation {
+            // Whitespace in CDATA sections is kept by the XML reader
+            class: ClassRepresentation::ClassName(object.name.trim().to_string()),
+            source_file: Some(source_file.to_path_buf()),
+            difficulty: object.difficult.is_some_and(|difficult| difficult != 0),
+            truncated: object.truncated.is_some_and(|truncated| truncated != 0),
+            image: image.clone(),
+            ..Annotation::from_min_max(
+                bndbox.xmin - 1.0,
+                bndbox.xmax,
+                bndbox.ymin - 1.0,
+                bndbox.ymax,
+            )
+        }
+    }
+
+    fn parse_document(path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        match Self::read_document(path) {
+            Ok((image, objects)) => objects
+                .into_iter()
+                .map(|object| Ok(Self::to_annotation(object, &image, path)))
+                .collect(),
+            Err(e) => vec![Err(e)],
+        }
+    }
+}
+
+impl Default for PascalVocParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatParser for PascalVocParser {
+    fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
+        let path: PathBuf = path.into();
+
+        if !path.metadata()?.is_dir() {
+            return Err(ParserError::WrongSource {
+                expected: SourceType::MultipleFiles,
+                found: SourceType::SingleFile,
+            });
+        }
+        self.files = Self::xml_files(Some(&path))?.into_iter();
+        self.source_directory = Some(path);
+        Ok(())
+    }
+
+    fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        self.pending.pop_front().ok_or(ParserError::OutOfElements)?
+    }
+
+    fn has_next(&mut self) -> bool {
+        while self.pending.is_empty() {
+            let Some(path) = self.files.next() else {
+                self.current_file = None;
+                return false;
+            };
+
+            match Self::read_document(&path) {
+                Ok((image, objects)) if objects.is_empty() => {
+                    self.empty_images.push(ImageRecord::new(image, Some(path)));
+                }
+                Ok((image, objects)) => {
+                    self.pending = objects
+                        .into_iter()
+                        .map(|object| Ok(Self::to_annotation(object, &image, &path)))
+                        .collect();
+                    self.current_file = Some(path);
+                }
+                Err(e) => {
+                    self.pending.push_back(Err(e));
+                    self.current_file = Some(path);
+                }
+            }
+        }
+        true
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_file.clone()
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.source_files().ok().map(|files| files.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        std::mem::take(&mut self.empty_images)
+    }
+}
+
+impl MultiFileParser for PascalVocParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        Self::xml_files(self.source_directory.as_deref())
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        Self::parse_document(path)
+    }
+
+    fn empty_image(&self, path: &Path) -> ImageRecord {
+        let image = Self::read_document(path)
+            .map(|(image, _)| image)
+            .unwrap_or_default();
+        ImageRecord::new(image, Some(path.to_path_buf()))
+    }
+}
@@ -39,6 +39,12 @@ impl TfObjectDetectionParser {
     }
 }
 
+impl Default for TfObjectDetectionParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormatParser for TfObjectDetectionParser {
     fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), super::ParserError> {
         let path: PathBuf = path.into();
@@ -55,8 +61,8 @@ impl FormatParser for TfObjectDetectionParser {
             Err(e) => match e.into_kind() {
                 csv::ErrorKind::Io(io) => return Err(ParserError::Io(io)),
                 _ => {
-                    return Err(ParserError::WrongFormat(format!(
-                        "CSV is malformed. Please verify its integrity"
+                    return Err(ParserError::WrongFormat(String::from(
+                        "CSV is malformed. Please verify its integrity",
                     )))
                 }
             },
@@ -92,4 +98,8 @@ impl FormatParser for TfObjectDetectionParser {
         self.next_item.set(next_item);
         is_some
     }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        Some(self.source_file.clone())
+    }
 }
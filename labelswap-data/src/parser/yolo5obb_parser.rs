@@ -1,21 +1,23 @@
-use super::reader_has_data_left;
-use super::ParserError;
+use super::{label_files, parse_lines, read_dir_sorted, reader_has_data_left, DirEntries};
+use super::{MultiFileParser, ParserError};
 use crate::models::format::SourceType;
-use crate::models::Annotation;
+use crate::models::{Annotation, ImageRecord};
 use crate::{models::annotation::ClassRepresentation, models::Image, parser::FormatParser};
-use std::io;
 use std::path::PathBuf;
 use std::{
-    fs::{DirEntry, File, ReadDir},
+    fs::{DirEntry, File},
     io::{BufRead, BufReader},
     path::Path,
 };
 
 pub struct Yolo5ObbParser {
     source_directory: Option<Box<Path>>,
-    file_enumerator: Option<ReadDir>,
+    file_enumerator: Option<DirEntries>,
     current_entry: Option<DirEntry>,
     current_reader: Option<BufReader<File>>,
+    empty_files: Vec<PathBuf>,
+    /// The error opening the current file, returned by the next get_next
+    open_error: Option<ParserError>,
 }
 
 impl Yolo5ObbParser {
@@ -25,22 +27,55 @@ impl Yolo5ObbParser {
             file_enumerator: None,
             current_entry: None,
             current_reader: None,
+            empty_files: Vec::new(),
+            open_error: None,
         }
     }
 
-    fn look_for_next_entry(&mut self) -> std::io::Result<DirEntry> {
-        let enumerator: &mut ReadDir = self.file_enumerator.as_mut().unwrap();
+    fn parse_line(line: &str, source_file: PathBuf) -> Result<Annotation, ParserError> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let elements: Vec<&str> = line.trim().split(" ").collect();
+        if elements.len() < 9 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected at least 9 elements in line {line}"
+            )));
+        }
 
-        while let Some(next_entry) = enumerator.next() {
-            let next_entry = next_entry?;
-            let path = next_entry.path();
-            let extension = path.extension().unwrap();
-            if extension == "txt" {
-                return Ok(next_entry);
-            }
+        let coordinates: Vec<f64> = elements[..8]
+            .iter()
+            .filter_map(|c| c.parse::<f64>().ok())
+            .collect();
+        if coordinates.len() < 8 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected 4 (x, y) pairs, got {}",
+                coordinates.len()
+            )));
         }
 
-        Err(io::Error::new(io::ErrorKind::NotFound, "Entry not found"))
+        let difficulty = match elements.get(9) {
+            Some(difficulty) => difficulty.parse::<i32>().map_err(|e| {
+                ParserError::WrongFormat(format!("Invalid difficulty {difficulty}: {e}"))
+            })? != 0,
+            None => false,
+        };
+
+        Ok(Annotation {
+            x1: coordinates[0],
+            y1: coordinates[1],
+            x2: coordinates[2],
+            y2: coordinates[3],
+            x3: coordinates[4],
+            y3: coordinates[5],
+            x4: coordinates[6],
+            y4: coordinates[7],
+            class: ClassRepresentation::ClassName(elements[8].to_string()),
+            source_file: Some(source_file),
+            difficulty,
+            truncated: false,
+            image: Image::new(),
+            segmentation: None,
+            keypoints: None,
+        })
     }
 
     fn reader_has_data(&mut self) -> bool {
@@ -53,6 +88,12 @@ impl Yolo5ObbParser {
     }
 }
 
+impl Default for Yolo5ObbParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormatParser for Yolo5ObbParser {
     fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
         let path = path.into();
@@ -64,7 +105,7 @@ impl FormatParser for Yolo5ObbParser {
             });
         }
 
-        let enumerator = std::fs::read_dir(&path)?;
+        let enumerator = read_dir_sorted(&path)?;
         self.file_enumerator = Some(enumerator);
 
         self.source_directory = Some(path.into());
@@ -72,6 +113,10 @@ impl FormatParser for Yolo5ObbParser {
     }
 
     fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        if let Some(error) = self.open_error.take() {
+            return Err(error);
+        }
+
         let reader: &mut BufReader<File> = self
             .current_reader
             .as_mut()
@@ -80,68 +125,58 @@ impl FormatParser for Yolo5ObbParser {
         let mut line = String::new();
         let _ = reader.read_line(&mut line)?;
 
-        let elements: Vec<&str> = line.trim().split(" ").collect();
-        if elements.len() < 9 {
-            return Err(ParserError::WrongFormat(format!(
-                "Expected at least 9 elements in line {line}"
-            )));
-        }
-
-        let coordinates: Vec<f64> = elements[..8]
-            .iter()
-            .filter_map(|c| c.parse::<f64>().ok())
-            .collect();
-        if coordinates.len() < 8 {
-            return Err(ParserError::WrongFormat(format!(
-                "Expected 4 (x, y) pairs, got {}",
-                coordinates.len()
-            )));
-        }
-
-        let source_file = self.current_entry.as_ref().unwrap().path();
-
-        Ok(Annotation {
-            x1: coordinates[0],
-            y1: coordinates[1],
-            x2: coordinates[2],
-            y2: coordinates[3],
-            x3: coordinates[4],
-            y3: coordinates[5],
-            x4: coordinates[6],
-            y4: coordinates[7],
-            class: ClassRepresentation::ClassName(elements[8].to_string()),
-            source_file: Some(source_file),
-            difficulty: match elements.get(9) {
-                Some(i) => i.parse::<i32>().unwrap() != 0,
-                None => false,
-            },
-            image: Image::new(),
-        })
+        Self::parse_line(&line, self.current_entry.as_ref().unwrap().path())
     }
 
     fn has_next(&mut self) -> bool {
-        if self.reader_has_data() {
+        if self.open_error.is_some() {
             return true;
         }
 
-        let reader = self.current_reader.as_mut();
+        if self.reader_has_data() {
+            return true;
+        }
 
-        if reader.is_none() || !reader_has_data_left(reader.unwrap()) {
-            self.current_entry = self.look_for_next_entry().ok();
-            if self.current_entry.is_none() {
-                return false;
+        let enumerator = self.file_enumerator.as_mut().unwrap();
+        match super::open_next_label_file(enumerator, &mut self.empty_files) {
+            Some((entry, reader)) => {
+                self.current_entry = Some(entry);
+                match reader {
+                    Ok(reader) => self.current_reader = Some(reader),
+                    Err(error) => {
+                        self.current_reader = None;
+                        self.open_error = Some(error);
+                    }
+                }
+                true
+            }
+            None => {
+                self.current_entry = None;
+                self.current_reader = None;
+                false
             }
         }
+    }
 
-        let current_entry = &self.current_entry;
-        let text_file = match File::open(current_entry.as_ref().unwrap().path()) {
-            Ok(file) => file,
-            Err(e) => {
-                eprintln!("{e}");
-                return false;
-            }
-        };
-        self.current_reader = Some(BufReader::new(text_file));
-        return true;
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_entry.as_ref().map(|entry| entry.path())
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.source_files().ok().map(|files| files.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        super::take_empty_label_files(&mut self.empty_files)
+    }
+}
+
+impl MultiFileParser for Yolo5ObbParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        label_files(self.source_directory.as_deref())
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        parse_lines(path, |line| Self::parse_line(line, path.to_path_buf()))
     }
 }
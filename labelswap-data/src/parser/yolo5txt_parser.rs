@@ -1,16 +1,24 @@
-use super::{FormatParser, ParserError};
-use crate::models::{self, annotation::ClassRepresentation, format::SourceType, Image};
+use super::{
+    label_files, parse_lines, read_dir_sorted, DirEntries, FormatParser, MultiFileParser,
+    ParserError,
+};
+use crate::models::{
+    self, annotation::ClassRepresentation, format::SourceType, Image, ImageRecord,
+};
 use std::{
-    fs::{DirEntry, ReadDir},
+    fs::DirEntry,
     io::{BufRead, BufReader},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 pub struct Yolo5TxtParser {
     source_directory: Option<PathBuf>,
-    file_enumerator: Option<ReadDir>,
+    file_enumerator: Option<DirEntries>,
     current_entry: Option<DirEntry>,
     current_reader: Option<BufReader<std::fs::File>>,
+    empty_files: Vec<PathBuf>,
+    /// The error opening the current file, returned by the next get_next
+    open_error: Option<ParserError>,
 }
 
 impl Yolo5TxtParser {
@@ -20,35 +28,13 @@ impl Yolo5TxtParser {
             file_enumerator: None,
             current_entry: None,
             current_reader: None,
+            empty_files: Vec::new(),
+            open_error: None,
         }
     }
-}
-
-impl FormatParser for Yolo5TxtParser {
-    fn init(&mut self, path: impl Into<std::path::PathBuf>) -> Result<(), ParserError> {
-        let path: PathBuf = path.into();
-        let metadata = path.metadata().unwrap();
-
-        if !metadata.is_dir() {
-            return Err(ParserError::WrongSource {
-                expected: SourceType::MultipleFiles,
-                found: SourceType::SingleFile,
-            });
-        }
-        self.file_enumerator = Some(std::fs::read_dir(&path)?);
-        self.source_directory = Some(path);
-        Ok(())
-    }
-
-    fn get_next(&mut self) -> Result<models::Annotation, ParserError> {
-        let reader = self
-            .current_reader
-            .as_mut()
-            .ok_or(ParserError::OutOfElements)?;
-
-        let mut line = String::new();
-        let _ = reader.read_line(&mut line)?;
 
+    fn parse_line(line: &str, source_file: PathBuf) -> Result<models::Annotation, ParserError> {
+        let line = line.trim_end_matches(['\n', '\r']);
         let elements: Vec<&str> = line.trim().split(' ').collect();
         if elements.len() != 5 {
             return Err(ParserError::WrongFormat(format!(
@@ -70,7 +56,7 @@ impl FormatParser for Yolo5TxtParser {
         }
 
         Ok(models::Annotation {
-            source_file: Some(self.current_entry.as_ref().unwrap().path()),
+            source_file: Some(source_file),
             class: ClassRepresentation::ClassId(elements[0].to_string()),
             difficulty: false,
             image: Image::empty(),
@@ -82,8 +68,51 @@ impl FormatParser for Yolo5TxtParser {
             )
         })
     }
+}
+
+impl Default for Yolo5TxtParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatParser for Yolo5TxtParser {
+    fn init(&mut self, path: impl Into<std::path::PathBuf>) -> Result<(), ParserError> {
+        let path: PathBuf = path.into();
+        let metadata = path.metadata().unwrap();
+
+        if !metadata.is_dir() {
+            return Err(ParserError::WrongSource {
+                expected: SourceType::MultipleFiles,
+                found: SourceType::SingleFile,
+            });
+        }
+        self.file_enumerator = Some(read_dir_sorted(&path)?);
+        self.source_directory = Some(path);
+        Ok(())
+    }
+
+    fn get_next(&mut self) -> Result<models::Annotation, ParserError> {
+        if let Some(error) = self.open_error.take() {
+            return Err(error);
+        }
+
+        let reader = self
+            .current_reader
+            .as_mut()
+            .ok_or(ParserError::OutOfElements)?;
+
+        let mut line = String::new();
+        let _ = reader.read_line(&mut line)?;
+
+        Self::parse_line(&line, self.current_entry.as_ref().unwrap().path())
+    }
 
     fn has_next(&mut self) -> bool {
+        if self.open_error.is_some() {
+            return true;
+        }
+
         // Checks if we still have lines remaining in the buffer.
         if let Some(reader) = self.current_reader.as_mut() {
             if super::reader_has_data_left(reader) {
@@ -92,39 +121,51 @@ impl FormatParser for Yolo5TxtParser {
         }
 
         // If we've gotten here, it means we have to look
-        // for the next file, and if it exisits,
+        // for the next file with annotations, and if it exists,
         // open a buffer for it.
         let file_enumerator = self.file_enumerator.as_mut().unwrap();
 
-        self.current_entry = file_enumerator.find_map(|entry| {
-            let entry = match entry {
-                Ok(entry) => entry,
-                Err(_) => return None,
-            };
-
-            match entry.path().extension() {
-                Some(extension) => {
-                    if extension == "txt" {
-                        return Some(entry);
+        match super::open_next_label_file(file_enumerator, &mut self.empty_files) {
+            // Open the next reader and return true
+            Some((entry, reader)) => {
+                self.current_entry = Some(entry);
+                match reader {
+                    Ok(reader) => self.current_reader = Some(reader),
+                    Err(error) => {
+                        self.current_reader = None;
+                        self.open_error = Some(error);
                     }
-                    None
                 }
-                None => None,
-            }
-        });
-
-        match self.current_entry.as_ref() {
-            // Open the next reader and return true
-            Some(entry) => {
-                let file = std::fs::File::open(entry.path()).unwrap();
-                self.current_reader = Some(BufReader::new(file));
                 true
             }
             // Means we have reached the end of the folder
             None => {
+                self.current_entry = None;
                 self.current_reader = None;
                 false
             }
         }
     }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_entry.as_ref().map(|entry| entry.path())
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.source_files().ok().map(|files| files.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        super::take_empty_label_files(&mut self.empty_files)
+    }
+}
+
+impl MultiFileParser for Yolo5TxtParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        label_files(self.source_directory.as_deref())
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<models::Annotation, ParserError>> {
+        parse_lines(path, |line| Self::parse_line(line, path.to_path_buf()))
+    }
 }
@@ -5,7 +5,7 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use super::{FormatParser, ParserError};
+use super::{read_dir_sorted, DirEntries, FormatParser, ParserError};
 use crate::models::{annotation::ClassRepresentation, format::SourceType, Annotation, Image};
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
@@ -13,7 +13,7 @@ use std::path::PathBuf;
 pub struct Yolo8ObbParser {
     source_directory: PathBuf,
     current_reader: Option<BufReader<std::fs::File>>,
-    file_enumerator: Option<std::fs::ReadDir>,
+    file_enumerator: Option<DirEntries>,
     current_entry: Option<std::fs::DirEntry>,
 }
 
@@ -29,7 +29,7 @@ impl FormatParser for Yolo8ObbParser {
         }
 
         self.source_directory = path;
-        self.file_enumerator = Some(std::fs::read_dir(&self.source_directory)?);
+        self.file_enumerator = Some(read_dir_sorted(&self.source_directory)?);
         todo!()
     }
 
@@ -75,6 +75,7 @@ impl FormatParser for Yolo8ObbParser {
         Ok(Annotation {
             image: Image::empty(),
             difficulty: false,
+            truncated: false,
             class: ClassRepresentation::ClassId(class_index.to_string()),
             source_file: Some(current_entry.path()),
             x1: coordinates[0],
@@ -85,6 +86,8 @@ impl FormatParser for Yolo8ObbParser {
             y3: coordinates[5],
             x4: coordinates[6],
             y4: coordinates[7],
+            segmentation: None,
+            keypoints: None,
         })
     }
 
@@ -132,4 +135,8 @@ impl FormatParser for Yolo8ObbParser {
             }
         }
     }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_entry.as_ref().map(|entry| entry.path())
+    }
 }
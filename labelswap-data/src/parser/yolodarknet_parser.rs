@@ -5,33 +5,44 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use super::{FormatParser, ParserError};
-use crate::models::{annotation::ClassRepresentation, format::SourceType, Annotation, Image};
-use std::fs::{DirEntry, ReadDir};
+use super::{
+    label_files, parse_lines, read_dir_sorted, DirEntries, FormatParser, MultiFileParser,
+    ParserError,
+};
+use crate::class_map::{load_class_map, ClassMap};
+use crate::models::{
+    annotation::ClassRepresentation, format::SourceType, Annotation, Image, ImageRecord,
+};
+use std::fs::DirEntry;
 use std::io;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct YoloDarknetParser {
     source_directory: PathBuf,
-    file_enumerator: Option<ReadDir>,
+    file_enumerator: Option<DirEntries>,
     current_entry: Option<DirEntry>,
     current_reader: Option<BufReader<std::fs::File>>,
-    class_map: Vec<String>,
+    empty_files: Vec<PathBuf>,
+    /// The error opening the current file, returned by the next get_next
+    open_error: Option<ParserError>,
+    class_map: ClassMap,
 }
 
 impl YoloDarknetParser {
     pub fn new() -> Self {
         Self {
             source_directory: PathBuf::new(),
-            class_map: Vec::new(),
+            class_map: ClassMap::new(),
             file_enumerator: None,
             current_entry: None,
             current_reader: None,
+            empty_files: Vec::new(),
+            open_error: None,
         }
     }
 
-    fn parse_class_map(&mut self) -> Result<(), io::Error> {
+    fn parse_class_map(&mut self) -> Result<(), ParserError> {
         let file = std::fs::read_dir(&self.source_directory)?.find_map(|f| match f {
             Ok(f) => {
                 if f.file_name() == "darknet.labels" {
@@ -47,7 +58,8 @@ impl YoloDarknetParser {
                 return Err(io::Error::new(
                     io::ErrorKind::NotFound,
                     "darknet.labels file was not found",
-                ))
+                )
+                .into())
             }
         };
 
@@ -55,49 +67,17 @@ impl YoloDarknetParser {
             return Err(io::Error::new(
                 io::ErrorKind::NotFound,
                 "darknet.labels is expected to be a regular file",
-            ));
-        }
-
-        let file = std::fs::File::open(file.path())?;
-        let reader = std::io::BufReader::new(file);
-
-        for line in reader.lines() {
-            self.class_map.push(line?);
-        }
-
-        Ok(())
-    }
-}
-
-impl FormatParser for YoloDarknetParser {
-    fn init(&mut self, path: impl Into<std::path::PathBuf>) -> Result<(), ParserError> {
-        let path: PathBuf = path.into();
-        if !path.metadata().unwrap().is_dir() {
-            return Err(ParserError::WrongSource {
-                expected: SourceType::MultipleFiles,
-                found: SourceType::SingleFile,
-            });
+            )
+            .into());
         }
 
-        self.source_directory.push(path);
-        self.parse_class_map()?;
-        self.file_enumerator = std::fs::read_dir(&self.source_directory).ok();
-
+        self.class_map = load_class_map(file.path())
+            .map_err(|e| ParserError::WrongFormat(format!("Invalid darknet.labels: {e}")))?;
         Ok(())
     }
 
-    fn get_next(&mut self) -> Result<crate::models::Annotation, ParserError> {
-        let reader = self
-            .current_reader
-            .as_mut()
-            .ok_or(ParserError::OutOfElements)?;
-        let current_entry = self
-            .current_entry
-            .as_ref()
-            .ok_or(ParserError::OutOfElements)?;
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
-
+    fn parse_line(&self, line: &str, source_file: PathBuf) -> Result<Annotation, ParserError> {
+        let line = line.trim_end_matches(['\n', '\r']);
         let elements: Vec<&str> = line.split(' ').collect();
         if elements.len() != 5 {
             return Err(ParserError::WrongFormat(format!(
@@ -106,13 +86,13 @@ impl FormatParser for YoloDarknetParser {
             )));
         }
 
-        let class_id: usize = elements[0]
+        let class_id: u32 = elements[0]
             .parse()
             .map_err(|e| ParserError::Other(format!("Invalid Class ID: {e}")))?;
 
         // Indexing the class map to get the class name
-        let class_name = match self.class_map.get(class_id) {
-            Some(class_name) => class_name.clone(),
+        let class_name = match self.class_map.name(class_id) {
+            Some(class_name) => class_name.to_string(),
             None => return Err(ParserError::WrongFormat(format!(
                 "Class id {class_id} does not have a corresponding class name in darknet.labels"
             ))),
@@ -131,7 +111,7 @@ impl FormatParser for YoloDarknetParser {
         }
 
         Ok(Annotation {
-            source_file: Some(current_entry.path()),
+            source_file: Some(source_file),
             class: ClassRepresentation::Both {
                 name: class_name,
                 id: class_id.to_string(),
@@ -146,8 +126,55 @@ impl FormatParser for YoloDarknetParser {
             )
         })
     }
+}
+
+impl Default for YoloDarknetParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatParser for YoloDarknetParser {
+    fn init(&mut self, path: impl Into<std::path::PathBuf>) -> Result<(), ParserError> {
+        let path: PathBuf = path.into();
+        if !path.metadata().unwrap().is_dir() {
+            return Err(ParserError::WrongSource {
+                expected: SourceType::MultipleFiles,
+                found: SourceType::SingleFile,
+            });
+        }
+
+        self.source_directory.push(path);
+        self.parse_class_map()?;
+        self.file_enumerator = read_dir_sorted(&self.source_directory).ok();
+
+        Ok(())
+    }
+
+    fn get_next(&mut self) -> Result<crate::models::Annotation, ParserError> {
+        if let Some(error) = self.open_error.take() {
+            return Err(error);
+        }
+
+        let reader = self
+            .current_reader
+            .as_mut()
+            .ok_or(ParserError::OutOfElements)?;
+        let current_entry = self
+            .current_entry
+            .as_ref()
+            .ok_or(ParserError::OutOfElements)?;
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        self.parse_line(&line, current_entry.path())
+    }
 
     fn has_next(&mut self) -> bool {
+        if self.open_error.is_some() {
+            return true;
+        }
+
         // Checks if we still have lines remaining in the buffer.
         if let Some(reader) = self.current_reader.as_mut() {
             if super::reader_has_data_left(reader) {
@@ -156,39 +183,51 @@ impl FormatParser for YoloDarknetParser {
         }
 
         // If we've gotten here, it means we have to look
-        // for the next file, and if it exisits,
+        // for the next file with annotations, and if it exists,
         // open a buffer for it.
         let file_enumerator = self.file_enumerator.as_mut().unwrap();
 
-        self.current_entry = file_enumerator.find_map(|entry| {
-            let entry = match entry {
-                Ok(entry) => entry,
-                Err(_) => return None,
-            };
-
-            match entry.path().extension() {
-                Some(extension) => {
-                    if extension == "txt" {
-                        return Some(entry);
+        match super::open_next_label_file(file_enumerator, &mut self.empty_files) {
+            // Open the next reader and return true
+            Some((entry, reader)) => {
+                self.current_entry = Some(entry);
+                match reader {
+                    Ok(reader) => self.current_reader = Some(reader),
+                    Err(error) => {
+                        self.current_reader = None;
+                        self.open_error = Some(error);
                     }
-                    None
                 }
-                None => None,
-            }
-        });
-
-        match self.current_entry.as_ref() {
-            // Open the next reader and return true
-            Some(entry) => {
-                let file = std::fs::File::open(entry.path()).unwrap();
-                self.current_reader = Some(BufReader::new(file));
                 true
             }
             // Means we have reached the end of the folder
             None => {
+                self.current_entry = None;
                 self.current_reader = None;
                 false
             }
         }
     }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_entry.as_ref().map(|entry| entry.path())
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.source_files().ok().map(|files| files.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        super::take_empty_label_files(&mut self.empty_files)
+    }
+}
+
+impl MultiFileParser for YoloDarknetParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        label_files(Some(&self.source_directory))
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        parse_lines(path, |line| self.parse_line(line, path.to_path_buf()))
+    }
 }
@@ -0,0 +1,216 @@
+/* yolopose_parser.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use super::{
+    label_files, parse_lines, read_dir_sorted, DirEntries, FormatParser, MultiFileParser,
+    ParserError,
+};
+use crate::models::{
+    annotation::ClassRepresentation, format::SourceType, Annotation, Image, ImageRecord, Keypoint,
+    Keypoints, Visibility,
+};
+use std::{
+    fs::DirEntry,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// YoloPoseParser: reads Ultralytics YOLO pose labels, one text file per
+/// image with a `class x y width height px1 py1 v1 ... pxn pyn vn` line per
+/// object, in normalized coordinates. The visibility of the keypoints is
+/// optional, as set by the `kpt_shape` of the dataset.
+pub struct YoloPoseParser {
+    source_directory: Option<PathBuf>,
+    file_enumerator: Option<DirEntries>,
+    current_entry: Option<DirEntry>,
+    current_reader: Option<BufReader<std::fs::File>>,
+    empty_files: Vec<PathBuf>,
+    /// The error opening the current file, returned by the next get_next
+    open_error: Option<ParserError>,
+    visibility: bool,
+}
+
+impl YoloPoseParser {
+    pub fn new() -> Self {
+        Self {
+            source_directory: None,
+            file_enumerator: None,
+            current_entry: None,
+            current_reader: None,
+            empty_files: Vec::new(),
+            open_error: None,
+            visibility: true,
+        }
+    }
+
+    /// Whether every keypoint has a visibility after its coordinates, as
+    /// with a `kpt_shape` of [n, 3], the default. Without it, keypoints at
+    /// (0, 0) are not labeled and the others are visible.
+    pub fn set_visibility(&mut self, visibility: bool) {
+        self.visibility = visibility;
+    }
+
+    fn parse_line(
+        line: &str,
+        source_file: PathBuf,
+        visibility: bool,
+    ) -> Result<Annotation, ParserError> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let elements: Vec<&str> = line.split_whitespace().collect();
+        let values_per_point = if visibility { 3 } else { 2 };
+        if elements.len() < 5 || (elements.len() - 5) % values_per_point != 0 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected a class, a box and keypoints of {values_per_point} values in line '{line}', but got {} elements",
+                elements.len()
+            )));
+        }
+
+        let values: Vec<f64> = elements[1..]
+            .iter()
+            .filter_map(|c| c.parse().ok())
+            .collect();
+
+        if values.len() != elements.len() - 1 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected {} valid numbers in line '{line}', but got {}",
+                elements.len() - 1,
+                values.len()
+            )));
+        }
+
+        let points = values[4..]
+            .chunks_exact(values_per_point)
+            .map(|point| {
+                let visibility = match point.get(2) {
+                    Some(&value) => Some(value)
+                        .filter(|value| value.fract() == 0.0 && *value >= 0.0)
+                        .and_then(|value| Visibility::from_value(value as u64))
+                        .ok_or(ParserError::WrongFormat(format!(
+                            "Invalid keypoint visibility {value} in line '{line}'"
+                        )))?,
+                    None if point[0] == 0.0 && point[1] == 0.0 => Visibility::NotLabeled,
+                    None => Visibility::Visible,
+                };
+                Ok(Keypoint::new(point[0], point[1], visibility))
+            })
+            .collect::<Result<Vec<_>, ParserError>>()?;
+        let keypoints = if points.is_empty() {
+            None
+        } else {
+            Some(Keypoints::new(points, None))
+        };
+
+        Ok(Annotation {
+            source_file: Some(source_file),
+            class: ClassRepresentation::ClassId(elements[0].to_string()),
+            difficulty: false,
+            image: Image::empty(),
+            keypoints,
+            ..Annotation::from_centers(values[0], values[1], values[2], values[3])
+        })
+    }
+}
+
+impl Default for YoloPoseParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatParser for YoloPoseParser {
+    fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
+        let path: PathBuf = path.into();
+
+        if !path.metadata()?.is_dir() {
+            return Err(ParserError::WrongSource {
+                expected: SourceType::MultipleFiles,
+                found: SourceType::SingleFile,
+            });
+        }
+        self.file_enumerator = Some(read_dir_sorted(&path)?);
+        self.source_directory = Some(path);
+        Ok(())
+    }
+
+    fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        if let Some(error) = self.open_error.take() {
+            return Err(error);
+        }
+
+        let reader = self
+            .current_reader
+            .as_mut()
+            .ok_or(ParserError::OutOfElements)?;
+
+        let mut line = String::new();
+        let _ = reader.read_line(&mut line)?;
+
+        Self::parse_line(
+            &line,
+            self.current_entry.as_ref().unwrap().path(),
+            self.visibility,
+        )
+    }
+
+    fn has_next(&mut self) -> bool {
+        if self.open_error.is_some() {
+            return true;
+        }
+
+        // Checks if we still have lines remaining in the buffer.
+        if let Some(reader) = self.current_reader.as_mut() {
+            if super::reader_has_data_left(reader) {
+                return true;
+            }
+        }
+
+        let file_enumerator = self.file_enumerator.as_mut().unwrap();
+
+        match super::open_next_label_file(file_enumerator, &mut self.empty_files) {
+            Some((entry, reader)) => {
+                self.current_entry = Some(entry);
+                match reader {
+                    Ok(reader) => self.current_reader = Some(reader),
+                    Err(error) => {
+                        self.current_reader = None;
+                        self.open_error = Some(error);
+                    }
+                }
+                true
+            }
+            None => {
+                self.current_entry = None;
+                self.current_reader = None;
+                false
+            }
+        }
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_entry.as_ref().map(|entry| entry.path())
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.source_files().ok().map(|files| files.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        super::take_empty_label_files(&mut self.empty_files)
+    }
+}
+
+impl MultiFileParser for YoloPoseParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        label_files(self.source_directory.as_deref())
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        parse_lines(path, |line| {
+            Self::parse_line(line, path.to_path_buf(), self.visibility)
+        })
+    }
+}
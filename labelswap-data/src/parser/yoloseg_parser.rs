@@ -0,0 +1,182 @@
+/* yoloseg_parser.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use super::{
+    label_files, parse_lines, read_dir_sorted, DirEntries, FormatParser, MultiFileParser,
+    ParserError,
+};
+use crate::models::{
+    annotation::ClassRepresentation, format::SourceType, Annotation, Image, ImageRecord,
+    Segmentation,
+};
+use std::{
+    fs::DirEntry,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// YoloSegParser: reads YOLO v5 and v8 segmentation labels, one text file
+/// per image with a `class x1 y1 x2 y2 ... xn yn` line per object. The
+/// points form a polygon in normalized coordinates, its bounding box is
+/// used as the corners of the annotation.
+pub struct YoloSegParser {
+    source_directory: Option<PathBuf>,
+    file_enumerator: Option<DirEntries>,
+    current_entry: Option<DirEntry>,
+    current_reader: Option<BufReader<std::fs::File>>,
+    empty_files: Vec<PathBuf>,
+    /// The error opening the current file, returned by the next get_next
+    open_error: Option<ParserError>,
+}
+
+impl YoloSegParser {
+    pub fn new() -> Self {
+        Self {
+            source_directory: None,
+            file_enumerator: None,
+            current_entry: None,
+            current_reader: None,
+            empty_files: Vec::new(),
+            open_error: None,
+        }
+    }
+
+    fn parse_line(line: &str, source_file: PathBuf) -> Result<Annotation, ParserError> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let elements: Vec<&str> = line.split_whitespace().collect();
+        if elements.len() < 7 || elements.len() % 2 == 0 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected a class and at least 3 x, y pairs in line '{line}', but got {} elements",
+                elements.len()
+            )));
+        }
+
+        let coordinates: Vec<f64> = elements[1..]
+            .iter()
+            .filter_map(|c| c.parse().ok())
+            .collect();
+
+        if coordinates.len() != elements.len() - 1 {
+            return Err(ParserError::WrongFormat(format!(
+                "Expected {} valid coordinates in line '{line}', but got {}",
+                elements.len() - 1,
+                coordinates.len()
+            )));
+        }
+
+        let polygon = coordinates
+            .chunks_exact(2)
+            .map(|point| (point[0], point[1]))
+            .collect();
+        let annotation = Annotation::from_segmentation(Segmentation::Polygons(vec![polygon]))
+            .ok_or(ParserError::WrongFormat(format!(
+                "Expected a polygon in line '{line}'"
+            )))?;
+
+        Ok(Annotation {
+            source_file: Some(source_file),
+            class: ClassRepresentation::ClassId(elements[0].to_string()),
+            difficulty: false,
+            image: Image::empty(),
+            ..annotation
+        })
+    }
+}
+
+impl Default for YoloSegParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatParser for YoloSegParser {
+    fn init(&mut self, path: impl Into<PathBuf>) -> Result<(), ParserError> {
+        let path: PathBuf = path.into();
+
+        if !path.metadata()?.is_dir() {
+            return Err(ParserError::WrongSource {
+                expected: SourceType::MultipleFiles,
+                found: SourceType::SingleFile,
+            });
+        }
+        self.file_enumerator = Some(read_dir_sorted(&path)?);
+        self.source_directory = Some(path);
+        Ok(())
+    }
+
+    fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        if let Some(error) = self.open_error.take() {
+            return Err(error);
+        }
+
+        let reader = self
+            .current_reader
+            .as_mut()
+            .ok_or(ParserError::OutOfElements)?;
+
+        let mut line = String::new();
+        let _ = reader.read_line(&mut line)?;
+
+        Self::parse_line(&line, self.current_entry.as_ref().unwrap().path())
+    }
+
+    fn has_next(&mut self) -> bool {
+        if self.open_error.is_some() {
+            return true;
+        }
+
+        // Checks if we still have lines remaining in the buffer.
+        if let Some(reader) = self.current_reader.as_mut() {
+            if super::reader_has_data_left(reader) {
+                return true;
+            }
+        }
+
+        let file_enumerator = self.file_enumerator.as_mut().unwrap();
+
+        match super::open_next_label_file(file_enumerator, &mut self.empty_files) {
+            Some((entry, reader)) => {
+                self.current_entry = Some(entry);
+                match reader {
+                    Ok(reader) => self.current_reader = Some(reader),
+                    Err(error) => {
+                        self.current_reader = None;
+                        self.open_error = Some(error);
+                    }
+                }
+                true
+            }
+            None => {
+                self.current_entry = None;
+                self.current_reader = None;
+                false
+            }
+        }
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        self.current_entry.as_ref().map(|entry| entry.path())
+    }
+
+    fn total_files(&self) -> Option<usize> {
+        self.source_files().ok().map(|files| files.len())
+    }
+
+    fn take_empty_images(&mut self) -> Vec<ImageRecord> {
+        super::take_empty_label_files(&mut self.empty_files)
+    }
+}
+
+impl MultiFileParser for YoloSegParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        label_files(self.source_directory.as_deref())
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        parse_lines(path, |line| Self::parse_line(line, path.to_path_buf()))
+    }
+}
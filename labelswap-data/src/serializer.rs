@@ -2,13 +2,21 @@ mod format_serializer;
 mod yolo5obb_serializer;
 mod tfobjectdetection;
 mod cocojson;
+mod pascalvoc_serializer;
+mod yolodarknet_serializer;
+mod yolopose_serializer;
+mod yoloseg_serializer;
 
 pub use format_serializer::FormatSerializer;
 pub use yolo5obb_serializer::Yolo5ObbSerializer;
 pub use tfobjectdetection::TfObjectDetectionSerializer;
 pub use cocojson::CocoJsonSerializer;
+pub use pascalvoc_serializer::PascalVocSerializer;
+pub use yolodarknet_serializer::YoloDarknetSerializer;
+pub use yolopose_serializer::YoloPoseSerializer;
+pub use yoloseg_serializer::YoloSegSerializer;
 
-use crate::models::{annotation, format};
+use crate::models::format;
 
 #[derive(thiserror::Error, Debug)]
 pub enum SerializerError {
@@ -1,6 +1,9 @@
 use super::{FormatSerializer, SerializerError, SerializerResult};
-use crate::models::{annotation::ClassRepresentation, format::SourceType, Annotation};
-use std::collections::HashMap;
+use crate::models::{
+    annotation::ClassRepresentation, format::SourceType, Annotation, Image, ImageRecord, Keypoints,
+    RleCounts, Segmentation,
+};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use chrono::Datelike;
@@ -9,8 +12,8 @@ use serde_json as json;
 pub struct CocoJsonSerializer {
     destination: PathBuf,
     root_object: json::Value,
-    categories: HashMap<u32, json::Value>,
-    images: HashMap<u32, json::Value>,
+    categories: BTreeMap<u32, json::Value>,
+    images: BTreeMap<u32, json::Value>,
     annotations: Vec<json::Value>,
     datetime_now: chrono::DateTime<chrono::Utc>,
 }
@@ -20,18 +23,110 @@ impl CocoJsonSerializer {
         Self {
             destination: PathBuf::new(),
             root_object: json::Value::Null,
-            categories: HashMap::new(),
-            images: HashMap::new(),
+            categories: BTreeMap::new(),
+            images: BTreeMap::new(),
             annotations: Vec::new(),
             datetime_now: chrono::Utc::now(),
         }
     }
 }
 
+impl CocoJsonSerializer {
+    /// Adds the image to the document if it is not there yet and returns
+    /// its id.
+    fn add_image(&mut self, image: &Image) -> SerializerResult<u32> {
+        let image_id = image.id.ok_or(SerializerError::MissingImageId)?;
+        if self.images.contains_key(&image_id) {
+            return Ok(image_id);
+        }
+
+        let image_filename = image
+            .path
+            .as_ref()
+            .ok_or(SerializerError::MissingImagePath)?
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+
+        let height = image
+            .height
+            .ok_or(SerializerError::MissingImageDimensions(String::from(
+                "height",
+            )))?;
+
+        let width = image
+            .width
+            .ok_or(SerializerError::MissingImageDimensions(String::from(
+                "width",
+            )))?;
+
+        let image_object = json::json!({
+            "id": image_id,
+            "file_name": image_filename,
+            "height": height,
+            "width": width,
+            "date_captured": self.datetime_now.to_rfc3339(),
+        });
+        self.images.insert(image_id, image_object);
+        Ok(image_id)
+    }
+
+    /// The keypoints as a flat list of x, y, visibility triplets.
+    fn keypoints_value(keypoints: &Keypoints) -> json::Value {
+        keypoints
+            .points
+            .iter()
+            .flat_map(|point| {
+                [
+                    json::json!(point.x),
+                    json::json!(point.y),
+                    json::json!(point.visibility.value()),
+                ]
+            })
+            .collect()
+    }
+
+    /// The segmentation as COCO stores it: polygons as flat lists of
+    /// coordinates and masks as objects with their size and counts. Masks
+    /// are used by COCO for crowds, so annotations with them are written
+    /// with iscrowd set.
+    fn segmentation_value(segmentation: Option<&Segmentation>) -> json::Value {
+        match segmentation {
+            None => json::json!([]),
+            Some(Segmentation::Polygons(polygons)) => polygons
+                .iter()
+                .map(|polygon| {
+                    polygon
+                        .iter()
+                        .flat_map(|&(x, y)| [x, y])
+                        .collect::<Vec<f64>>()
+                })
+                .collect(),
+            Some(Segmentation::Rle(mask)) => {
+                let counts = match &mask.counts {
+                    RleCounts::Uncompressed(counts) => json::json!(counts),
+                    RleCounts::Compressed(counts) => json::json!(counts),
+                };
+                json::json!({
+                    "size": [mask.height, mask.width],
+                    "counts": counts,
+                })
+            }
+        }
+    }
+}
+
+impl Default for CocoJsonSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormatSerializer for CocoJsonSerializer {
     fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
         let mut path: PathBuf = path.into();
-        if !path.metadata()?.is_file() {
+        if path.is_dir() {
             return Err(SerializerError::WrongDestination {
                 expected: SourceType::SingleFile,
                 found: SourceType::MultipleFiles,
@@ -88,55 +183,41 @@ impl FormatSerializer for CocoJsonSerializer {
         // We have to add the images and categories to the format map,
         // as must add them to the final document
 
-        if !self.categories.contains_key(&class_id) {
-            let category = json::json!({
+        let category = self.categories.entry(class_id).or_insert_with(|| {
+            json::json!({
                 "id": class_id,
                 "name": class_name.clone(),
                 "supercategory": "none",
-            });
-            self.categories.insert(class_id, category);
-        }
-
-        let image_id = annotation.image.id.ok_or(SerializerError::MissingImageId)?;
-        let image_filename = annotation
-            .image
-            .path
+            })
+        });
+        if let Some(skeleton) = annotation
+            .keypoints
             .as_ref()
-            .ok_or(SerializerError::MissingImagePath)?
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
-
-        let height = annotation
-            .image
-            .height
-            .ok_or(SerializerError::MissingImageDimensions(String::from(
-                "height",
-            )))?;
-
-        let width = annotation
-            .image
-            .height
-            .ok_or(SerializerError::MissingImageDimensions(String::from(
-                "width",
-            )))?;
-
-        if !self.images.contains_key(&image_id) {
-            let image_object = json::json!({
-                "id": image_id,
-                "file_name": image_filename,
-                "height": height,
-                "width": width,
-                "date_captured": self.datetime_now.to_rfc3339(),
-            });
-            self.images.insert(image_id, image_object);
+            .and_then(|keypoints| keypoints.skeleton.as_ref())
+        {
+            let category = category.as_object_mut().unwrap();
+            if !category.contains_key("keypoints") {
+                // COCO numbers the keypoints of the skeleton from 1
+                let edges: Vec<[usize; 2]> = skeleton
+                    .edges
+                    .iter()
+                    .map(|&(from, to)| [from + 1, to + 1])
+                    .collect();
+                category.insert("keypoints".into(), json::json!(skeleton.names));
+                category.insert("skeleton".into(), json::json!(edges));
+            }
         }
 
+        let image_id = self.add_image(&annotation.image)?;
+
         let annot_width = annotation.get_xmax() - annotation.get_xmin();
         let annot_height = annotation.get_ymax() - annotation.get_ymin();
+        let area = match &annotation.segmentation {
+            Some(segmentation) => segmentation.area(),
+            None => annot_width * annot_height,
+        };
 
-        let annotation = json::json!({
+        let mut annotation_object = json::json!({
             "id": self.annotations.len(),
             "image_id": image_id,
             "category_id": class_id,
@@ -146,12 +227,29 @@ impl FormatSerializer for CocoJsonSerializer {
                 annot_width,
                 annot_height,
             ],
-            "area": annot_width * annot_height,
-            "iscrowd": 0,
-            "segmentation": [],
+            "area": area,
+            "iscrowd": i32::from(matches!(annotation.segmentation, Some(Segmentation::Rle(_)))),
+            "segmentation": Self::segmentation_value(annotation.segmentation.as_ref()),
         });
+        if let Some(keypoints) = annotation.keypoints.as_ref() {
+            let object = annotation_object.as_object_mut().unwrap();
+            object.insert("keypoints".into(), Self::keypoints_value(keypoints));
+            object.insert("num_keypoints".into(), json::json!(keypoints.labeled()));
+        }
+
+        self.annotations.push(annotation_object);
+        Ok(())
+    }
+
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        // Images without annotations are still part of the dataset
+        if image.is_empty() {
+            self.add_image(&image.image)?;
+        }
 
-        self.annotations.push(annotation);
+        for annotation in image.annotations {
+            self.push(annotation)?;
+        }
         Ok(())
     }
 
@@ -164,8 +262,9 @@ impl FormatSerializer for CocoJsonSerializer {
         root_map.insert("images".into(), json::Value::Array(images));
         root_map.insert("annotations".into(), json::Value::Array(self.annotations));
 
-        let stream = std::fs::File::open(self.destination)?;
-        json::to_writer_pretty(stream, &self.root_object);
+        let stream = std::fs::File::create(self.destination)?;
+        json::to_writer_pretty(stream, &self.root_object)
+            .map_err(|e| SerializerError::Other(e.to_string()))?;
 
         Ok(())
     }
@@ -5,7 +5,7 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::models::Annotation;
+use crate::models::{Annotation, ImageRecord};
 use std::path::PathBuf;
 
 use super::SerializerResult;
@@ -15,6 +15,15 @@ pub trait FormatSerializer {
     fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()>;
     // Adds an annotation to self
     fn push(&mut self, annotation: Annotation) -> SerializerResult<()>;
+    /// Adds an image along with its annotations. Serializers of formats that
+    /// can store images without annotations keep them, the others only
+    /// push the annotations.
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        for annotation in image.annotations {
+            self.push(annotation)?;
+        }
+        Ok(())
+    }
     /// Gives up ownership and completes the serialization of self.
     fn finish(self) -> SerializerResult<()>;
 }
@@ -0,0 +1,256 @@
+/* pascalvoc_serializer.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::models::{format::SourceType, Annotation, Image, ImageRecord};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use super::{FormatSerializer, SerializerError, SerializerResult};
+
+/// PascalVocSerializer: writes Pascal VOC labels, one XML file per image
+/// named after it. Boxes are written in whole pixels with the 1-based
+/// convention of VOC: `xmin` and `ymin` are the first pixels inside the
+/// box, `xmax` and `ymax` the last ones.
+pub struct PascalVocSerializer {
+    destination: Option<PathBuf>,
+    image_map: HashMap<PathBuf, (Image, Vec<Annotation>)>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename = "annotation")]
+struct Document {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    folder: Option<String>,
+    filename: String,
+    size: Size,
+    segmented: u8,
+    #[serde(rename = "object")]
+    objects: Vec<Object>,
+}
+
+#[derive(Debug, Serialize)]
+struct Size {
+    width: u32,
+    height: u32,
+    depth: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct Object {
+    name: String,
+    pose: &'static str,
+    truncated: u8,
+    difficult: u8,
+    bndbox: BndBox,
+}
+
+#[derive(Debug, Serialize)]
+struct BndBox {
+    xmin: u32,
+    ymin: u32,
+    xmax: u32,
+    ymax: u32,
+}
+
+impl PascalVocSerializer {
+    pub fn new() -> Self {
+        Self {
+            destination: None,
+            image_map: HashMap::new(),
+        }
+    }
+
+    /// The width and height of an image, which VOC requires.
+    fn dimensions(image: &Image) -> SerializerResult<(u32, u32)> {
+        let width = image
+            .width
+            .ok_or(SerializerError::MissingImageDimensions(String::from(
+                "width",
+            )))?;
+        let height = image
+            .height
+            .ok_or(SerializerError::MissingImageDimensions(String::from(
+                "height",
+            )))?;
+        Ok((width, height))
+    }
+
+    /// The first and last 1-based pixels between `min` and `max`, inside
+    /// an image of `size` pixels. Boxes are at least one pixel wide.
+    fn pixel_range(min: f64, max: f64, size: u32) -> (u32, u32) {
+        let size = f64::from(size.max(1));
+        let first = (min.round() + 1.0).clamp(1.0, size);
+        let last = max.round().clamp(first, size);
+        (first as u32, last as u32)
+    }
+
+    fn to_object(annotation: &Annotation, width: u32, height: u32) -> SerializerResult<Object> {
+        let name = annotation
+            .class
+            .name()
+            .ok_or(SerializerError::MissingClassName)?
+            .to_string();
+
+        let (xmin, xmax) = Self::pixel_range(annotation.get_xmin(), annotation.get_xmax(), width);
+        let (ymin, ymax) = Self::pixel_range(annotation.get_ymin(), annotation.get_ymax(), height);
+
+        Ok(Object {
+            name,
+            pose: "Unspecified",
+            truncated: u8::from(annotation.truncated),
+            difficult: u8::from(annotation.difficulty),
+            bndbox: BndBox {
+                xmin,
+                ymin,
+                xmax,
+                ymax,
+            },
+        })
+    }
+
+    fn to_xml(
+        image_path: &Path,
+        image: &Image,
+        annotations: &[Annotation],
+    ) -> SerializerResult<String> {
+        let (width, height) = Self::dimensions(image)?;
+        let objects = annotations
+            .iter()
+            .map(|annotation| Self::to_object(annotation, width, height))
+            .collect::<SerializerResult<Vec<Object>>>()?;
+
+        let document = Document {
+            folder: image_path
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .map(|folder| folder.to_string_lossy().to_string()),
+            filename: image_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            size: Size {
+                width,
+                height,
+                depth: 3,
+            },
+            segmented: 0,
+            objects,
+        };
+
+        let mut xml = String::new();
+        let mut serializer = quick_xml::se::Serializer::new(&mut xml);
+        serializer.indent(' ', 4);
+        document
+            .serialize(serializer)
+            .map_err(|e| SerializerError::Other(e.to_string()))?;
+        xml.push('\n');
+        Ok(xml)
+    }
+}
+
+impl Default for PascalVocSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatSerializer for PascalVocSerializer {
+    fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
+        let path: PathBuf = path.into();
+
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+
+        if !path.is_dir() {
+            return Err(SerializerError::WrongDestination {
+                expected: SourceType::MultipleFiles,
+                found: SourceType::SingleFile,
+            });
+        }
+
+        self.destination = Some(path);
+
+        Ok(())
+    }
+
+    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+        let image_path = annotation
+            .image
+            .path
+            .clone()
+            .ok_or(SerializerError::MissingImagePath)?;
+        if annotation.class.name().is_none() {
+            return Err(SerializerError::MissingClassName);
+        }
+        Self::dimensions(&annotation.image)?;
+
+        self.image_map
+            .entry(image_path)
+            .or_insert_with(|| (annotation.image.clone(), Vec::new()))
+            .1
+            .push(annotation);
+        Ok(())
+    }
+
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        if !image.is_empty() {
+            for annotation in image.annotations {
+                self.push(annotation)?;
+            }
+            return Ok(());
+        }
+
+        // An image without annotations is written as a file without objects
+        let image_path = image
+            .image
+            .path
+            .clone()
+            .ok_or(SerializerError::MissingImagePath)?;
+        Self::dimensions(&image.image)?;
+        self.image_map
+            .entry(image_path)
+            .or_insert_with(|| (image.image, Vec::new()));
+        Ok(())
+    }
+
+    fn finish(self) -> SerializerResult<()> {
+        let destination = self
+            .destination
+            .as_ref()
+            .ok_or(SerializerError::Other(String::from(
+                "The serializer has not been initialized",
+            )))?;
+
+        for (image_path, (image, annotations)) in self.image_map {
+            let file_name = match image_path.file_stem() {
+                Some(stem) => {
+                    let mut file_name = stem.to_os_string();
+                    file_name.push(".xml");
+                    PathBuf::from(file_name)
+                }
+                None => {
+                    return Err(SerializerError::Other(format!(
+                        "Could not get a file name for {}",
+                        image_path.display()
+                    )))
+                }
+            };
+
+            let relative_path = crate::resolve_relative_path(destination, &file_name)?;
+
+            let xml = Self::to_xml(&image_path, &image, &annotations)?;
+            std::fs::write(relative_path, xml)?;
+        }
+
+        Ok(())
+    }
+}
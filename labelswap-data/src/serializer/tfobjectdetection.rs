@@ -20,11 +20,26 @@ struct Record {
     ymax: f64,
 }
 
+impl TfObjectDetectionSerializer {
+    pub fn new() -> Self {
+        Self {
+            destination: PathBuf::new(),
+            writer: None,
+        }
+    }
+}
+
+impl Default for TfObjectDetectionSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormatSerializer for TfObjectDetectionSerializer {
     fn init(&mut self, path: impl Into<std::path::PathBuf>) -> SerializerResult<()> {
         let mut path: PathBuf = path.into();
 
-        if !path.metadata()?.is_file() {
+        if path.is_dir() {
             return Err(SerializerError::WrongDestination {
                 expected: SourceType::SingleFile,
                 found: SourceType::MultipleFiles,
@@ -45,9 +60,13 @@ impl FormatSerializer for TfObjectDetectionSerializer {
             }
         };
 
-        let mut writer = csv::Writer::from_path(&path)?;
-        writer.write_record(&[
-            "filename", "width", "height", "class", "xmin", "ymin", "xmax", "xmin",
+        // The header is written by hand so that it is present even when no
+        // annotation gets pushed.
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_path(&path)?;
+        writer.write_record([
+            "filename", "width", "height", "class", "xmin", "ymin", "xmax", "ymax",
         ])?;
         self.writer = Some(writer);
         self.destination.push(path);
@@ -77,7 +96,7 @@ impl FormatSerializer for TfObjectDetectionSerializer {
             .image
             .height
             .ok_or(SerializerError::MissingImageDimensions(String::from(
-                "Width",
+                "Height",
             )))?;
 
         let tf_record = Record {
@@ -5,7 +5,7 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::models::{annotation::ClassRepresentation, Annotation};
+use crate::models::{annotation::ClassRepresentation, Annotation, ImageRecord, Winding};
 use std::{
     collections::HashMap,
     fs::File,
@@ -18,6 +18,7 @@ use super::{FormatSerializer, SerializerError, SerializerResult};
 pub struct Yolo5ObbSerializer {
     destination: Option<PathBuf>,
     annotation_map: HashMap<PathBuf, Vec<Annotation>>,
+    winding: Option<Winding>,
 }
 
 impl Yolo5ObbSerializer {
@@ -25,26 +26,33 @@ impl Yolo5ObbSerializer {
         Self {
             destination: None,
             annotation_map: HashMap::new(),
+            winding: None,
         }
     }
 
+    /// Writes the corners following `winding` from the top-left-most one.
+    /// With None, the default, they are written in the order they have.
+    pub fn set_winding(&mut self, winding: Option<Winding>) {
+        self.winding = winding;
+    }
+
     fn write_to_file(path: &Path, annotations: &[Annotation]) -> SerializerResult<()> {
-        let mut stream = File::open(path)?;
+        let mut stream = File::create(path)?;
 
         for annotation in annotations {
             let class = match annotation.class.as_ref() {
                 ClassRepresentation::ClassName(name) => name,
                 ClassRepresentation::Both { name, .. } => name,
                 _ => {
-                    return Err(SerializerError::WrongClassRepresentation(format!(
-                        "Expected class representation to contain name"
+                    return Err(SerializerError::WrongClassRepresentation(String::from(
+                        "Expected class representation to contain name",
                     )))
                 }
             };
 
             let difficulty = if annotation.difficulty { 1 } else { 0 };
             let format = format!(
-                "{} {} {} {} {} {} {} {} {} {}",
+                "{} {} {} {} {} {} {} {} {} {}\n",
                 annotation.x1,
                 annotation.y1,
                 annotation.x2,
@@ -56,17 +64,27 @@ impl Yolo5ObbSerializer {
                 class,
                 difficulty
             );
-            stream.write(format.as_bytes())?;
+            stream.write_all(format.as_bytes())?;
         }
 
         Ok(())
     }
 }
 
+impl Default for Yolo5ObbSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl FormatSerializer for Yolo5ObbSerializer {
     fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
         let path: PathBuf = path.into();
 
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+
         if !path.is_dir() {
             return Err(SerializerError::WrongDestination {
                 expected: crate::models::format::SourceType::MultipleFiles,
@@ -74,16 +92,16 @@ impl FormatSerializer for Yolo5ObbSerializer {
             });
         }
 
-        if !path.exists() {
-            std::fs::create_dir_all(&path);
-        }
-
         self.destination = Some(path);
 
         Ok(())
     }
 
-    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+    fn push(&mut self, mut annotation: Annotation) -> SerializerResult<()> {
+        if let Some(winding) = self.winding {
+            annotation.order_corners(winding);
+        }
+
         let path = match annotation.source_file.as_ref() {
             Some(path) => path,
             None => return Err(SerializerError::MissingSourceFile),
@@ -101,6 +119,25 @@ impl FormatSerializer for Yolo5ObbSerializer {
         Ok(())
     }
 
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        if !image.is_empty() {
+            for annotation in image.annotations {
+                self.push(annotation)?;
+            }
+            return Ok(());
+        }
+
+        // An image without annotations is written as an empty label file,
+        // named after its source file or, if it has none, after the image.
+        let path = match (image.source_file, image.image.path) {
+            (Some(source_file), _) => source_file,
+            (None, Some(image_path)) => image_path.with_extension("txt"),
+            (None, None) => return Err(SerializerError::MissingSourceFile),
+        };
+        self.annotation_map.entry(path).or_default();
+        Ok(())
+    }
+
     fn finish(self) -> SerializerResult<()> {
         // TODO: Remove unwrap
         let destination = self.destination.as_ref().unwrap();
@@ -109,18 +146,14 @@ impl FormatSerializer for Yolo5ObbSerializer {
             let file_name = match path.file_name() {
                 Some(file_name) => PathBuf::from(file_name),
                 None => {
-                    eprintln!("Could not get a file name for path {:?}", path);
-                    continue;
+                    return Err(SerializerError::Other(format!(
+                        "Could not get a file name for {}",
+                        path.display()
+                    )))
                 }
             };
 
-            let relative_path = match crate::resolve_relative_path(destination, &file_name) {
-                Ok(relative_path) => relative_path,
-                Err(e) => {
-                    eprintln!("Failed to parse {:?}, got {}", file_name, e);
-                    continue;
-                }
-            };
+            let relative_path = crate::resolve_relative_path(destination, &file_name)?;
 
             Self::write_to_file(&relative_path, &annotations)?;
         }
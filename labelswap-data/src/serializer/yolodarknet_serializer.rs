@@ -0,0 +1,250 @@
+/* yolodarknet_serializer.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::class_map::ClassMap;
+use crate::models::{annotation::ClassRepresentation, Annotation, Image, ImageRecord};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use super::{FormatSerializer, SerializerError, SerializerResult};
+
+/// YoloDarknetSerializer: writes YOLO Darknet labels, one text file per
+/// image with a `class cx cy w h` line per annotation, and the
+/// `darknet.labels` file listing the name of every class id, one per line.
+/// The classes are the ones of the class map, if given, along with the ones
+/// of the annotations written.
+pub struct YoloDarknetSerializer {
+    destination: Option<PathBuf>,
+    annotation_map: HashMap<PathBuf, LabelFile>,
+    class_map: ClassMap,
+    write_labels: bool,
+}
+
+/// The annotations of a label file, along with the path it is named after.
+struct LabelFile {
+    named_after: PathBuf,
+    annotations: Vec<Annotation>,
+}
+
+impl YoloDarknetSerializer {
+    pub fn new() -> Self {
+        Self {
+            destination: None,
+            annotation_map: HashMap::new(),
+            class_map: ClassMap::new(),
+            write_labels: true,
+        }
+    }
+
+    /// Sets the classes written to `darknet.labels`, even those without
+    /// annotations.
+    pub fn set_class_map(&mut self, class_map: ClassMap) {
+        self.class_map = class_map;
+    }
+
+    /// Sets whether `darknet.labels` is written, which it is by default.
+    /// It can be left to whoever knows the classes better, such as a
+    /// MappingGenerator writing every generated class.
+    pub fn set_write_labels(&mut self, write_labels: bool) {
+        self.write_labels = write_labels;
+    }
+
+    /// The label file of an image, named after the image or, if it has no
+    /// path, after its source file. Returns it along with the path it is
+    /// named after, or fails if another image has the same name, as both
+    /// would be written to the same file.
+    fn label_file(
+        &self,
+        image: &Image,
+        source_file: Option<&Path>,
+    ) -> SerializerResult<(PathBuf, PathBuf)> {
+        let named_after = image
+            .path
+            .as_deref()
+            .or(source_file)
+            .ok_or(SerializerError::MissingSourceFile)?;
+        let stem = named_after
+            .file_stem()
+            .ok_or(SerializerError::MissingSourceFile)?;
+        let mut file_name = stem.to_os_string();
+        file_name.push(".txt");
+        let file_name = PathBuf::from(file_name);
+
+        match self.annotation_map.get(&file_name) {
+            Some(label_file) if label_file.named_after != named_after => {
+                Err(SerializerError::Other(format!(
+                    "{} and {} would both be written to {}",
+                    label_file.named_after.display(),
+                    named_after.display(),
+                    file_name.display()
+                )))
+            }
+            _ => Ok((file_name, named_after.to_path_buf())),
+        }
+    }
+
+    /// Adds `annotation` to the label file `file_name`, if given.
+    fn add_to_label_file(
+        &mut self,
+        (file_name, named_after): (PathBuf, PathBuf),
+        annotation: Option<Annotation>,
+    ) {
+        let label_file = self
+            .annotation_map
+            .entry(file_name)
+            .or_insert_with(|| LabelFile {
+                named_after,
+                annotations: Vec::new(),
+            });
+        label_file.annotations.extend(annotation);
+    }
+
+    /// Adds the class of an annotation to the class map, or checks that it
+    /// matches the class already there.
+    fn add_class(&mut self, class: &ClassRepresentation<String>) -> SerializerResult<()> {
+        let (name, id) = match class.as_ref() {
+            ClassRepresentation::Both { name, id } => (name, id),
+            ClassRepresentation::ClassName(_) => return Err(SerializerError::MissingClassID),
+            _ => return Err(SerializerError::MissingClassName),
+        };
+        let id: u32 = id
+            .parse()
+            .map_err(|_| SerializerError::WrongClassRepresentation(id.clone()))?;
+
+        match (self.class_map.name(id), self.class_map.id(name)) {
+            (Some(known_name), Some(known_id)) if known_name == name && known_id == id => Ok(()),
+            (None, None) => self
+                .class_map
+                .insert(id, name.clone())
+                .map_err(|e| SerializerError::Other(e.to_string())),
+            _ => Err(SerializerError::Other(format!(
+                "Class '{name}' with id {id} does not match the classes written before"
+            ))),
+        }
+    }
+
+    fn write_to_file(path: &Path, annotations: &[Annotation]) -> SerializerResult<()> {
+        let mut stream = File::create(path)?;
+
+        for annotation in annotations {
+            let class = annotation
+                .class
+                .id()
+                .ok_or(SerializerError::MissingClassID)?;
+
+            let (x_min, x_max) = (annotation.get_xmin(), annotation.get_xmax());
+            let (y_min, y_max) = (annotation.get_ymin(), annotation.get_ymax());
+            let line = format!(
+                "{} {} {} {} {}\n",
+                class,
+                (x_min + x_max) / 2.0,
+                (y_min + y_max) / 2.0,
+                x_max - x_min,
+                y_max - y_min
+            );
+            stream.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// The content of `darknet.labels`. The id of a class is its line, so
+    /// it fails if an id below the highest one does not have a name.
+    fn labels(&self) -> SerializerResult<String> {
+        let Some((last_id, _)) = self.class_map.iter().last() else {
+            return Ok(String::new());
+        };
+
+        let mut labels = String::new();
+        for id in 0..=last_id {
+            let Some(name) = self.class_map.name(id) else {
+                return Err(SerializerError::Other(format!(
+                    "Class id {id} does not have a name, every id up to {last_id} needs one"
+                )));
+            };
+            labels.push_str(name);
+            labels.push('\n');
+        }
+        Ok(labels)
+    }
+}
+
+impl Default for YoloDarknetSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatSerializer for YoloDarknetSerializer {
+    fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
+        let path: PathBuf = path.into();
+
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+
+        if !path.is_dir() {
+            return Err(SerializerError::WrongDestination {
+                expected: crate::models::format::SourceType::MultipleFiles,
+                found: crate::models::format::SourceType::SingleFile,
+            });
+        }
+
+        self.destination = Some(path);
+
+        Ok(())
+    }
+
+    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+        let label_file = self.label_file(&annotation.image, annotation.source_file.as_deref())?;
+        self.add_class(&annotation.class)?;
+        self.add_to_label_file(label_file, Some(annotation));
+        Ok(())
+    }
+
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        if !image.is_empty() {
+            for annotation in image.annotations {
+                self.push(annotation)?;
+            }
+            return Ok(());
+        }
+
+        // An image without annotations is written as an empty label file
+        let label_file = self.label_file(&image.image, image.source_file.as_deref())?;
+        self.add_to_label_file(label_file, None);
+        Ok(())
+    }
+
+    fn finish(self) -> SerializerResult<()> {
+        let destination = self
+            .destination
+            .as_ref()
+            .ok_or(SerializerError::Other(String::from(
+                "The serializer has not been initialized",
+            )))?;
+
+        let labels = match self.write_labels {
+            true => Some(self.labels()?),
+            false => None,
+        };
+        for (file_name, label_file) in self.annotation_map.iter() {
+            let relative_path = crate::resolve_relative_path(destination, file_name)?;
+            Self::write_to_file(&relative_path, &label_file.annotations)?;
+        }
+
+        if let Some(labels) = labels {
+            let path = crate::resolve_relative_path(destination, "darknet.labels")?;
+            std::fs::write(path, labels)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+/* yolopose_serializer.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::models::{annotation::ClassRepresentation, Annotation, ImageRecord, Visibility};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use super::{FormatSerializer, SerializerError, SerializerResult};
+
+/// YoloPoseSerializer: writes Ultralytics YOLO pose labels. Every line of
+/// a dataset must have the same number of keypoints, so annotations with
+/// fewer keypoints than others are completed with keypoints that are not
+/// labeled, written as zeros.
+pub struct YoloPoseSerializer {
+    destination: Option<PathBuf>,
+    annotation_map: HashMap<PathBuf, Vec<Annotation>>,
+    visibility: bool,
+}
+
+impl YoloPoseSerializer {
+    pub fn new() -> Self {
+        Self {
+            destination: None,
+            annotation_map: HashMap::new(),
+            visibility: true,
+        }
+    }
+
+    /// Whether to write the visibility of every keypoint after its
+    /// coordinates, for a `kpt_shape` of [n, 3], the default.
+    pub fn set_visibility(&mut self, visibility: bool) {
+        self.visibility = visibility;
+    }
+
+    fn write_to_file(
+        &self,
+        path: &Path,
+        annotations: &[Annotation],
+        keypoint_count: usize,
+    ) -> SerializerResult<()> {
+        let mut stream = File::create(path)?;
+
+        for annotation in annotations {
+            let class = match annotation.class.as_ref() {
+                ClassRepresentation::ClassId(id) => id,
+                ClassRepresentation::Both { id, .. } => id,
+                _ => return Err(SerializerError::MissingClassID),
+            };
+
+            let (x_min, x_max) = (annotation.get_xmin(), annotation.get_xmax());
+            let (y_min, y_max) = (annotation.get_ymin(), annotation.get_ymax());
+            let mut line = format!(
+                "{} {} {} {} {}",
+                class,
+                (x_min + x_max) / 2.0,
+                (y_min + y_max) / 2.0,
+                x_max - x_min,
+                y_max - y_min
+            );
+
+            let points = annotation
+                .keypoints
+                .as_ref()
+                .map(|keypoints| keypoints.points.as_slice())
+                .unwrap_or_default();
+            for i in 0..keypoint_count {
+                let (x, y, visibility) = match points.get(i) {
+                    Some(point) if point.visibility != Visibility::NotLabeled => {
+                        (point.x, point.y, point.visibility.value())
+                    }
+                    _ => (0.0, 0.0, 0),
+                };
+                line.push_str(&format!(" {x} {y}"));
+                if self.visibility {
+                    line.push_str(&format!(" {visibility}"));
+                }
+            }
+            line.push('\n');
+            stream.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for YoloPoseSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatSerializer for YoloPoseSerializer {
+    fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
+        let path: PathBuf = path.into();
+
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+
+        if !path.is_dir() {
+            return Err(SerializerError::WrongDestination {
+                expected: crate::models::format::SourceType::MultipleFiles,
+                found: crate::models::format::SourceType::SingleFile,
+            });
+        }
+
+        self.destination = Some(path);
+
+        Ok(())
+    }
+
+    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+        let path = match annotation.source_file.as_ref() {
+            Some(path) => path.clone(),
+            None => return Err(SerializerError::MissingSourceFile),
+        };
+
+        self.annotation_map
+            .entry(path)
+            .or_default()
+            .push(annotation);
+        Ok(())
+    }
+
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        if !image.is_empty() {
+            for annotation in image.annotations {
+                self.push(annotation)?;
+            }
+            return Ok(());
+        }
+
+        // An image without annotations is written as an empty label file,
+        // named after its source file or, if it has none, after the image.
+        let path = match (image.source_file, image.image.path) {
+            (Some(source_file), _) => source_file,
+            (None, Some(image_path)) => image_path.with_extension("txt"),
+            (None, None) => return Err(SerializerError::MissingSourceFile),
+        };
+        self.annotation_map.entry(path).or_default();
+        Ok(())
+    }
+
+    fn finish(self) -> SerializerResult<()> {
+        let destination = self
+            .destination
+            .as_ref()
+            .ok_or(SerializerError::Other(String::from(
+                "The serializer has not been initialized",
+            )))?;
+
+        let keypoint_count = self
+            .annotation_map
+            .values()
+            .flatten()
+            .filter_map(|annotation| annotation.keypoints.as_ref())
+            .map(|keypoints| keypoints.points.len())
+            .max()
+            .unwrap_or_default();
+
+        for (path, annotations) in self.annotation_map.iter() {
+            let file_name = match path.file_name() {
+                Some(file_name) => PathBuf::from(file_name),
+                None => {
+                    return Err(SerializerError::Other(format!(
+                        "Could not get a file name for {}",
+                        path.display()
+                    )))
+                }
+            };
+
+            let relative_path = crate::resolve_relative_path(destination, &file_name)?;
+
+            self.write_to_file(&relative_path, annotations, keypoint_count)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+/* yoloseg_serializer.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use crate::models::{annotation::ClassRepresentation, Annotation, ImageRecord, Segmentation};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use super::{FormatSerializer, SerializerError, SerializerResult};
+
+/// YoloSegSerializer: writes YOLO v5 and v8 segmentation labels. Annotations
+/// without a polygon are written with the four corners of their box.
+pub struct YoloSegSerializer {
+    destination: Option<PathBuf>,
+    annotation_map: HashMap<PathBuf, Vec<Annotation>>,
+}
+
+impl YoloSegSerializer {
+    pub fn new() -> Self {
+        Self {
+            destination: None,
+            annotation_map: HashMap::new(),
+        }
+    }
+
+    /// The polygon written for an annotation. YOLO stores one polygon per
+    /// object, so the parts of an object split in several polygons are
+    /// joined by going to the closest point of the next part and back.
+    fn polygon(annotation: &Annotation) -> Vec<(f64, f64)> {
+        let polygons = match annotation.segmentation.as_ref() {
+            Some(Segmentation::Polygons(polygons)) if !polygons.is_empty() => polygons,
+            _ => return annotation.corners().to_vec(),
+        };
+
+        let mut joined = polygons[0].clone();
+        for polygon in polygons[1..].iter().filter(|polygon| !polygon.is_empty()) {
+            let Some(&(x, y)) = joined.last() else {
+                joined.clone_from(polygon);
+                continue;
+            };
+            let distance = |&(px, py): &(f64, f64)| (px - x).hypot(py - y);
+            let closest = (0..polygon.len())
+                .min_by(|&a, &b| distance(&polygon[a]).total_cmp(&distance(&polygon[b])))
+                .unwrap_or_default();
+
+            joined.extend(polygon[closest..].iter().chain(polygon[..=closest].iter()));
+            joined.push((x, y));
+        }
+        joined
+    }
+
+    fn write_to_file(path: &Path, annotations: &[Annotation]) -> SerializerResult<()> {
+        let mut stream = File::create(path)?;
+
+        for annotation in annotations {
+            let class = match annotation.class.as_ref() {
+                ClassRepresentation::ClassId(id) => id,
+                ClassRepresentation::Both { id, .. } => id,
+                _ => return Err(SerializerError::MissingClassID),
+            };
+
+            let mut line = class.clone();
+            for (x, y) in Self::polygon(annotation) {
+                line.push_str(&format!(" {x} {y}"));
+            }
+            line.push('\n');
+            stream.write_all(line.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for YoloSegSerializer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatSerializer for YoloSegSerializer {
+    fn init(&mut self, path: impl Into<PathBuf>) -> SerializerResult<()> {
+        let path: PathBuf = path.into();
+
+        if !path.exists() {
+            std::fs::create_dir_all(&path)?;
+        }
+
+        if !path.is_dir() {
+            return Err(SerializerError::WrongDestination {
+                expected: crate::models::format::SourceType::MultipleFiles,
+                found: crate::models::format::SourceType::SingleFile,
+            });
+        }
+
+        self.destination = Some(path);
+
+        Ok(())
+    }
+
+    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+        let path = match annotation.source_file.as_ref() {
+            Some(path) => path.clone(),
+            None => return Err(SerializerError::MissingSourceFile),
+        };
+
+        self.annotation_map
+            .entry(path)
+            .or_default()
+            .push(annotation);
+        Ok(())
+    }
+
+    fn push_image(&mut self, image: ImageRecord) -> SerializerResult<()> {
+        if !image.is_empty() {
+            for annotation in image.annotations {
+                self.push(annotation)?;
+            }
+            return Ok(());
+        }
+
+        // An image without annotations is written as an empty label file,
+        // named after its source file or, if it has none, after the image.
+        let path = match (image.source_file, image.image.path) {
+            (Some(source_file), _) => source_file,
+            (None, Some(image_path)) => image_path.with_extension("txt"),
+            (None, None) => return Err(SerializerError::MissingSourceFile),
+        };
+        self.annotation_map.entry(path).or_default();
+        Ok(())
+    }
+
+    fn finish(self) -> SerializerResult<()> {
+        let destination = self
+            .destination
+            .as_ref()
+            .ok_or(SerializerError::Other(String::from(
+                "The serializer has not been initialized",
+            )))?;
+
+        for (path, annotations) in self.annotation_map {
+            let file_name = match path.file_name() {
+                Some(file_name) => PathBuf::from(file_name),
+                None => {
+                    return Err(SerializerError::Other(format!(
+                        "Could not get a file name for {}",
+                        path.display()
+                    )))
+                }
+            };
+
+            let relative_path = crate::resolve_relative_path(destination, &file_name)?;
+
+            Self::write_to_file(&relative_path, &annotations)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,11 +1,31 @@
+pub mod align_boxes;
+pub mod assign_image_id;
 pub mod class_mapping;
+pub mod class_remap;
+pub mod drop_segmentation;
+pub mod filter;
 pub mod lookup_image;
 pub mod normalize;
+pub mod order_corners;
+pub mod sanitize;
+pub mod scheduler;
 pub mod transform;
 
-pub use class_mapping::ClassMapping;
+pub use align_boxes::{AlignBoxes, BoxPolicy};
+pub use assign_image_id::AssignImageId;
+pub use class_mapping::{ClassMapping, ClassOrder, MappingGenerator};
+pub use class_remap::{ClassRemap, ClassRule, ClassRules, ClassRulesError};
+pub use drop_segmentation::DropSegmentation;
+pub use filter::{AnnotationFilter, FilterAction, Predicate, PredicateError};
 pub use lookup_image::LookupImage;
 pub use normalize::Denormalize;
 pub use normalize::Normalize;
+pub use normalize::ReadDimensions;
+pub use order_corners::OrderCorners;
+pub use sanitize::Sanitize;
+pub use scheduler::ScheduleError;
+pub use transform::AnnotationField;
 pub use transform::RequiredTransformations;
 pub use transform::Transform;
+pub use transform::TransformOutcome;
+pub use transform::TransformStage;
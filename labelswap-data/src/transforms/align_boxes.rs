@@ -0,0 +1,166 @@
+/* align_boxes.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{AnnotationField, RequiredTransformations, Transform, TransformOutcome};
+use crate::conversion_report::{BoxAlignment, ConversionReport};
+use crate::models::{Annotation, Format, Winding};
+
+/// What AlignBoxes does with oriented boxes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BoxPolicy {
+    /// Replaces the box with the smallest axis aligned box containing it
+    #[default]
+    Enclosing,
+    /// Replaces the box with the largest axis aligned box centered on it
+    /// that fits inside of it
+    Inscribed,
+    /// Fails to convert the annotation
+    Refuse,
+}
+
+/// AlignBoxes: replaces oriented boxes with axis aligned ones, for formats
+/// that can not store rotation. Boxes that are already axis aligned are
+/// kept as they are.
+pub struct AlignBoxes {
+    policy: BoxPolicy,
+    alignment: BoxAlignment,
+}
+
+impl AlignBoxes {
+    pub fn new(policy: BoxPolicy) -> Self {
+        Self {
+            policy,
+            alignment: BoxAlignment {
+                policy,
+                ..BoxAlignment::default()
+            },
+        }
+    }
+
+    /// The half width and half height of the largest axis aligned box,
+    /// centered on (center_x, center_y), inside of the convex polygon
+    /// formed by `corners` in clockwise order.
+    fn inscribed_size(corners: &[(f64, f64); 4], center_x: f64, center_y: f64) -> (f64, f64) {
+        // Each edge limits the half sizes with |n.x| * a + |n.y| * b <= d,
+        // n being the normal of the edge and d its distance to the center
+        let mut constraints = Vec::with_capacity(corners.len());
+        for i in 0..corners.len() {
+            let (x1, y1) = corners[i];
+            let (x2, y2) = corners[(i + 1) % corners.len()];
+            let length = (x2 - x1).hypot(y2 - y1);
+            if length == 0.0 {
+                continue;
+            }
+            let (normal_x, normal_y) = ((y2 - y1) / length, (x1 - x2) / length);
+            let distance = normal_x * (x1 - center_x) + normal_y * (y1 - center_y);
+            if distance <= 0.0 {
+                return (0.0, 0.0);
+            }
+            constraints.push((normal_x.abs(), normal_y.abs(), distance));
+        }
+
+        // The best size touches one edge at its optimum, or two edges
+        let mut candidates = Vec::new();
+        for (i, &(p1, q1, d1)) in constraints.iter().enumerate() {
+            if p1 > 0.0 && q1 > 0.0 {
+                candidates.push((d1 / (2.0 * p1), d1 / (2.0 * q1)));
+            }
+            for &(p2, q2, d2) in constraints[i + 1..].iter() {
+                let determinant = p1 * q2 - p2 * q1;
+                if determinant.abs() > f64::EPSILON {
+                    candidates.push((
+                        (d1 * q2 - d2 * q1) / determinant,
+                        (p1 * d2 - p2 * d1) / determinant,
+                    ));
+                }
+            }
+        }
+
+        let fits = |&(a, b): &(f64, f64)| {
+            a >= 0.0
+                && b >= 0.0
+                && constraints
+                    .iter()
+                    .all(|(p, q, d)| p * a + q * b <= d * (1.0 + 1e-9))
+        };
+        candidates
+            .into_iter()
+            .filter(fits)
+            .max_by(|(a1, b1), (a2, b2)| (a1 * b1).total_cmp(&(a2 * b2)))
+            .unwrap_or((0.0, 0.0))
+    }
+}
+
+impl Transform for AlignBoxes {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let corners = annotation.corners();
+        if corners.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+            return Err(anyhow!(
+                "The box has non-finite coordinates and can not be aligned: {corners:?}"
+            ));
+        }
+
+        let (x_min, x_max) = (annotation.get_xmin(), annotation.get_xmax());
+        let (y_min, y_max) = (annotation.get_ymin(), annotation.get_ymax());
+        let enclosing_area = (x_max - x_min) * (y_max - y_min);
+        let area = annotation.area();
+        if enclosing_area - area <= enclosing_area * 1e-9 {
+            return Ok(TransformOutcome::Keep);
+        }
+
+        let aligned = match self.policy {
+            BoxPolicy::Refuse => {
+                return Err(anyhow!(
+                    "{} can not store oriented boxes",
+                    target_format.name
+                ))
+            }
+            BoxPolicy::Enclosing => Annotation::from_min_max(x_min, x_max, y_min, y_max),
+            BoxPolicy::Inscribed => {
+                let mut ordered = Annotation::from_min_max(0.0, 0.0, 0.0, 0.0);
+                ordered.set_corners(annotation.corners());
+                ordered.order_corners(Winding::Clockwise);
+                let corners = ordered.corners();
+                let center_x = corners.iter().map(|(x, _)| x).sum::<f64>() / 4.0;
+                let center_y = corners.iter().map(|(_, y)| y).sum::<f64>() / 4.0;
+
+                let (a, b) = Self::inscribed_size(&corners, center_x, center_y);
+                Annotation::from_min_max(center_x - a, center_x + a, center_y - b, center_y + b)
+            }
+        };
+
+        self.alignment.boxes += 1;
+        self.alignment.original_area += area;
+        self.alignment.aligned_area += aligned.area();
+        annotation.set_corners(aligned.corners());
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn report(&self, report: &mut ConversionReport) {
+        report.box_alignment = Some(self.alignment.clone());
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::AlignBoxes.requires()
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::AlignBoxes.provides()
+    }
+
+    fn name(&self) -> &str {
+        "AlignBoxes"
+    }
+}
@@ -0,0 +1,125 @@
+/* assign_image_id.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use super::{AnnotationField, RequiredTransformations, Transform, TransformOutcome};
+use crate::models::{Annotation, Format, Image, ImageRecord};
+use crate::parser::{CocoJsonParser, FormatParser};
+use anyhow::{anyhow, Result};
+
+/// AssignImageId: gives every distinct image path a numeric id. Ids are
+/// handed out in the order images are first seen, so converting the same
+/// dataset twice gives the same ids.
+///
+/// Ids can be seeded from an existing COCO JSON file, in which case images
+/// with the same path, relative to the image directory, keep their id and
+/// new images get ids above the highest one in the file. Annotations that already have an id keep it.
+pub struct AssignImageId {
+    ids: HashMap<PathBuf, u32>,
+    /// Ids read from a COCO file, by image path
+    seeded_ids: HashMap<PathBuf, u32>,
+    next_id: u32,
+}
+
+impl AssignImageId {
+    pub fn new() -> Self {
+        Self {
+            ids: HashMap::new(),
+            seeded_ids: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Keeps the image ids found in the COCO JSON file at `path`.
+    pub fn from_coco(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut parser = CocoJsonParser::new();
+        parser
+            .init(path)
+            .map_err(|e| anyhow!("Failed to read image ids from {}: {e}", path.display()))?;
+
+        let mut transform = Self::new();
+        for image in parser.images() {
+            let (Some(id), Some(path)) = (image.id, image.path.as_ref()) else {
+                continue;
+            };
+            transform.seeded_ids.insert(path.clone(), id);
+            transform.next_id = transform.next_id.max(id.saturating_add(1));
+        }
+        Ok(transform)
+    }
+
+    fn id_for(&mut self, path: &Path) -> Result<u32> {
+        if let Some(id) = self.ids.get(path) {
+            return Ok(*id);
+        }
+
+        let id = match self.seeded_ids.get(path) {
+            Some(id) => *id,
+            None => {
+                let id = self.next_id;
+                self.next_id = id.checked_add(1).ok_or(anyhow!("Ran out of image ids"))?;
+                id
+            }
+        };
+        self.ids.insert(path.to_path_buf(), id);
+        Ok(id)
+    }
+
+    /// Gives `image` the id of its path, unless it already has one.
+    fn assign(&mut self, image: &mut Image) -> Result<()> {
+        if image.id.is_some() {
+            return Ok(());
+        }
+
+        let path = image
+            .path
+            .as_ref()
+            .ok_or(anyhow!("Expected image path to be Some"))?;
+        image.id = Some(self.id_for(path)?);
+        Ok(())
+    }
+}
+
+impl Default for AssignImageId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transform for AssignImageId {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        self.assign(&mut annotation.image)?;
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn apply_image(
+        &mut self,
+        image: &mut ImageRecord,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<()> {
+        self.assign(&mut image.image)
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::AssignImageId.requires()
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::AssignImageId.provides()
+    }
+}
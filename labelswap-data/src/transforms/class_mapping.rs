@@ -5,18 +5,21 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use super::Transform;
+use super::{AnnotationField, Transform, TransformOutcome};
+use crate::class_map::{ClassMap, ClassMapError};
 use crate::models::{annotation::ClassRepresentation::{self, Both,ClassId,ClassName}, Annotation, Format};
 use anyhow::anyhow;
 
 pub struct ClassMapping {
-    map: HashMap<String, String>,
+    map: ClassMap,
 }
 
 impl ClassMapping {
-    pub fn new(map: HashMap<String, String>) -> Self {
+    pub fn new(map: ClassMap) -> Self {
         Self {
             map
         }
@@ -29,27 +32,116 @@ impl Transform for ClassMapping {
         annotation: &mut Annotation,
         _source_format: &Format,
         _target_format: &Format,
-    ) -> anyhow::Result<()> {
-        /* 
-         * Cloning is inexpensive here, as the values are usually small strings.
-         */
+    ) -> anyhow::Result<TransformOutcome> {
         let new_format = match annotation.class.as_ref() {
             ClassName(name) => {
-                if !self.map.contains_key(name) {
-                    return Err(anyhow!("Class Name {name} does not appear on the annotation conversion map"));
-                }
-                Both { name: name.clone(), id: self.map.get(name).unwrap().clone() }
+                let id = self.map.id(name).ok_or(anyhow!(
+                    "Class Name {name} does not appear on the annotation conversion map"
+                ))?;
+                Both { name: name.clone(), id: id.to_string() }
             },
             ClassId(id) => {
-                if !self.map.contains_key(id) {
-                    return Err(anyhow!("Class id {id} does not appear on the annotation conversion map"));
-                }
-                Both { name: self.map.get(id).unwrap().clone(), id: id.clone() }
+                let name = id
+                    .trim()
+                    .parse()
+                    .ok()
+                    .and_then(|numeric_id| self.map.name(numeric_id))
+                    .ok_or(anyhow!("Class id {id} does not appear on the annotation conversion map"))?;
+                Both { name: name.to_string(), id: id.clone() }
             }
-            Both {..} => return Ok(()),
+            Both {..} => return Ok(TransformOutcome::Keep),
             ClassRepresentation::None => return Err(anyhow!("Expected annotation to have a not none class representation")),
         };
         annotation.class = new_format;
-        Ok(())
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        &[AnnotationField::ClassName, AnnotationField::ClassId]
+    }
+}
+
+/// The order in which a MappingGenerator gives ids to classes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassOrder {
+    /// Classes sorted by name
+    Alphabetical,
+    /// Classes in the order they are first found in the source
+    FirstAppearance,
+    /// The classes of the list come first, in its order. Classes that are
+    /// not in it follow in the order they are first found.
+    Seeded(Vec<String>),
+}
+
+/// MappingGenerator: builds a class mapping from the class names found in
+/// the source, for conversions that need one when none was given. Ids are
+/// the positions of the classes, starting at 0.
+///
+/// The classes can be written to a file with one name per line, like
+/// `darknet.labels` or `classes.txt`, so that the conversion can be
+/// repeated with the same ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappingGenerator {
+    order: ClassOrder,
+    output: Option<PathBuf>,
+}
+
+impl MappingGenerator {
+    pub fn new(order: ClassOrder) -> Self {
+        Self {
+            order,
+            output: None,
+        }
+    }
+
+    /// Sets the file the generated classes are written to.
+    pub fn set_output(&mut self, output: Option<PathBuf>) {
+        self.output = output;
     }
-}
\ No newline at end of file
+
+    pub fn output(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    /// Orders the classes found in the source, given in the order they
+    /// were found. The position of a class is its id.
+    pub fn class_list(&self, found: Vec<String>) -> Vec<String> {
+        match &self.order {
+            ClassOrder::Alphabetical => {
+                let mut classes = found;
+                classes.sort();
+                classes
+            }
+            ClassOrder::FirstAppearance => found,
+            ClassOrder::Seeded(seed) => {
+                let mut classes = Vec::with_capacity(seed.len() + found.len());
+                let mut seen = HashSet::new();
+                for class in seed.iter().cloned().chain(found) {
+                    if seen.insert(class.clone()) {
+                        classes.push(class);
+                    }
+                }
+                classes
+            }
+        }
+    }
+
+    /// Writes `classes` to the output file, if there is one.
+    pub fn write(&self, classes: &[String]) -> std::io::Result<()> {
+        let Some(output) = self.output.as_ref() else {
+            return Ok(());
+        };
+
+        let mut stream = std::io::BufWriter::new(std::fs::File::create(output)?);
+        for class in classes {
+            writeln!(stream, "{class}")?;
+        }
+        stream.flush()
+    }
+
+    /// The mapping in which the id of each class is its position in
+    /// `classes`.
+    pub fn mapping(classes: &[String]) -> Result<ClassMap, ClassMapError> {
+        ClassMap::from_names(classes.iter().cloned())
+    }
+}
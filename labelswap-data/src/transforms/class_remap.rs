@@ -0,0 +1,307 @@
+/* class_remap.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::path::Path;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use super::{AnnotationField, Transform, TransformOutcome};
+use crate::class_map::ClassMap;
+use crate::conversion_report::{ConversionReport, RuleCount};
+use crate::models::{annotation::ClassRepresentation, Annotation, Format};
+use anyhow::{anyhow, Result};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClassRulesError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TOML Error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("JSON Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid pattern: {0}")]
+    Regex(#[from] regex::Error),
+    #[error("Rule files with the .{0} extension are not supported")]
+    UnsupportedExtension(String),
+}
+
+/// A rule changing the class of annotations, identified by class name.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClassRule {
+    /// Gives the class `from` the name `to`
+    Rename { from: String, to: String },
+    /// Gives every class in `from` the name `to`
+    Merge { from: Vec<String>, to: String },
+    /// Drops the annotations of the given classes
+    Drop { classes: Vec<String> },
+    /// Drops the annotations of every class that is not listed
+    KeepOnly { classes: Vec<String> },
+    /// Replaces the parts of class names matching `pattern` with
+    /// `replacement`, which may refer to capture groups like `$1`
+    RegexRename {
+        pattern: String,
+        replacement: String,
+    },
+}
+
+impl Display for ClassRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rename { from, to } => write!(f, "rename {from} to {to}"),
+            Self::Merge { from, to } => write!(f, "merge {} into {to}", from.join(", ")),
+            Self::Drop { classes } => write!(f, "drop {}", classes.join(", ")),
+            Self::KeepOnly { classes } => write!(f, "keep only {}", classes.join(", ")),
+            Self::RegexRename {
+                pattern,
+                replacement,
+            } => write!(f, "replace /{pattern}/ with {replacement}"),
+        }
+    }
+}
+
+/// ClassRules: a list of rules, applied in order. A rule sees the class
+/// name left by the previous ones.
+///
+/// Rule files are TOML or JSON:
+///
+/// ```toml
+/// [[rules]]
+/// type = "merge"
+/// from = ["car", "truck", "bus"]
+/// to = "vehicle"
+///
+/// [[rules]]
+/// type = "drop"
+/// classes = ["background"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ClassRules {
+    pub rules: Vec<ClassRule>,
+}
+
+impl ClassRules {
+    /// Reads the rules from a `.toml` or `.json` file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ClassRulesError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if extension != "toml" && extension != "json" {
+            return Err(ClassRulesError::UnsupportedExtension(extension));
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        let rules = match extension.as_str() {
+            "toml" => toml::from_str(&contents)?,
+            _ => serde_json::from_str(&contents)?,
+        };
+        Ok(rules)
+    }
+}
+
+/// A rule ready to be applied.
+enum CompiledRule {
+    Rename { from: HashSet<String>, to: String },
+    Drop(HashSet<String>),
+    KeepOnly(HashSet<String>),
+    RegexRename { regex: Regex, replacement: String },
+}
+
+/// What a rule does to a class name.
+enum RuleResult {
+    Unchanged,
+    Renamed(String),
+    Dropped,
+}
+
+impl CompiledRule {
+    fn new(rule: &ClassRule) -> Result<Self, ClassRulesError> {
+        let set = |classes: &[String]| classes.iter().cloned().collect();
+        let rule = match rule {
+            ClassRule::Rename { from, to } => Self::Rename {
+                from: set(std::slice::from_ref(from)),
+                to: to.clone(),
+            },
+            ClassRule::Merge { from, to } => Self::Rename {
+                from: set(from),
+                to: to.clone(),
+            },
+            ClassRule::Drop { classes } => Self::Drop(set(classes)),
+            ClassRule::KeepOnly { classes } => Self::KeepOnly(set(classes)),
+            ClassRule::RegexRename {
+                pattern,
+                replacement,
+            } => Self::RegexRename {
+                regex: Regex::new(pattern)?,
+                replacement: replacement.clone(),
+            },
+        };
+        Ok(rule)
+    }
+
+    fn apply(&self, name: &str) -> RuleResult {
+        match self {
+            Self::Rename { from, to } if from.contains(name) && name != to => {
+                RuleResult::Renamed(to.clone())
+            }
+            Self::Drop(classes) if classes.contains(name) => RuleResult::Dropped,
+            Self::KeepOnly(classes) if !classes.contains(name) => RuleResult::Dropped,
+            Self::RegexRename { regex, replacement } => {
+                let renamed = regex.replace_all(name, replacement.as_str());
+                if renamed == name {
+                    RuleResult::Unchanged
+                } else {
+                    RuleResult::Renamed(renamed.into_owned())
+                }
+            }
+            _ => RuleResult::Unchanged,
+        }
+    }
+}
+
+/// ClassRemap: renames, merges and drops classes following a set of
+/// ClassRules. The remaining classes get new contiguous ids, starting at 0.
+///
+/// If the classes of the source are known, the new ids follow their order:
+/// every class keeps its relative position, merged classes take the place
+/// of the first one. Classes that are not known get the next free id when
+/// they are first seen.
+pub struct ClassRemap {
+    rules: Vec<ClassRule>,
+    compiled: Vec<CompiledRule>,
+    counts: Vec<usize>,
+    classes: ClassMap,
+}
+
+impl ClassRemap {
+    pub fn new(
+        rules: ClassRules,
+        source_classes: Option<&ClassMap>,
+    ) -> Result<Self, ClassRulesError> {
+        let compiled = rules
+            .rules
+            .iter()
+            .map(CompiledRule::new)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut remap = Self {
+            counts: vec![0; rules.rules.len()],
+            rules: rules.rules,
+            compiled,
+            classes: ClassMap::new(),
+        };
+        for (_, name) in source_classes.iter().flat_map(|classes| classes.iter()) {
+            if let Ok(name) = remap.remap(name, false) {
+                remap.class_id(&name);
+            }
+        }
+        Ok(remap)
+    }
+
+    /// The classes left after applying the rules, by their new id.
+    pub fn classes(&self) -> &ClassMap {
+        &self.classes
+    }
+
+    /// Applies every rule to `name`, returning the new name or the index of
+    /// the rule that dropped the class. If `count` is true, the rules that
+    /// change the class are counted.
+    fn remap(&mut self, name: &str, count: bool) -> Result<String, usize> {
+        let mut name = name.to_string();
+        for (index, rule) in self.compiled.iter().enumerate() {
+            let result = rule.apply(&name);
+            if count && !matches!(result, RuleResult::Unchanged) {
+                self.counts[index] += 1;
+            }
+            match result {
+                RuleResult::Unchanged => {}
+                RuleResult::Renamed(renamed) => name = renamed,
+                RuleResult::Dropped => return Err(index),
+            }
+        }
+        Ok(name)
+    }
+
+    /// The id of a remapped class, giving it the next one if it is new.
+    fn class_id(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.classes.id(name) {
+            return id;
+        }
+        let id = self.classes.len() as u32;
+        // The name is not in the map and ids are contiguous, so it can not
+        // fail
+        let _ = self.classes.insert(id, name);
+        id
+    }
+}
+
+impl Transform for ClassRemap {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let name = annotation
+            .class
+            .name()
+            .ok_or(anyhow!("Expected annotation to have a class name"))?
+            .to_string();
+
+        let name = match self.remap(&name, true) {
+            Ok(name) => name,
+            Err(index) => {
+                let reason = format!("Class rule: {}", self.rules[index]);
+                return Ok(TransformOutcome::Drop(reason));
+            }
+        };
+
+        let id = self.class_id(&name);
+        annotation.class = ClassRepresentation::Both {
+            name,
+            id: id.to_string(),
+        };
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn report(&self, report: &mut ConversionReport) {
+        report
+            .class_rule_counts
+            .extend(
+                self.rules
+                    .iter()
+                    .zip(self.counts.iter())
+                    .map(|(rule, count)| RuleCount {
+                        rule: rule.to_string(),
+                        annotations: *count,
+                    }),
+            );
+        report.remapped_classes = self
+            .classes
+            .iter()
+            .map(|(_, name)| name.to_string())
+            .collect();
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        &[AnnotationField::ClassName]
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        &[AnnotationField::ClassName, AnnotationField::ClassId]
+    }
+
+    fn name(&self) -> &str {
+        "ClassRemap"
+    }
+}
@@ -0,0 +1,61 @@
+/* drop_segmentation.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use anyhow::Result;
+
+use super::{AnnotationField, RequiredTransformations, Transform, TransformOutcome};
+use crate::conversion_report::ConversionReport;
+use crate::models::{Annotation, Format};
+
+/// DropSegmentation: removes the segmentations the target format can not
+/// store. The corners of an annotation already are the bounding box of its
+/// segmentation, so the annotation falls back to that box.
+#[derive(Default)]
+pub struct DropSegmentation {
+    dropped: usize,
+}
+
+impl DropSegmentation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Transform for DropSegmentation {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let dropped = annotation
+            .segmentation
+            .as_ref()
+            .is_some_and(|segmentation| !target_format.segmentation_type.can_store(segmentation));
+        if dropped {
+            annotation.segmentation = None;
+            self.dropped += 1;
+        }
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn report(&self, report: &mut ConversionReport) {
+        report.dropped_segmentations = self.dropped;
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::DropSegmentation.requires()
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::DropSegmentation.provides()
+    }
+
+    fn name(&self) -> &str {
+        "DropSegmentation"
+    }
+}
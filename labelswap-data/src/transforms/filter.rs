@@ -0,0 +1,496 @@
+/* filter.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use glob::Pattern;
+
+use super::{AnnotationField, Transform, TransformOutcome, TransformStage};
+use crate::models::{Annotation, Format};
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PredicateError {
+    #[error("Expected {expected}, found {found}")]
+    Unexpected { expected: String, found: String },
+    #[error("Unknown field {0}")]
+    UnknownField(String),
+    #[error("Invalid number {0}")]
+    InvalidNumber(String),
+    #[error("Invalid image pattern {0}")]
+    InvalidPattern(String),
+}
+
+/// A property of an annotation that can be compared to a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Measure {
+    /// Area of the box in pixels
+    AreaPx,
+    /// Width of the box in pixels
+    WidthPx,
+    /// Height of the box in pixels
+    HeightPx,
+    /// Area of the box as a fraction of the image area
+    Area,
+    /// Width of the box as a fraction of the image width
+    Width,
+    /// Height of the box as a fraction of the image height
+    Height,
+    /// Width divided by height, in pixels
+    Aspect,
+}
+
+impl Measure {
+    fn from_name(name: &str) -> Option<Self> {
+        let measure = match name {
+            "area_px" => Self::AreaPx,
+            "width_px" => Self::WidthPx,
+            "height_px" => Self::HeightPx,
+            "area" => Self::Area,
+            "width" => Self::Width,
+            "height" => Self::Height,
+            "aspect" | "aspect_ratio" => Self::Aspect,
+            _ => return None,
+        };
+        Some(measure)
+    }
+
+    fn in_pixels(&self) -> bool {
+        matches!(
+            self,
+            Self::AreaPx | Self::WidthPx | Self::HeightPx | Self::Aspect
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Comparison {
+    fn from_token(token: &str) -> Option<Self> {
+        let comparison = match token {
+            "<" => Self::Less,
+            "<=" => Self::LessOrEqual,
+            ">" => Self::Greater,
+            ">=" => Self::GreaterOrEqual,
+            "==" | "=" => Self::Equal,
+            "!=" => Self::NotEqual,
+            _ => return None,
+        };
+        Some(comparison)
+    }
+
+    fn compare(&self, left: f64, right: f64) -> bool {
+        match self {
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterOrEqual => left >= right,
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+        }
+    }
+}
+
+/// Predicate: a condition on an annotation, parsed from expressions like
+/// `class in [person, dog] and area_px > 64 and not difficult`.
+///
+/// Conditions can be combined with `and`, `or`, `not` and parentheses:
+/// - `class == name`, `class != name`, `class in [a, b]` and
+///   `class not in [a, b]` compare the class name, or its id if the
+///   annotation does not have a name.
+/// - `area_px`, `width_px`, `height_px`, `area`, `width`, `height` and
+///   `aspect` compared to a number with `<`, `<=`, `>`, `>=`, `==` or `!=`.
+///   Measures without the `_px` suffix are fractions of the image size.
+/// - `difficult` is true for annotations marked as difficult.
+/// - `image matches "glob"` compares the image path with a glob pattern.
+///   Annotations without an image path never match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+    ClassIn(Vec<String>),
+    Difficult,
+    ImageMatches(Pattern),
+    Compare {
+        measure: Measure,
+        comparison: Comparison,
+        value: f64,
+    },
+}
+
+impl FromStr for Predicate {
+    type Err = PredicateError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let mut parser = PredicateParser {
+            tokens: tokenize(expression),
+            position: 0,
+        };
+        let predicate = parser.parse_or()?;
+        match parser.next() {
+            None => Ok(predicate),
+            Some(token) => Err(PredicateError::Unexpected {
+                expected: String::from("the end of the expression"),
+                found: token,
+            }),
+        }
+    }
+}
+
+impl Predicate {
+    /// Whether `annotation` satisfies the predicate. `normalized` tells if
+    /// the coordinates of the annotation are normalized. Converting them to
+    /// the units of a measure needs the dimensions of the image.
+    pub fn matches(&self, annotation: &Annotation, normalized: bool) -> Result<bool> {
+        let matches = match self {
+            Self::And(left, right) => {
+                left.matches(annotation, normalized)? && right.matches(annotation, normalized)?
+            }
+            Self::Or(left, right) => {
+                left.matches(annotation, normalized)? || right.matches(annotation, normalized)?
+            }
+            Self::Not(predicate) => !predicate.matches(annotation, normalized)?,
+            Self::ClassIn(classes) => {
+                let class = annotation
+                    .class
+                    .name()
+                    .or(annotation.class.id())
+                    .ok_or(anyhow!("Expected annotation to have a class"))?;
+                classes.iter().any(|name| name == class)
+            }
+            Self::Difficult => annotation.difficulty,
+            Self::ImageMatches(pattern) => annotation
+                .image
+                .path
+                .as_ref()
+                .is_some_and(|path| pattern.matches_path(path)),
+            Self::Compare {
+                measure,
+                comparison,
+                value,
+            } => comparison.compare(
+                measure_annotation(annotation, *measure, normalized)?,
+                *value,
+            ),
+        };
+        Ok(matches)
+    }
+
+    /// The fields of an annotation the predicate reads.
+    fn fields(&self, fields: &mut Vec<AnnotationField>) {
+        let field = match self {
+            Self::And(left, right) | Self::Or(left, right) => {
+                left.fields(fields);
+                right.fields(fields);
+                return;
+            }
+            Self::Not(predicate) => return predicate.fields(fields),
+            Self::ClassIn(_) => AnnotationField::ClassName,
+            Self::ImageMatches(_) => AnnotationField::ImagePath,
+            Self::Difficult | Self::Compare { .. } => return,
+        };
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+}
+
+/// Computes `measure` for the box of `annotation`, in the units of the
+/// measure.
+fn measure_annotation(annotation: &Annotation, measure: Measure, normalized: bool) -> Result<f64> {
+    let mut width = annotation.get_xmax() - annotation.get_xmin();
+    let mut height = annotation.get_ymax() - annotation.get_ymin();
+    // The area between the corners, so that rotated boxes have their
+    // actual area
+    let mut area = annotation.area();
+
+    if measure.in_pixels() == normalized {
+        let (image_width, image_height) = match (annotation.image.width, annotation.image.height) {
+            (Some(width), Some(height)) => (f64::from(width), f64::from(height)),
+            _ => {
+                return Err(anyhow!(
+                    "The image dimensions are needed to filter by {measure:?}"
+                ))
+            }
+        };
+        let (scale_x, scale_y) = if normalized {
+            (image_width, image_height)
+        } else {
+            (1.0 / image_width, 1.0 / image_height)
+        };
+        width *= scale_x;
+        height *= scale_y;
+        area *= scale_x * scale_y;
+    }
+
+    let value = match measure {
+        Measure::AreaPx | Measure::Area => area,
+        Measure::WidthPx | Measure::Width => width,
+        Measure::HeightPx | Measure::Height => height,
+        Measure::Aspect => width / height,
+    };
+    Ok(value)
+}
+
+/// Splits an expression into words, quoted strings and symbols. Quoted
+/// strings keep their quotes so that they are not taken as keywords.
+fn tokenize(expression: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut token = String::from('"');
+            for c2 in chars.by_ref() {
+                if c2 == c {
+                    break;
+                }
+                token.push(c2);
+            }
+            tokens.push(token);
+        } else if "[](),".contains(c) {
+            chars.next();
+            tokens.push(c.to_string());
+        } else if "<>=!".contains(c) {
+            chars.next();
+            let mut token = c.to_string();
+            if chars.peek() == Some(&'=') {
+                chars.next();
+                token.push('=');
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || "[](),<>=!\"'".contains(c) {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+struct PredicateParser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl PredicateParser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), PredicateError> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => Err(unexpected(expected, token)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Predicate, PredicateError> {
+        let mut predicate = self.parse_and()?;
+        while self.peek() == Some("or") {
+            self.next();
+            predicate = Predicate::Or(Box::new(predicate), Box::new(self.parse_and()?));
+        }
+        Ok(predicate)
+    }
+
+    fn parse_and(&mut self) -> Result<Predicate, PredicateError> {
+        let mut predicate = self.parse_unary()?;
+        while self.peek() == Some("and") {
+            self.next();
+            predicate = Predicate::And(Box::new(predicate), Box::new(self.parse_unary()?));
+        }
+        Ok(predicate)
+    }
+
+    fn parse_unary(&mut self) -> Result<Predicate, PredicateError> {
+        match self.next().as_deref() {
+            Some("not") => Ok(Predicate::Not(Box::new(self.parse_unary()?))),
+            Some("(") => {
+                let predicate = self.parse_or()?;
+                self.expect(")")?;
+                Ok(predicate)
+            }
+            Some("difficult") | Some("difficulty") => Ok(Predicate::Difficult),
+            Some("class") => self.parse_class(),
+            Some("image") => {
+                self.expect("matches")?;
+                let pattern = self.parse_value()?;
+                Pattern::new(&pattern)
+                    .map(Predicate::ImageMatches)
+                    .map_err(|_| PredicateError::InvalidPattern(pattern))
+            }
+            Some(field) if !field.starts_with('"') => {
+                let measure = Measure::from_name(field)
+                    .ok_or(PredicateError::UnknownField(field.to_string()))?;
+                let token = self.next();
+                let comparison = token
+                    .as_deref()
+                    .and_then(Comparison::from_token)
+                    .ok_or_else(|| unexpected("a comparison", token.clone()))?;
+                let value = self.parse_value()?;
+                let value = value
+                    .parse()
+                    .map_err(|_| PredicateError::InvalidNumber(value))?;
+                Ok(Predicate::Compare {
+                    measure,
+                    comparison,
+                    value,
+                })
+            }
+            token => Err(unexpected("a condition", token.map(String::from))),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Predicate, PredicateError> {
+        let token = self.next();
+        let predicate = match token.as_deref() {
+            Some("==") | Some("=") => Predicate::ClassIn(vec![self.parse_value()?]),
+            Some("!=") => Predicate::Not(Box::new(Predicate::ClassIn(vec![self.parse_value()?]))),
+            Some("in") => Predicate::ClassIn(self.parse_list()?),
+            Some("not") => {
+                self.expect("in")?;
+                Predicate::Not(Box::new(Predicate::ClassIn(self.parse_list()?)))
+            }
+            _ => return Err(unexpected("==, !=, in or not in", token)),
+        };
+        Ok(predicate)
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<String>, PredicateError> {
+        self.expect("[")?;
+        let mut values = vec![self.parse_value()?];
+        loop {
+            match self.next().as_deref() {
+                Some(",") => values.push(self.parse_value()?),
+                Some("]") => return Ok(values),
+                token => return Err(unexpected(", or ]", token.map(String::from))),
+            }
+        }
+    }
+
+    /// A word or a quoted string, without its quotes.
+    fn parse_value(&mut self) -> Result<String, PredicateError> {
+        match self.next() {
+            Some(token) if token.starts_with('"') => Ok(token[1..].to_string()),
+            Some(token) if !token.starts_with(|c| "[](),<>=!".contains(c)) => Ok(token),
+            token => Err(unexpected("a value", token)),
+        }
+    }
+}
+
+fn unexpected(expected: &str, found: Option<String>) -> PredicateError {
+    PredicateError::Unexpected {
+        expected: expected.to_string(),
+        found: found.unwrap_or_else(|| String::from("the end of the expression")),
+    }
+}
+
+/// What AnnotationFilter does with the annotations matching its predicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    Keep,
+    Drop,
+}
+
+/// AnnotationFilter: keeps or drops annotations depending on whether they
+/// match a Predicate. The dropped annotations are reported as filtered, with
+/// the expression as the reason.
+pub struct AnnotationFilter {
+    predicate: Predicate,
+    expression: String,
+    action: FilterAction,
+    stage: TransformStage,
+    requires: Vec<AnnotationField>,
+}
+
+impl AnnotationFilter {
+    /// Creates a filter from an expression. The stage the filter is added
+    /// to tells whether the coordinates are in the units of the source or
+    /// of the target format.
+    pub fn new(expression: &str, action: FilterAction) -> Result<Self, PredicateError> {
+        let predicate: Predicate = expression.parse()?;
+        let mut requires = Vec::new();
+        predicate.fields(&mut requires);
+
+        Ok(Self {
+            predicate,
+            expression: expression.trim().to_string(),
+            action,
+            stage: TransformStage::default(),
+            requires,
+        })
+    }
+}
+
+impl Display for AnnotationFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.action {
+            FilterAction::Keep => write!(f, "keep if {}", self.expression),
+            FilterAction::Drop => write!(f, "drop if {}", self.expression),
+        }
+    }
+}
+
+impl Transform for AnnotationFilter {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        source_format: &Format,
+        target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let normalized = self.stage.is_normalized(source_format, target_format);
+        let matches = self.predicate.matches(annotation, normalized)?;
+
+        let keep = match self.action {
+            FilterAction::Keep => matches,
+            FilterAction::Drop => !matches,
+        };
+        if keep {
+            Ok(TransformOutcome::Keep)
+        } else {
+            Ok(TransformOutcome::Drop(format!("Filter: {self}")))
+        }
+    }
+
+    fn set_stage(&mut self, stage: TransformStage) {
+        self.stage = stage;
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        &self.requires
+    }
+
+    fn name(&self) -> &str {
+        "AnnotationFilter"
+    }
+}
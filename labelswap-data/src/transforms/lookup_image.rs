@@ -7,16 +7,24 @@
 
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    ffi::OsString,
+    path::{Path, PathBuf},
 };
 
-use super::Transform;
-use crate::models::{Annotation, Format};
+use super::{AnnotationField, RequiredTransformations, Transform, TransformOutcome};
+use crate::conversion_report::ConversionReport;
+use crate::models::{Annotation, Format, ImageRecord};
+use crate::parser::read_dir_sorted;
 use anyhow::{anyhow, Result};
+use image::ImageFormat;
 
+/// LookupImage: finds the image of an annotation in the image directory.
+/// An image belongs to an annotation when its file stem is the same as the
+/// one of the annotation's source file, e.g. `001.png` for `001.txt`.
 pub struct LookupImage {
     pub image_directory: PathBuf,
-    source_to_image_map: HashMap<PathBuf, PathBuf>,
+    /// Images in the directory by file stem, built on the first lookup
+    images_by_stem: Option<HashMap<OsString, PathBuf>>,
     sources_without_image: HashSet<PathBuf>,
 }
 
@@ -28,10 +36,53 @@ impl LookupImage {
 
         Ok(Self {
             image_directory,
+            images_by_stem: None,
             sources_without_image: HashSet::new(),
-            source_to_image_map: HashMap::new(),
         })
     }
+
+    /// Lists the images in the directory by their file name, as image paths
+    /// are relative to the image directory. Files whose extension is not an
+    /// image format are ignored, so labels stored next to their images are
+    /// never taken for one. If two images share a stem, the first one in
+    /// file name order is used.
+    fn index_images(image_directory: &Path) -> Result<HashMap<OsString, PathBuf>> {
+        let mut images = HashMap::new();
+        for entry in read_dir_sorted(image_directory)? {
+            let path = entry?.path();
+            if !path.is_file() || ImageFormat::from_path(&path).is_err() {
+                continue;
+            }
+            if let (Some(stem), Some(file_name)) = (path.file_stem(), path.file_name()) {
+                images
+                    .entry(stem.to_os_string())
+                    .or_insert(PathBuf::from(file_name));
+            }
+        }
+        Ok(images)
+    }
+
+    /// The image with the same file stem as `source_file`.
+    fn find_image(&mut self, source_file: Option<&PathBuf>) -> Result<PathBuf> {
+        let source_file = source_file.ok_or(anyhow!("Expected source file to be Some"))?;
+
+        if self.images_by_stem.is_none() {
+            self.images_by_stem = Some(Self::index_images(&self.image_directory)?);
+        }
+        let images = self.images_by_stem.as_ref().unwrap();
+
+        let image = source_file.file_stem().and_then(|stem| images.get(stem));
+        match image {
+            Some(image) => Ok(image.clone()),
+            None => {
+                self.sources_without_image.insert(source_file.clone());
+                Err(anyhow!(
+                    "Could not find an image for {}",
+                    source_file.display()
+                ))
+            }
+        }
+    }
 }
 
 impl Transform for LookupImage {
@@ -40,43 +91,32 @@ impl Transform for LookupImage {
         annotation: &mut Annotation,
         _source_format: &Format,
         _target_format: &Format,
-    ) -> Result<()> {
-        let annotation_source = annotation
-            .source_file
-            .as_ref()
-            .ok_or(anyhow!("Expected source file to be Some"))?;
-
-        if let Some(image) = self.source_to_image_map.get(annotation_source) {
-            annotation.image.path = Some(image.clone());
-            return Ok(());
-        }
+    ) -> Result<TransformOutcome> {
+        annotation.image.path = Some(self.find_image(annotation.source_file.as_ref())?);
+        Ok(TransformOutcome::Keep)
+    }
 
-        if self.sources_without_image.contains(annotation_source) {
-            return Err(anyhow!(
-                "Annotation source does not have an image related to it"
-            ));
-        }
+    fn apply_image(
+        &mut self,
+        image: &mut ImageRecord,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<()> {
+        image.image.path = Some(self.find_image(image.source_file.as_ref())?);
+        Ok(())
+    }
 
-        let annotation_source = annotation_source.to_str().unwrap();
+    fn report(&self, report: &mut ConversionReport) {
+        let mut sources: Vec<PathBuf> = self.sources_without_image.iter().cloned().collect();
+        sources.sort();
+        report.unmatched_images.extend(sources);
+    }
 
-        let dir_entry = self
-            .image_directory
-            .read_dir()?
-            .filter_map(|file| file.ok())
-            .find(|file| file.file_name().to_str().unwrap().starts_with(annotation_source));
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::LookupImage.requires()
+    }
 
-        return match dir_entry {
-            Some(file) => {
-                let filename = file.path();
-                self.source_to_image_map
-                    .insert(PathBuf::from(annotation_source), filename.clone());
-                annotation.image.path = Some(filename);
-                Ok(())
-            }
-            None => {
-                self.sources_without_image.insert(PathBuf::from(annotation_source));
-                Err(anyhow!("Could not find an image for {annotation_source}"))
-            }
-        };
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::LookupImage.provides()
     }
 }
@@ -6,25 +6,26 @@
  */
 
 use crate::{
-    models::{Annotation, Format, Image},
+    models::{Annotation, Format, Image, ImageRecord},
     resolve_relative_path,
+    worker_pool::WorkerPool,
 };
 use anyhow::{anyhow, Result};
 use image::io::Reader as ImageReader;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use super::Transform;
+use super::{AnnotationField, RequiredTransformations, Transform, TransformOutcome};
 
 pub struct Normalize {
-    image_directory: PathBuf,
+    dimensions: ImageDimensions,
 }
 
 impl Normalize {
-    pub fn new(image_directory: PathBuf) -> Result<Self> {
-        if !image_directory.is_dir() {
-            return Err(anyhow!("Expected {:?} to be a directory", image_directory));
-        }
-        Ok(Self { image_directory })
+    pub fn new(image_directory: Option<PathBuf>) -> Result<Self> {
+        Ok(Self {
+            dimensions: ImageDimensions::new(image_directory)?,
+        })
     }
 
     fn normalize(annotation: &mut Annotation, width: u32, height: u32) {
@@ -40,6 +41,13 @@ impl Normalize {
         annotation.y2 /= height;
         annotation.y3 /= height;
         annotation.y4 /= height;
+
+        if let Some(segmentation) = &mut annotation.segmentation {
+            segmentation.scale(1.0 / width, 1.0 / height);
+        }
+        if let Some(keypoints) = &mut annotation.keypoints {
+            keypoints.scale(1.0 / width, 1.0 / height);
+        }
     }
 }
 
@@ -49,47 +57,68 @@ impl Transform for Normalize {
         annotation: &mut Annotation,
         _source_format: &Format,
         _target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let (width, height) = self.dimensions.get(&mut annotation.image)?;
+        Self::normalize(annotation, width, height);
+
+        Ok(TransformOutcome::Keep)
+    }
+
+    /// Only fills in the dimensions, as there is nothing to scale.
+    fn apply_image(
+        &mut self,
+        image: &mut ImageRecord,
+        _source_format: &Format,
+        _target_format: &Format,
     ) -> Result<()> {
-        let image = &mut annotation.image;
+        self.dimensions.get(&mut image.image)?;
+        Ok(())
+    }
 
-        let (width, height) = match (image.width, image.height) {
-            (Some(width), Some(height)) => (width, height),
-            _ => {
-                read_image_dimensions(image, &self.image_directory)?
-            },
-        };
+    fn prepare(&mut self, annotations: &[Annotation], pool: &WorkerPool) {
+        self.dimensions.prepare(annotations, pool);
+    }
 
-        Self::normalize(annotation, width, height);
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::Normalize.requires()
+    }
 
-        Ok(())
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::Normalize.provides()
     }
 }
 
 pub struct Denormalize {
-    image_directory: PathBuf,
+    dimensions: ImageDimensions,
 }
 
 impl Denormalize {
-    pub fn new(image_directory: PathBuf) -> Result<Self> {
-        if !image_directory.is_dir() {
-            return Err(anyhow!("Expected {:?} to be a directory", image_directory));
-        }
-        Ok(Self { image_directory })
+    pub fn new(image_directory: Option<PathBuf>) -> Result<Self> {
+        Ok(Self {
+            dimensions: ImageDimensions::new(image_directory)?,
+        })
     }
 
     fn denormalize(annotation: &mut Annotation, width: u32, height: u32) {
         let width = f64::from(width);
         let height = f64::from(height);
 
-        annotation.x1 /= width;
-        annotation.x2 /= width;
-        annotation.x3 /= width;
-        annotation.x4 /= width;
+        annotation.x1 *= width;
+        annotation.x2 *= width;
+        annotation.x3 *= width;
+        annotation.x4 *= width;
 
-        annotation.y1 /= height;
-        annotation.y2 /= height;
-        annotation.y3 /= height;
-        annotation.y4 /= height;
+        annotation.y1 *= height;
+        annotation.y2 *= height;
+        annotation.y3 *= height;
+        annotation.y4 *= height;
+
+        if let Some(segmentation) = &mut annotation.segmentation {
+            segmentation.scale(width, height);
+        }
+        if let Some(keypoints) = &mut annotation.keypoints {
+            keypoints.scale(width, height);
+        }
     }
 }
 
@@ -99,32 +128,162 @@ impl Transform for Denormalize {
         annotation: &mut Annotation,
         _source_format: &Format,
         _target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let (width, height) = self.dimensions.get(&mut annotation.image)?;
+        Self::denormalize(annotation, width, height);
+
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn apply_image(
+        &mut self,
+        image: &mut ImageRecord,
+        _source_format: &Format,
+        _target_format: &Format,
     ) -> Result<()> {
-        let image = &mut annotation.image;
+        self.dimensions.get(&mut image.image)?;
+        Ok(())
+    }
 
-        let (width, height) = match (image.width, image.height) {
-            (Some(width), Some(height)) => (width, height),
-            _ => read_image_dimensions(image, &self.image_directory)?,
-        };
+    fn prepare(&mut self, annotations: &[Annotation], pool: &WorkerPool) {
+        self.dimensions.prepare(annotations, pool);
+    }
 
-        Self::denormalize(annotation, width, height);
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::Denormalize.requires()
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::Denormalize.provides()
+    }
+}
+
+/// Fills in the dimensions of the images for targets that store them, when
+/// the source does not and no normalization reads them already.
+pub struct ReadDimensions {
+    dimensions: ImageDimensions,
+}
+
+impl ReadDimensions {
+    pub fn new(image_directory: PathBuf) -> Result<Self> {
+        Ok(Self {
+            dimensions: ImageDimensions::new(Some(image_directory))?,
+        })
+    }
+}
 
+impl Transform for ReadDimensions {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        self.dimensions.get(&mut annotation.image)?;
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn apply_image(
+        &mut self,
+        image: &mut ImageRecord,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<()> {
+        self.dimensions.get(&mut image.image)?;
         Ok(())
     }
+
+    fn prepare(&mut self, annotations: &[Annotation], pool: &WorkerPool) {
+        self.dimensions.prepare(annotations, pool);
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        RequiredTransformations::ReadDimensions.requires()
+    }
+
+    fn provides(&self) -> &[AnnotationField] {
+        RequiredTransformations::ReadDimensions.provides()
+    }
 }
 
-/// Reads the image dimensions and writes them to the instance
-fn read_image_dimensions(image: &mut Image, image_directory: &Path) -> Result<(u32, u32)> {
-    let image_path = match image.path.as_ref() {
-        Some(path) => path,
-        None => return Err(anyhow!("Expected image path to be Some")),
-    };
+/// Reads the dimensions of the images annotations belong to, remembering
+/// them as many annotations usually share the same image. Without an image
+/// directory, only the dimensions stored in the annotations can be used.
+struct ImageDimensions {
+    image_directory: Option<PathBuf>,
+    cache: HashMap<PathBuf, (u32, u32)>,
+}
 
-    let image_path = resolve_relative_path(image_directory, image_path)?;
-    let (width, height) = ImageReader::open(&image_path)?.into_dimensions()?;
+impl ImageDimensions {
+    fn new(image_directory: Option<PathBuf>) -> Result<Self> {
+        if let Some(image_directory) = image_directory.as_ref() {
+            if !image_directory.is_dir() {
+                return Err(anyhow!("Expected {:?} to be a directory", image_directory));
+            }
+        }
+        Ok(Self {
+            image_directory,
+            cache: HashMap::new(),
+        })
+    }
 
-    image.width = Some(width);
-    image.height = Some(height);
+    /// The dimensions of the annotation's image. If the annotation does not
+    /// have them, they are read from the image and written to it.
+    fn get(&mut self, image: &mut Image) -> Result<(u32, u32)> {
+        if let (Some(width), Some(height)) = (image.width, image.height) {
+            return Ok((width, height));
+        }
+
+        let image_path = match image.path.as_ref() {
+            Some(path) => path,
+            None => return Err(anyhow!("Expected image path to be Some")),
+        };
+        let Some(image_directory) = self.image_directory.as_ref() else {
+            return Err(anyhow!(
+                "The dimensions of {} are not known and no image directory was given",
+                image_path.display()
+            ));
+        };
+
+        let (width, height) = match self.cache.get(image_path) {
+            Some(dimensions) => *dimensions,
+            None => {
+                let dimensions = read_image_dimensions(image_directory, image_path)?;
+                self.cache.insert(image_path.clone(), dimensions);
+                dimensions
+            }
+        };
 
-    Ok((width, height))
-}
\ No newline at end of file
+        image.width = Some(width);
+        image.height = Some(height);
+        Ok((width, height))
+    }
+
+    /// Reads the dimensions of every image in `annotations` that are not
+    /// known yet, in parallel.
+    fn prepare(&mut self, annotations: &[Annotation], pool: &WorkerPool) {
+        let Some(image_directory) = self.image_directory.as_ref() else {
+            return;
+        };
+        let paths: HashSet<&PathBuf> = annotations
+            .iter()
+            .filter(|a| a.image.width.is_none() || a.image.height.is_none())
+            .filter_map(|a| a.image.path.as_ref())
+            .filter(|path| !self.cache.contains_key(*path))
+            .collect();
+        let paths: Vec<&PathBuf> = paths.into_iter().collect();
+
+        let dimensions = pool.map(&paths, |path| read_image_dimensions(image_directory, path));
+
+        for (path, dimensions) in paths.into_iter().zip(dimensions) {
+            if let Ok(dimensions) = dimensions {
+                self.cache.insert(path.clone(), dimensions);
+            }
+        }
+    }
+}
+
+fn read_image_dimensions(image_directory: &Path, image_path: &Path) -> Result<(u32, u32)> {
+    let image_path = resolve_relative_path(image_directory, image_path)?;
+    Ok(ImageReader::open(&image_path)?.into_dimensions()?)
+}
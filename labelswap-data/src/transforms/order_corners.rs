@@ -0,0 +1,46 @@
+/* order_corners.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use anyhow::Result;
+
+use super::{Transform, TransformOutcome};
+use crate::models::{Annotation, Format, Winding};
+
+/// OrderCorners: puts the corners of every annotation in the same order,
+/// following a winding from the top-left-most corner. With
+/// Winding::Clockwise it is the canonical order described in Annotation.
+pub struct OrderCorners {
+    winding: Winding,
+}
+
+impl OrderCorners {
+    pub fn new(winding: Winding) -> Self {
+        Self { winding }
+    }
+}
+
+impl Default for OrderCorners {
+    fn default() -> Self {
+        Self::new(Winding::default())
+    }
+}
+
+impl Transform for OrderCorners {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        annotation.order_corners(self.winding);
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn name(&self) -> &str {
+        "OrderCorners"
+    }
+}
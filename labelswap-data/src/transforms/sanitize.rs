@@ -0,0 +1,426 @@
+/* sanitize.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use anyhow::Result;
+
+use super::{AnnotationField, Transform, TransformOutcome, TransformStage};
+use crate::conversion_report::{ConversionReport, GeometryChange, GeometryFix};
+use crate::models::{
+    format::BoxType,
+    keypoints::{Keypoint, Visibility},
+    segmentation::Segmentation,
+    Annotation, Format, RotatedBox,
+};
+
+/// Sanitize: fixes the geometry of annotations coming from labeling tools.
+///
+/// - In formats with axis aligned boxes, a NaN coordinate takes the value
+///   of the neighbouring corner on the same edge, x1 and x4 or x2 and x3
+///   for x, y1 and y2 or y3 and y4 for y. Infinite coordinates are clipped
+///   like any other coordinate. Annotations that can not be fixed this way
+///   are dropped, and so are oriented boxes with non-finite coordinates, as
+///   the order of their corners is not known.
+/// - Boxes are clipped to the image bounds: 0 to 1 for normalized
+///   coordinates, 0 to the image dimensions otherwise. Without dimensions,
+///   pixel coordinates are only clipped at 0. Rotated boxes are replaced
+///   with the smallest rotated box around their part inside the image, or
+///   with the bounding box of that part when the rotated one crosses the
+///   bounds, and dropped if they lie entirely outside the image.
+/// - Segmentation polygons are clipped to the image bounds, and keypoints
+///   outside them are marked as not labeled. Non-finite points are removed
+///   or marked as not labeled too.
+/// - Boxes with a zero width or height are dropped, as well as the ones
+///   smaller than the minimum size. Sizes are measured between the minimum
+///   and maximum coordinates, so boxes whose corners are swapped are kept.
+///
+/// Every change is recorded in ConversionReport::geometry_fixes.
+pub struct Sanitize {
+    stage: TransformStage,
+    min_size: f64,
+    fixes: Vec<GeometryFix>,
+}
+
+impl Sanitize {
+    /// Creates the transform. The stage it is added to tells whether the
+    /// coordinates are normalized and which kind of boxes they describe.
+    pub fn new() -> Self {
+        Self {
+            stage: TransformStage::default(),
+            min_size: 0.0,
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Drops boxes whose width or height, in pixels, is below `min_size`.
+    /// Normalized boxes are only checked if the image dimensions are known.
+    pub fn set_min_size(&mut self, min_size: f64) {
+        self.min_size = min_size;
+    }
+
+    fn record(&mut self, annotation: &Annotation, change: GeometryChange) {
+        self.fixes.push(GeometryFix {
+            source_file: annotation.source_file.clone(),
+            image: annotation.image.path.clone(),
+            change,
+        });
+    }
+
+    /// Replaces NaN coordinates with the one of the corner sharing the same
+    /// edge, which is only valid for axis aligned boxes in the canonical
+    /// order. Returns None if a coordinate could not be fixed, or whether
+    /// any was.
+    fn fix_nan(annotation: &mut Annotation) -> Option<bool> {
+        let mut fixed = false;
+        let pairs = [
+            (&mut annotation.x1, &mut annotation.x4),
+            (&mut annotation.x2, &mut annotation.x3),
+            (&mut annotation.y1, &mut annotation.y2),
+            (&mut annotation.y4, &mut annotation.y3),
+        ];
+        for (a, b) in pairs {
+            match (a.is_nan(), b.is_nan()) {
+                (true, true) => return None,
+                (true, false) => *a = *b,
+                (false, true) => *b = *a,
+                (false, false) => continue,
+            }
+            fixed = true;
+        }
+        Some(fixed)
+    }
+
+    /// Removes the non-finite points of segmentation polygons and marks the
+    /// non-finite keypoints as not labeled. Returns whether any changed.
+    fn fix_non_finite_points(annotation: &mut Annotation) -> bool {
+        let mut fixed = false;
+        if let Some(Segmentation::Polygons(polygons)) = annotation.segmentation.as_mut() {
+            for polygon in polygons.iter_mut() {
+                let length = polygon.len();
+                polygon.retain(|(x, y)| x.is_finite() && y.is_finite());
+                fixed |= polygon.len() != length;
+            }
+        }
+        if let Some(keypoints) = annotation.keypoints.as_mut() {
+            for point in keypoints.points.iter_mut() {
+                if point.visibility != Visibility::NotLabeled
+                    && !(point.x.is_finite() && point.y.is_finite())
+                {
+                    *point = Keypoint::new(0.0, 0.0, Visibility::NotLabeled);
+                    fixed = true;
+                }
+            }
+        }
+        fixed
+    }
+
+    /// Clips every corner to `max_x` and `max_y`, and at 0. The corners
+    /// must form a box aligned with the axes, which keeps its shape. Returns
+    /// whether any coordinate changed.
+    fn clip(annotation: &mut Annotation, max_x: f64, max_y: f64) -> bool {
+        let mut clipped = false;
+        let mut clip = |value: &mut f64, max: f64| {
+            let clamped = value.clamp(0.0, max);
+            if clamped != *value {
+                *value = clamped;
+                clipped = true;
+            }
+        };
+
+        for x in [
+            &mut annotation.x1,
+            &mut annotation.x2,
+            &mut annotation.x3,
+            &mut annotation.x4,
+        ] {
+            clip(x, max_x);
+        }
+        for y in [
+            &mut annotation.y1,
+            &mut annotation.y2,
+            &mut annotation.y3,
+            &mut annotation.y4,
+        ] {
+            clip(y, max_y);
+        }
+        clipped
+    }
+
+    /// Clips a rotated box to `max_x` and `max_y`, and at 0, keeping the
+    /// winding of its corners. Returns None if no part of it is inside
+    /// those bounds, or whether it changed.
+    fn clip_rotated(annotation: &mut Annotation, max_x: f64, max_y: f64) -> Option<bool> {
+        // Corners computed from the clipped part may cross the bounds by a
+        // rounding error
+        const TOLERANCE: f64 = 1e-9;
+        let inside = |&(x, y): &(f64, f64)| {
+            (-TOLERANCE..=max_x + TOLERANCE).contains(&x)
+                && (-TOLERANCE..=max_y + TOLERANCE).contains(&y)
+        };
+
+        let corners = annotation.corners();
+        if corners.iter().all(inside) {
+            return Some(false);
+        }
+        // A box touching the bounds from the outside leaves a line or a
+        // point inside them
+        let part = clip_polygon(&corners, max_x, max_y);
+        let (xs, ys): (Vec<f64>, Vec<f64>) = part.iter().copied().unzip();
+        let min = |values: &[f64]| values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = |values: &[f64]| values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let bounding_box = Annotation::from_min_max(min(&xs), max(&xs), min(&ys), max(&ys));
+        if part.len() < 3 || bounding_box.area() <= 0.0 {
+            return None;
+        }
+
+        let rotated = RotatedBox::min_area(&part).corners();
+        let clipped = if rotated.iter().all(inside) {
+            rotated.map(|(x, y)| (x.clamp(0.0, max_x), y.clamp(0.0, max_y)))
+        } else {
+            bounding_box.corners()
+        };
+
+        let winding = annotation.winding();
+        annotation.set_corners(clipped);
+        if winding.is_some() && annotation.winding() != winding {
+            let mut corners = annotation.corners();
+            corners.reverse();
+            annotation.set_corners(corners);
+        }
+        Some(true)
+    }
+
+    /// Clips the segmentation polygons to `max_x` and `max_y`, and at 0,
+    /// and marks the keypoints outside those bounds as not labeled. Masks
+    /// cover the image already. Returns whether anything changed.
+    fn clip_points(annotation: &mut Annotation, max_x: f64, max_y: f64) -> bool {
+        let mut clipped = false;
+        if let Some(Segmentation::Polygons(polygons)) = annotation.segmentation.as_mut() {
+            for polygon in polygons.iter_mut() {
+                let clipped_polygon = clip_polygon(polygon, max_x, max_y);
+                if clipped_polygon != *polygon {
+                    *polygon = clipped_polygon;
+                    clipped = true;
+                }
+            }
+            // Polygons outside the image become points or lines
+            polygons.retain(|polygon| polygon.len() >= 3);
+            if polygons.is_empty() {
+                annotation.segmentation = None;
+            }
+        }
+
+        if let Some(keypoints) = annotation.keypoints.as_mut() {
+            let inside = |x: f64, y: f64| (0.0..=max_x).contains(&x) && (0.0..=max_y).contains(&y);
+            for point in keypoints.points.iter_mut() {
+                if point.visibility != Visibility::NotLabeled && !inside(point.x, point.y) {
+                    *point = Keypoint::new(0.0, 0.0, Visibility::NotLabeled);
+                    clipped = true;
+                }
+            }
+        }
+        clipped
+    }
+}
+
+impl Default for Sanitize {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn coordinates(annotation: &Annotation) -> impl Iterator<Item = f64> {
+    [
+        annotation.x1,
+        annotation.x2,
+        annotation.x3,
+        annotation.x4,
+        annotation.y1,
+        annotation.y2,
+        annotation.y3,
+        annotation.y4,
+    ]
+    .into_iter()
+}
+
+/// Whether the corners form a box aligned with the image axes, in either
+/// winding: each corner shares its x or y coordinate with the next one.
+fn is_axis_aligned(annotation: &Annotation) -> bool {
+    let corners = annotation.corners();
+    (0..4).all(|i| {
+        let (x1, y1) = corners[i];
+        let (x2, y2) = corners[(i + 1) % 4];
+        x1 == x2 || y1 == y2
+    })
+}
+
+/// Clips a polygon to the rectangle from 0 to `max_x` and `max_y`, one edge
+/// of the rectangle at a time (Sutherland-Hodgman).
+fn clip_polygon(polygon: &[(f64, f64)], max_x: f64, max_y: f64) -> Vec<(f64, f64)> {
+    // Each edge as the axis it bounds, its coordinate, and whether the
+    // points inside are below it
+    let edges = [
+        (0, 0.0, false),
+        (0, max_x, true),
+        (1, 0.0, false),
+        (1, max_y, true),
+    ];
+
+    let mut points = polygon.to_vec();
+    for (axis, bound, below) in edges {
+        let coordinate = |point: (f64, f64)| if axis == 0 { point.0 } else { point.1 };
+        let inside = |point: (f64, f64)| {
+            if below {
+                coordinate(point) <= bound
+            } else {
+                coordinate(point) >= bound
+            }
+        };
+        let intersection = |from: (f64, f64), to: (f64, f64)| {
+            let t = (bound - coordinate(from)) / (coordinate(to) - coordinate(from));
+            let x = from.0 + t * (to.0 - from.0);
+            let y = from.1 + t * (to.1 - from.1);
+            if axis == 0 {
+                (bound, y)
+            } else {
+                (x, bound)
+            }
+        };
+
+        let input = std::mem::take(&mut points);
+        for (i, &current) in input.iter().enumerate() {
+            let previous = input[(i + input.len() - 1) % input.len()];
+            if inside(current) != inside(previous) {
+                points.push(intersection(previous, current));
+            }
+            if inside(current) {
+                points.push(current);
+            }
+        }
+    }
+    points
+}
+
+impl Transform for Sanitize {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        source_format: &Format,
+        target_format: &Format,
+    ) -> Result<TransformOutcome> {
+        let format = self.stage.format(source_format, target_format);
+        let normalized = format.is_normalized;
+
+        if format.box_type == BoxType::AxisAligned {
+            match Self::fix_nan(annotation) {
+                None => {
+                    self.record(annotation, GeometryChange::DroppedNonFinite);
+                    return Ok(TransformOutcome::Drop(String::from(
+                        "Sanitize: NaN coordinates",
+                    )));
+                }
+                Some(true) => self.record(annotation, GeometryChange::FixedNonFinite),
+                Some(false) => {}
+            }
+        } else if coordinates(annotation).any(|coordinate| !coordinate.is_finite()) {
+            self.record(annotation, GeometryChange::DroppedNonFinite);
+            return Ok(TransformOutcome::Drop(String::from(
+                "Sanitize: non-finite coordinates",
+            )));
+        }
+        if Self::fix_non_finite_points(annotation) {
+            self.record(annotation, GeometryChange::FixedNonFinite);
+        }
+
+        let dimensions = match (annotation.image.width, annotation.image.height) {
+            (Some(width), Some(height)) => Some((f64::from(width), f64::from(height))),
+            _ => None,
+        };
+        let (max_x, max_y) = match (normalized, dimensions) {
+            (true, _) => (1.0, 1.0),
+            (false, Some(dimensions)) => dimensions,
+            (false, None) => (f64::INFINITY, f64::INFINITY),
+        };
+        let had_infinite = coordinates(annotation).any(f64::is_infinite);
+        let clipped = if is_axis_aligned(annotation) {
+            Self::clip(annotation, max_x, max_y)
+        } else if had_infinite {
+            // The part of the box inside the image is not known
+            self.record(annotation, GeometryChange::DroppedNonFinite);
+            return Ok(TransformOutcome::Drop(String::from(
+                "Sanitize: infinite coordinates",
+            )));
+        } else {
+            match Self::clip_rotated(annotation, max_x, max_y) {
+                Some(clipped) => clipped,
+                None => {
+                    self.record(annotation, GeometryChange::DroppedDegenerate);
+                    return Ok(TransformOutcome::Drop(String::from(
+                        "Sanitize: outside the image",
+                    )));
+                }
+            }
+        };
+        if Self::clip_points(annotation, max_x, max_y) || clipped {
+            self.record(annotation, GeometryChange::Clipped);
+        }
+        if had_infinite {
+            if coordinates(annotation).any(f64::is_infinite) {
+                self.record(annotation, GeometryChange::DroppedNonFinite);
+                return Ok(TransformOutcome::Drop(String::from(
+                    "Sanitize: infinite coordinates",
+                )));
+            }
+            self.record(annotation, GeometryChange::FixedNonFinite);
+        }
+
+        let width = annotation.get_xmax() - annotation.get_xmin();
+        let height = annotation.get_ymax() - annotation.get_ymin();
+        if width <= 0.0 || height <= 0.0 {
+            self.record(annotation, GeometryChange::DroppedDegenerate);
+            return Ok(TransformOutcome::Drop(String::from("Sanitize: zero size")));
+        }
+
+        let size_px = match (normalized, dimensions) {
+            (false, _) => Some((width, height)),
+            (true, Some((image_width, image_height))) => {
+                Some((width * image_width, height * image_height))
+            }
+            (true, None) => None,
+        };
+        if let Some((width, height)) = size_px {
+            if width < self.min_size || height < self.min_size {
+                self.record(annotation, GeometryChange::DroppedTooSmall);
+                return Ok(TransformOutcome::Drop(String::from(
+                    "Sanitize: smaller than the minimum size",
+                )));
+            }
+        }
+
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn set_stage(&mut self, stage: TransformStage) {
+        self.stage = stage;
+    }
+
+    fn report(&self, report: &mut ConversionReport) {
+        report.geometry_fixes.extend(self.fixes.iter().cloned());
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        // Before the required transforms, only the dimensions stored in
+        // the source format can be used, as they are read later
+        match self.stage {
+            TransformStage::BeforeRequired => &[],
+            TransformStage::AfterRequired => &[AnnotationField::ImageDimensions],
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Sanitize"
+    }
+}
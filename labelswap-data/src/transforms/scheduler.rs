@@ -0,0 +1,75 @@
+/* scheduler.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use super::AnnotationField;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("No valid order exists for the transforms at positions {unscheduled:?}")]
+pub struct ScheduleError {
+    /// Positions of the transforms that depend on each other in a cycle, or
+    /// on a transform that is part of one
+    pub unscheduled: Vec<usize>,
+}
+
+/// Orders transforms so that every transform runs after the ones that
+/// provide the fields it requires. Each element of `dependencies` is the
+/// (requires, provides) pair of a transform.
+///
+/// A transform that provides a field it requires modifies that field, like
+/// a class rename does. It only waits for the providers of that field that
+/// come before it in `dependencies`, and the ones after it wait for it, so
+/// transforms modifying the same field run in the order they were given.
+///
+/// Returns the positions of the transforms in the order they have to run.
+/// Transforms that do not depend on each other keep their relative order.
+pub fn schedule(
+    dependencies: &[(&[AnnotationField], &[AnnotationField])],
+) -> Result<Vec<usize>, ScheduleError> {
+    let count = dependencies.len();
+
+    // runs_before[i] holds every transform that has to wait for i
+    let mut runs_before: Vec<Vec<usize>> = vec![Vec::new(); count];
+    let mut pending_dependencies = vec![0usize; count];
+
+    for (provider, (_, provides)) in dependencies.iter().enumerate() {
+        for (dependent, (requires, dependent_provides)) in dependencies.iter().enumerate() {
+            if provider == dependent {
+                continue;
+            }
+            let waits = requires.iter().any(|field| {
+                provides.contains(field)
+                    && (provider < dependent || !dependent_provides.contains(field))
+            });
+            if waits {
+                runs_before[provider].push(dependent);
+                pending_dependencies[dependent] += 1;
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(count);
+    let mut scheduled = vec![false; count];
+
+    while order.len() < count {
+        let next = (0..count).find(|&i| !scheduled[i] && pending_dependencies[i] == 0);
+        let next = match next {
+            Some(next) => next,
+            None => {
+                let unscheduled = (0..count).filter(|&i| !scheduled[i]).collect();
+                return Err(ScheduleError { unscheduled });
+            }
+        };
+
+        scheduled[next] = true;
+        order.push(next);
+        for &dependent in runs_before[next].iter() {
+            pending_dependencies[dependent] -= 1;
+        }
+    }
+
+    Ok(order)
+}
@@ -5,8 +5,53 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use crate::models::{Annotation, Format};
+use crate::conversion_report::ConversionReport;
+use crate::models::{Annotation, Format, ImageRecord};
+use crate::worker_pool::WorkerPool;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// What happens to an annotation after a transform is applied to it.
+#[derive(Debug, PartialEq)]
+pub enum TransformOutcome {
+    /// The annotation continues through the rest of the transforms.
+    Keep,
+    /// The annotation is discarded without it being an error, for the given
+    /// reason. The remaining transforms are not applied to it.
+    Drop(String),
+    /// The annotation is discarded and the given annotations, if any, take
+    /// its place. They go through the rest of the transforms in order, e.g.
+    /// to split an annotation when tiling an image.
+    Replace(Vec<Annotation>),
+}
+
+/// Where a transform added with ConversionPipeline::add_transform runs,
+/// relative to the transforms required by the conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransformStage {
+    /// Runs on the annotations as they are read from the source format.
+    BeforeRequired,
+    /// Runs on the annotations once they match the target format.
+    #[default]
+    AfterRequired,
+}
+
+impl TransformStage {
+    /// Whether the coordinates of the annotations are normalized at this
+    /// stage of a conversion from `source_format` to `target_format`.
+    pub fn is_normalized(&self, source_format: &Format, target_format: &Format) -> bool {
+        self.format(source_format, target_format).is_normalized
+    }
+
+    /// The format the annotations match at this stage of a conversion from
+    /// `source_format` to `target_format`.
+    pub fn format<'a>(&self, source_format: &'a Format, target_format: &'a Format) -> &'a Format {
+        match self {
+            Self::BeforeRequired => source_format,
+            Self::AfterRequired => target_format,
+        }
+    }
+}
 
 pub trait Transform {
     fn apply(
@@ -14,14 +59,109 @@ pub trait Transform {
         annotation: &mut Annotation,
         source_format: &Format,
         target_format: &Format,
-    ) -> Result<()>;
+    ) -> Result<TransformOutcome>;
+
+    /// Applied to the images without annotations before they are written,
+    /// for the transforms that fill in fields of the image, such as its path
+    /// or its dimensions, which the target format may need. Transforms that
+    /// only work on annotations keep the default, which does nothing.
+    fn apply_image(
+        &mut self,
+        _image: &mut ImageRecord,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with a batch of annotations before the transform is applied to
+    /// each of them, when converting in parallel. Expensive work, like
+    /// reading files, can be done here for the whole batch using `pool`.
+    /// Failures should be left for apply to report.
+    fn prepare(&mut self, _annotations: &[Annotation], _pool: &WorkerPool) {}
+
+    /// Called by ConversionPipeline::add_transform with the stage the
+    /// transform is added to. Transforms that read coordinates use it to
+    /// tell whether they are normalized.
+    fn set_stage(&mut self, _stage: TransformStage) {}
+
+    /// Adds what the transform found while converting to the report. It is
+    /// called once, after every annotation has been converted.
+    fn report(&self, _report: &mut ConversionReport) {}
+
+    /// The fields of the annotation this transform reads. Required
+    /// transforms that provide them are scheduled before this one, and the
+    /// conversion fails if they can only run after it.
+    fn requires(&self) -> &[AnnotationField] {
+        &[]
+    }
+
+    /// The fields of the annotation this transform fills in.
+    fn provides(&self) -> &[AnnotationField] {
+        &[]
+    }
+
+    /// A name to refer to the transform in messages.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
 }
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+/// A piece of information stored in an Annotation that a transform can
+/// read or fill in. It is used to decide in which order transforms run.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum AnnotationField {
+    SourceFile,
+    ImagePath,
+    ImageDimensions,
+    ImageId,
+    ClassName,
+    ClassId,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum RequiredTransformations {
     MapToName,
     MapToId,
     Normalize,
     Denormalize,
+    ReadDimensions,
     LookupImage,
+    AssignImageId,
+    AlignBoxes,
+    DropSegmentation,
+}
+
+impl RequiredTransformations {
+    /// The fields of the annotation read by the transform implementing this
+    /// transformation.
+    pub fn requires(&self) -> &'static [AnnotationField] {
+        match self {
+            // The dimensions are read from the image file when the
+            // annotation does not contain them.
+            Self::Normalize | Self::Denormalize | Self::ReadDimensions => {
+                &[AnnotationField::ImagePath]
+            }
+            Self::LookupImage => &[AnnotationField::SourceFile],
+            Self::AssignImageId => &[AnnotationField::ImagePath],
+            Self::MapToName | Self::MapToId => &[],
+            Self::AlignBoxes | Self::DropSegmentation => &[],
+        }
+    }
+
+    /// The fields of the annotation filled in by the transform implementing
+    /// this transformation.
+    pub fn provides(&self) -> &'static [AnnotationField] {
+        match self {
+            Self::Normalize | Self::Denormalize | Self::ReadDimensions => {
+                &[AnnotationField::ImageDimensions]
+            }
+            Self::LookupImage => &[AnnotationField::ImagePath],
+            Self::AssignImageId => &[AnnotationField::ImageId],
+            Self::MapToName | Self::MapToId => {
+                &[AnnotationField::ClassName, AnnotationField::ClassId]
+            }
+            Self::AlignBoxes | Self::DropSegmentation => &[],
+        }
+    }
 }
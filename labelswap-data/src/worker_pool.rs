@@ -0,0 +1,42 @@
+/* worker_pool.rs
+ *
+ * Copyright 2024 Diego Iván M.E <diegoivan.mae@gmail.com>
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use anyhow::Result;
+use rayon::prelude::*;
+
+/// WorkerPool: a pool of threads used to read source files and images in
+/// parallel during a conversion.
+pub struct WorkerPool {
+    pool: rayon::ThreadPool,
+}
+
+impl WorkerPool {
+    /// Creates a pool with `threads` threads, or one per CPU if it is 0.
+    pub fn new(threads: usize) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("labelswap-worker-{index}"))
+            .build()?;
+        Ok(Self { pool })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Applies `function` to every item using the threads in the pool. The
+    /// results are in the same order as the items.
+    pub fn map<T, R, F>(&self, items: &[T], function: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync + Send,
+    {
+        self.pool
+            .install(|| items.par_iter().map(&function).collect())
+    }
+}
@@ -0,0 +1,43 @@
+use labelswap_data::models::{Annotation, Winding};
+
+#[test]
+fn constructors_share_the_corner_order() {
+    let from_centers = Annotation::from_centers(15.0, 25.0, 10.0, 30.0);
+    let from_min_max = Annotation::from_min_max(10.0, 20.0, 10.0, 40.0);
+    assert_eq!(from_centers.corners(), from_min_max.corners());
+    assert_eq!(from_min_max.winding(), Some(Winding::Clockwise));
+
+    let line = Annotation::from_min_max(10.0, 20.0, 10.0, 10.0);
+    assert_eq!(line.winding(), None);
+}
+
+#[test]
+fn order_corners() {
+    let canonical = [(2.0, 1.0), (5.0, 2.0), (4.0, 5.0), (1.0, 4.0)];
+
+    // Counter clockwise, starting from another corner
+    let mut annotation = Annotation::new(4.0, 5.0, 2.0, 1.0, 5.0, 2.0, 1.0, 4.0);
+    assert_eq!(annotation.winding(), Some(Winding::CounterClockwise));
+    annotation.order_corners(Winding::Clockwise);
+    assert_eq!(annotation.corners(), canonical);
+
+    // Crossed corners
+    let mut annotation = Annotation::new(2.0, 5.0, 1.0, 4.0, 1.0, 2.0, 4.0, 5.0);
+    annotation.order_corners(Winding::Clockwise);
+    assert_eq!(annotation.corners(), canonical);
+
+    annotation.order_corners(Winding::CounterClockwise);
+    assert_eq!(
+        annotation.corners(),
+        [(2.0, 1.0), (1.0, 4.0), (4.0, 5.0), (5.0, 2.0)]
+    );
+    assert_eq!(annotation.winding(), Some(Winding::CounterClockwise));
+
+    // On a diamond the top and left corners tie, the top one goes first
+    let mut diamond = Annotation::new(0.0, 1.0, 2.0, 1.0, 1.0, 0.0, 1.0, 2.0);
+    diamond.order_corners(Winding::Clockwise);
+    assert_eq!(
+        diamond.corners(),
+        [(1.0, 0.0), (2.0, 1.0), (1.0, 2.0), (0.0, 1.0)]
+    );
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use std::path::PathBuf;
+
+use labelswap_data::class_map::{load_class_map, ClassMap, ClassMapError};
+use labelswap_data::models::{annotation::ClassRepresentation, Annotation};
+use labelswap_data::transforms::{self, Transform};
+
+fn expected_map() -> ClassMap {
+    ClassMap::from_names(["helmet", "head", "person"]).unwrap()
+}
+
+fn load(name: &str) -> Result<ClassMap, ClassMapError> {
+    load_class_map(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/class-maps")
+            .join(name),
+    )
+}
+
+#[test]
+fn load_every_format() {
+    for name in [
+        "darknet.names",
+        "data.yaml",
+        "data-list.yaml",
+        "classes.csv",
+        "classes.json",
+        "names-to-ids.json",
+    ] {
+        match load(name) {
+            Ok(map) => assert_eq!(map, expected_map(), "{name}"),
+            Err(e) => panic!("Failed to load {name}: {e}"),
+        }
+    }
+}
+
+#[test]
+fn reject_duplicates() {
+    assert!(matches!(
+        load("duplicate-name.labels"),
+        Err(ClassMapError::DuplicateName(name)) if name == "helmet"
+    ));
+    assert!(matches!(
+        load("duplicate-id.csv"),
+        Err(ClassMapError::DuplicateId(0))
+    ));
+
+    let mut map = ClassMap::new();
+    map.insert(0, "helmet").unwrap();
+    assert!(matches!(
+        map.insert(1, ""),
+        Err(ClassMapError::WrongFormat(_))
+    ));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn reject_malformed_files() {
+    assert!(matches!(
+        load("blank-line.names"),
+        Err(ClassMapError::WrongFormat(_))
+    ));
+    assert!(matches!(
+        load("classes.xml"),
+        Err(ClassMapError::UnsupportedExtension(extension)) if extension == "xml"
+    ));
+}
+
+#[test]
+fn numeric_class_names() {
+    // Names that look like ids are only looked up as names
+    let map = ClassMap::from_names(["1", "0"]).unwrap();
+    assert_eq!(map.id("1"), Some(0));
+    assert_eq!(map.name(1), Some("0"));
+
+    let format = common::dummy_format();
+    let mut transform = transforms::ClassMapping::new(map);
+    let mut map_class = |class| {
+        let mut annotation = Annotation {
+            class,
+            ..Annotation::from_top_left_corner(0.0, 0.0, 1.0, 1.0)
+        };
+        transform.apply(&mut annotation, &format, &format).unwrap();
+        annotation.class
+    };
+
+    assert_eq!(
+        map_class(ClassRepresentation::ClassName(String::from("1"))),
+        ClassRepresentation::Both {
+            name: String::from("1"),
+            id: String::from("0"),
+        }
+    );
+    assert_eq!(
+        map_class(ClassRepresentation::ClassId(String::from("1"))),
+        ClassRepresentation::Both {
+            name: String::from("0"),
+            id: String::from("1"),
+        }
+    );
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use std::path::PathBuf;
+
+use labelswap_data::class_map::ClassMap;
+use labelswap_data::conversion_report::{ConversionReport, RuleCount};
+use common::dummy_format;
+use labelswap_data::models::{annotation::ClassRepresentation, Annotation};
+use labelswap_data::transforms::{
+    ClassRemap, ClassRule, ClassRules, ClassRulesError, Transform, TransformOutcome,
+};
+
+fn load(name: &str) -> Result<ClassRules, ClassRulesError> {
+    ClassRules::load(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/class-rules")
+            .join(name),
+    )
+}
+
+/// Runs `remap` on an annotation of class `name`, returning its new class
+/// or None if it was dropped.
+fn remap_class(remap: &mut ClassRemap, name: &str) -> Option<ClassRepresentation<String>> {
+    let format = dummy_format();
+    let mut annotation = Annotation {
+        class: ClassRepresentation::ClassName(String::from(name)),
+        ..Annotation::from_top_left_corner(0.0, 0.0, 1.0, 1.0)
+    };
+    match remap.apply(&mut annotation, &format, &format).unwrap() {
+        TransformOutcome::Drop(_) => None,
+        _ => Some(annotation.class),
+    }
+}
+
+fn both(name: &str, id: u32) -> Option<ClassRepresentation<String>> {
+    Some(ClassRepresentation::Both {
+        name: String::from(name),
+        id: id.to_string(),
+    })
+}
+
+#[test]
+fn load_rule_files() {
+    let rules = load("rules.toml").unwrap();
+    assert_eq!(rules, load("rules.json").unwrap());
+    assert_eq!(rules.rules.len(), 5);
+    assert_eq!(
+        rules.rules[1],
+        ClassRule::Merge {
+            from: vec![
+                String::from("car"),
+                String::from("truck"),
+                String::from("bus")
+            ],
+            to: String::from("vehicle"),
+        }
+    );
+
+    assert!(matches!(
+        load("rules.yaml"),
+        Err(ClassRulesError::UnsupportedExtension(extension)) if extension == "yaml"
+    ));
+    assert!(matches!(
+        ClassRemap::new(load("bad-pattern.json").unwrap(), None),
+        Err(ClassRulesError::Regex(_))
+    ));
+}
+
+#[test]
+fn remap_with_source_classes() {
+    let source =
+        ClassMap::from_names(["background", "person", "car", "bicycle", "truck_2", "dog"]).unwrap();
+    let mut remap = ClassRemap::new(load("rules.toml").unwrap(), Some(&source)).unwrap();
+
+    // Classes keep their relative order, merged ones take the place of the
+    // first one
+    let classes: Vec<&str> = remap.classes().iter().map(|(_, name)| name).collect();
+    assert_eq!(classes, vec!["pedestrian", "vehicle", "bicycle"]);
+
+    assert_eq!(remap_class(&mut remap, "truck_2"), both("vehicle", 1));
+    assert_eq!(remap_class(&mut remap, "bicycle"), both("bicycle", 2));
+    assert_eq!(remap_class(&mut remap, "person"), both("pedestrian", 0));
+    assert_eq!(remap_class(&mut remap, "bus"), both("vehicle", 1));
+    assert_eq!(remap_class(&mut remap, "background"), None);
+    assert_eq!(remap_class(&mut remap, "dog"), None);
+    assert_eq!(remap_class(&mut remap, "dog"), None);
+
+    let mut report = ConversionReport::default();
+    remap.report(&mut report);
+    let counts: Vec<usize> = report
+        .class_rule_counts
+        .iter()
+        .map(|RuleCount { annotations, .. }| *annotations)
+        .collect();
+    assert_eq!(counts, vec![1, 2, 1, 1, 2]);
+    assert_eq!(
+        report.remapped_classes,
+        vec!["pedestrian", "vehicle", "bicycle"]
+    );
+}
+
+#[test]
+fn remap_without_source_classes() {
+    let rules = ClassRules {
+        rules: vec![ClassRule::Rename {
+            from: String::from("car"),
+            to: String::from("vehicle"),
+        }],
+    };
+    let mut remap = ClassRemap::new(rules, None).unwrap();
+
+    // Ids are given as classes are first seen
+    assert_eq!(remap_class(&mut remap, "person"), both("person", 0));
+    assert_eq!(remap_class(&mut remap, "car"), both("vehicle", 1));
+    assert_eq!(remap_class(&mut remap, "vehicle"), both("vehicle", 1));
+    assert_eq!(remap.classes().len(), 2);
+}
@@ -1,8 +1,22 @@
-use labelswap_data::models::{annotation::ClassRepresentation, Annotation, Image};
+use labelswap_data::models::{
+    annotation::ClassRepresentation, Annotation, Image, Keypoint, RleCounts, RleMask, Segmentation,
+    Skeleton, Visibility,
+};
 use labelswap_data::parser::{CocoJsonParser, FormatParser};
-use std::path::Path;
+use labelswap_data::serializer::{CocoJsonSerializer, FormatSerializer};
+use std::path::PathBuf;
 
 mod common_parser;
+
+fn coco_image() -> Image {
+    Image {
+        width: Some(490),
+        height: Some(275),
+        path: Some(PathBuf::from("0001.jpg")),
+        id: Some(0),
+    }
+}
+
 #[test]
 fn test_io() {
     let mut parser = CocoJsonParser::new();
@@ -15,13 +29,14 @@ fn test_io() {
         &mut parser,
         vec![
             Annotation {
-                image: Image::new_with_path(Path::new("0001.jpg")),
+                image: coco_image(),
                 class: ClassRepresentation::Both {
                     id: String::from("2"),
                     name: String::from("helmet"),
                 },
                 source_file: None,
                 difficulty: false,
+                truncated: false,
                 x1: 45.0,
                 x2: 45.0 + 85.0,
                 x3: 45.0 + 85.0,
@@ -30,15 +45,18 @@ fn test_io() {
                 y2: 2.0,
                 y3: 2.0 + 85.0,
                 y4: 2.0 + 85.0,
+                segmentation: None,
+                keypoints: None,
             },
             Annotation {
-                image: Image::new_with_path(Path::new("0001.jpg")),
+                image: coco_image(),
                 class: ClassRepresentation::Both {
                     id: String::from("2"),
                     name: String::from("helmet"),
                 },
                 source_file: None,
                 difficulty: false,
+                truncated: false,
                 x1: 324.0,
                 x2: 324.0 + 72.0,
                 x3: 324.0 + 72.0,
@@ -47,7 +65,160 @@ fn test_io() {
                 y2: 29.0,
                 y3: 29.0 + 81.0,
                 y4: 29.0 + 81.0,
+                segmentation: None,
+                keypoints: None,
             },
         ],
     );
 }
+
+#[test]
+fn test_io_empty_images() {
+    let mut parser = CocoJsonParser::new();
+    let test_path = common_parser::resolve_test_path("tests/cocojson-labels/coco.json");
+    parser.init(&test_path).unwrap();
+
+    // The only image in the file has annotations
+    assert!(parser.take_empty_images().is_empty());
+}
+
+fn parse_all(path: &PathBuf) -> Vec<Annotation> {
+    let mut parser = CocoJsonParser::new();
+    parser.init(path).unwrap();
+    let mut annotations = Vec::new();
+    while parser.has_next() {
+        annotations.push(parser.get_next().unwrap());
+    }
+    annotations
+}
+
+#[test]
+fn test_segmentation_round_trip() {
+    let test_path = common_parser::resolve_test_path("tests/cocojson-labels/segmentation.json");
+    let annotations = parse_all(&test_path);
+
+    let segmentations: Vec<_> = annotations
+        .iter()
+        .map(|annotation| annotation.segmentation.clone())
+        .collect();
+    assert_eq!(
+        segmentations,
+        vec![
+            Some(Segmentation::Polygons(vec![
+                vec![(10.0, 10.0), (30.0, 10.0), (30.0, 30.0), (10.0, 30.0)],
+                vec![(40.0, 10.0), (50.0, 10.0), (45.0, 20.0)],
+            ])),
+            Some(Segmentation::Rle(RleMask {
+                height: 80,
+                width: 100,
+                counts: RleCounts::Uncompressed(vec![162, 4, 76, 4, 7754]),
+            })),
+            Some(Segmentation::Rle(RleMask {
+                height: 80,
+                width: 100,
+                counts: RleCounts::Compressed(String::from("Z:6Z20fZ7")),
+            })),
+        ]
+    );
+
+    let destination = std::env::temp_dir().join(format!(
+        "labelswap-coco-segmentation-{}.json",
+        std::process::id()
+    ));
+    let mut serializer = CocoJsonSerializer::new();
+    serializer.init(&destination).unwrap();
+    for annotation in parse_all(&test_path) {
+        serializer.push(annotation).unwrap();
+    }
+    serializer.finish().unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&destination).unwrap()).unwrap();
+    let written_annotations = written["annotations"].as_array().unwrap();
+    let areas: Vec<_> = written_annotations
+        .iter()
+        .map(|annotation| annotation["area"].as_f64().unwrap())
+        .collect();
+    assert_eq!(areas, vec![450.0, 8.0, 12.0]);
+    assert_eq!(written_annotations[1]["iscrowd"], 1);
+
+    assert_eq!(parse_all(&destination), annotations);
+}
+
+#[test]
+fn test_invalid_segmentation() {
+    let destination = std::env::temp_dir().join(format!(
+        "labelswap-coco-odd-polygon-{}.json",
+        std::process::id()
+    ));
+    let document = serde_json::json!({
+        "categories": [{"id": 1, "name": "person"}],
+        "images": [{"id": 0, "file_name": "0001.jpg"}],
+        "annotations": [{
+            "id": 0,
+            "image_id": 0,
+            "category_id": 1,
+            "bbox": [10, 10, 20, 20],
+            "segmentation": [[10, 10, 30, 10, 30]],
+        }],
+    });
+    std::fs::write(&destination, document.to_string()).unwrap();
+
+    let mut parser = CocoJsonParser::new();
+    parser.init(&destination).unwrap();
+    assert!(parser.get_next().is_err());
+}
+
+#[test]
+fn test_keypoints_round_trip() {
+    let test_path = common_parser::resolve_test_path("tests/cocojson-labels/keypoints.json");
+    let annotations = parse_all(&test_path);
+
+    let keypoints = annotations[0].keypoints.as_ref().unwrap();
+    assert_eq!(
+        keypoints.points,
+        vec![
+            Keypoint::new(140.0, 80.0, Visibility::Visible),
+            Keypoint::new(130.0, 70.0, Visibility::Occluded),
+            Keypoint::new(0.0, 0.0, Visibility::NotLabeled),
+        ]
+    );
+    assert_eq!(
+        keypoints.skeleton.as_deref(),
+        Some(&Skeleton {
+            names: vec![
+                String::from("nose"),
+                String::from("left_eye"),
+                String::from("right_eye"),
+            ],
+            edges: vec![(0, 1), (0, 2)],
+        })
+    );
+    assert_eq!(keypoints.get("left_eye"), Some(&keypoints.points[1]));
+    assert_eq!(keypoints.labeled(), 2);
+    assert_eq!(annotations[1].keypoints, None);
+
+    let destination = std::env::temp_dir().join(format!(
+        "labelswap-coco-keypoints-{}.json",
+        std::process::id()
+    ));
+    let mut serializer = CocoJsonSerializer::new();
+    serializer.init(&destination).unwrap();
+    for annotation in parse_all(&test_path) {
+        serializer.push(annotation).unwrap();
+    }
+    serializer.finish().unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&destination).unwrap()).unwrap();
+    assert_eq!(written["annotations"][0]["num_keypoints"], 2);
+    let person = written["categories"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|category| category["id"] == 1)
+        .unwrap();
+    assert_eq!(person["skeleton"], serde_json::json!([[1, 2], [1, 3]]));
+
+    assert_eq!(parse_all(&destination), annotations);
+}
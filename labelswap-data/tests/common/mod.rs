@@ -0,0 +1,8 @@
+use labelswap_data::models::Format;
+
+/// A format that stores axis aligned boxes in pixels with class names, and
+/// nothing else. Tests change the fields they care about with the struct
+/// update syntax.
+pub fn dummy_format() -> Format {
+    Format::new("Dummy format", "dummy")
+}
@@ -16,5 +16,5 @@ pub fn test_annotation(parser: &mut impl parser::FormatParser, expected: Vec<Ann
 pub fn resolve_test_path(path: impl AsRef<Path>) -> PathBuf {
     let mut cwd = std::env::current_dir().expect("Could not get current directory");
     cwd.push(path);
-    return cwd;
+    cwd
 }
@@ -0,0 +1,1324 @@
+use labelswap_data::class_map::ClassMap;
+use labelswap_data::conversion_pipeline::*;
+use labelswap_data::conversion_plan::PipelineInput;
+use labelswap_data::conversion_progress::{CancellationToken, ConversionProgress};
+use labelswap_data::conversion_report::ConversionReport;
+use labelswap_data::models::annotation::ClassRepresentation;
+use labelswap_data::models::format::{
+    BoxType, ClassFormat, ClassMapping, ImageDimensions, ImageId, ImagePath, SegmentationType,
+    SourceType,
+};
+use labelswap_data::models::{Annotation, Format, Image};
+use labelswap_data::parser::{
+    CocoJsonParser, FormatParser, MultiFileParser, ParserError, TfObjectDetectionParser,
+    Yolo5TxtParser,
+};
+use labelswap_data::serializer::*;
+use labelswap_data::transforms::{
+    AnnotationField, AnnotationFilter, ClassOrder, ClassRemap, ClassRule, ClassRules,
+    FilterAction, MappingGenerator, RequiredTransformations, Transform, TransformOutcome,
+    TransformStage,
+};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+mod common_parser;
+
+/// Keeps the annotations it receives, so that they can be inspected after
+/// the serializer has been consumed by the pipeline.
+struct MemorySerializer {
+    annotations: Rc<RefCell<Vec<Annotation>>>,
+    finished: Rc<RefCell<bool>>,
+}
+
+impl FormatSerializer for MemorySerializer {
+    fn init(&mut self, _path: impl Into<PathBuf>) -> SerializerResult<()> {
+        Ok(())
+    }
+
+    fn push(&mut self, annotation: Annotation) -> SerializerResult<()> {
+        if matches!(annotation.class, ClassRepresentation::None) {
+            return Err(SerializerError::MissingClassName);
+        }
+        self.annotations.borrow_mut().push(annotation);
+        Ok(())
+    }
+
+    fn finish(self) -> SerializerResult<()> {
+        *self.finished.borrow_mut() = true;
+        Ok(())
+    }
+}
+
+impl MemorySerializer {
+    fn new() -> Self {
+        Self {
+            annotations: Rc::new(RefCell::new(Vec::new())),
+            finished: Rc::new(RefCell::new(false)),
+        }
+    }
+}
+
+/// Returns a fixed list of parsing results.
+struct MemoryParser {
+    results: VecDeque<Result<Annotation, ParserError>>,
+}
+
+impl FormatParser for MemoryParser {
+    fn init(&mut self, _path: impl Into<PathBuf>) -> Result<(), ParserError> {
+        Ok(())
+    }
+
+    fn get_next(&mut self) -> Result<Annotation, ParserError> {
+        self.results.pop_front().ok_or(ParserError::OutOfElements)?
+    }
+
+    fn has_next(&mut self) -> bool {
+        !self.results.is_empty()
+    }
+
+    fn current_source_file(&self) -> Option<PathBuf> {
+        Some(PathBuf::from("memory.txt"))
+    }
+}
+
+/// Parses a single annotation from each of `files` imaginary label files,
+/// cancelling `token` when it reaches `cancel_at`.
+struct CancellingParser {
+    files: usize,
+    cancel_at: usize,
+    token: CancellationToken,
+    parsed: AtomicUsize,
+}
+
+impl MultiFileParser for CancellingParser {
+    fn source_files(&self) -> Result<Vec<PathBuf>, ParserError> {
+        Ok((0..self.files)
+            .map(|index| PathBuf::from(format!("{index:03}.txt")))
+            .collect())
+    }
+
+    fn parse_file(&self, path: &Path) -> Vec<Result<Annotation, ParserError>> {
+        self.parsed.fetch_add(1, Ordering::Relaxed);
+        if path == Path::new(&format!("{:03}.txt", self.cancel_at)) {
+            self.token.cancel();
+        }
+        vec![Ok(Annotation {
+            source_file: Some(path.to_path_buf()),
+            ..annotation_with_class(ClassRepresentation::ClassId(String::from("0")))
+        })]
+    }
+}
+
+fn annotation_with_class(class: ClassRepresentation<String>) -> Annotation {
+    Annotation {
+        class,
+        source_file: Some(PathBuf::from("memory.txt")),
+        ..Annotation::from_centers(0.5, 0.5, 0.1, 0.1)
+    }
+}
+
+/// A parser that yields a valid annotation, a malformed line, an annotation
+/// the serializer rejects and another valid annotation.
+fn faulty_parser() -> MemoryParser {
+    MemoryParser {
+        results: VecDeque::from([
+            Ok(annotation_with_class(ClassRepresentation::ClassId(String::from("0")))),
+            Err(ParserError::WrongFormat(String::from("Malformed line"))),
+            Ok(annotation_with_class(ClassRepresentation::None)),
+            Ok(annotation_with_class(ClassRepresentation::ClassId(String::from("1")))),
+        ]),
+    }
+}
+
+/// Drops annotations narrower than `min_width`, recording the order in
+/// which transforms were applied in `log`.
+struct MinWidthFilter {
+    min_width: f64,
+    log: Rc<RefCell<Vec<&'static str>>>,
+    name: &'static str,
+    requires: Vec<AnnotationField>,
+}
+
+impl Transform for MinWidthFilter {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> anyhow::Result<TransformOutcome> {
+        self.log.borrow_mut().push(self.name);
+        if (annotation.x2 - annotation.x1).abs() < self.min_width {
+            return Ok(TransformOutcome::Drop(String::from("Too narrow")));
+        }
+        Ok(TransformOutcome::Keep)
+    }
+
+    fn requires(&self) -> &[AnnotationField] {
+        &self.requires
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+}
+
+fn min_width_filter(
+    name: &'static str,
+    min_width: f64,
+    log: &Rc<RefCell<Vec<&'static str>>>,
+) -> Box<MinWidthFilter> {
+    Box::new(MinWidthFilter {
+        min_width,
+        log: log.clone(),
+        name,
+        requires: Vec::new(),
+    })
+}
+
+/// Splits every annotation into its left and right halves. An empty
+/// replacement is returned for annotations from `ignored_source`.
+struct SplitHalves {
+    ignored_source: PathBuf,
+}
+
+impl Transform for SplitHalves {
+    fn apply(
+        &mut self,
+        annotation: &mut Annotation,
+        _source_format: &Format,
+        _target_format: &Format,
+    ) -> anyhow::Result<TransformOutcome> {
+        if annotation.source_file.as_ref() == Some(&self.ignored_source) {
+            return Ok(TransformOutcome::Replace(Vec::new()));
+        }
+
+        let width = (annotation.x2 - annotation.x1).abs() / 2.0;
+        let height = (annotation.y3 - annotation.y1).abs();
+        let center_y = (annotation.y1 + annotation.y3) / 2.0;
+        let half = |center_x: f64| Annotation {
+            class: ClassRepresentation::ClassId(String::from("half")),
+            source_file: annotation.source_file.clone(),
+            ..Annotation::from_centers(center_x, center_y, width, height)
+        };
+        let left = annotation.x1.min(annotation.x2) + width / 2.0;
+        Ok(TransformOutcome::Replace(vec![half(left), half(left + width)]))
+    }
+}
+
+fn tf_format() -> Format {
+    Format {
+        name: String::from("Tensorflow Object Detection CSV"),
+        id: String::from("tfcsv"),
+        file_extension: Some(String::from("csv")),
+        is_normalized: false,
+        image_path: ImagePath::ContainsPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::ContainsDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
+        class_mapping: ClassMapping::NoMapping,
+        class_format: ClassFormat::Name,
+        source_type: SourceType::SingleFile,
+    }
+}
+
+fn yolo5txt_format() -> Format {
+    Format {
+        name: String::from("YOLO v5 TXT"),
+        id: String::from("yolo5txt"),
+        file_extension: None,
+        is_normalized: true,
+        image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
+        class_mapping: ClassMapping::NoMapping,
+        class_format: ClassFormat::Id,
+        source_type: SourceType::MultipleFiles,
+    }
+}
+
+fn coco_format() -> Format {
+    Format {
+        name: String::from("COCO JSON"),
+        id: String::from("cocojson"),
+        file_extension: Some(String::from("json")),
+        is_normalized: false,
+        image_path: ImagePath::ContainsPath,
+        image_id: ImageId::ContainsId,
+        image_dimensions: ImageDimensions::ContainsDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
+        class_mapping: ClassMapping::ContainsMapping,
+        class_format: ClassFormat::Both,
+        source_type: SourceType::SingleFile,
+    }
+}
+
+/// A format without normalized coordinates or image paths that stores
+/// classes as `class_format`.
+fn class_format(class_format: ClassFormat) -> Format {
+    Format {
+        name: format!("{class_format:?} format"),
+        id: format!("{class_format:?}"),
+        file_extension: None,
+        is_normalized: false,
+        image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
+        class_mapping: ClassMapping::NoMapping,
+        class_format,
+        source_type: SourceType::SingleFile,
+    }
+}
+
+fn output_path(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("labelswap-test-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).expect("Could not create test output directory");
+    directory.join(name)
+}
+
+#[test]
+fn convert_without_transforms() {
+    let format = yolo5txt_format();
+    let mut parser = Yolo5TxtParser::new();
+    parser
+        .init(common_parser::resolve_test_path("tests/yolo5txt-labels"))
+        .unwrap();
+
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+    let finished = serializer.finished.clone();
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_read, 4);
+    assert_eq!(report.annotations_transformed, 4);
+    assert_eq!(report.annotations_written, 4);
+    assert_eq!(report.class_counts.get("1"), Some(&4));
+    assert!(report.applied_transformations.is_empty());
+    assert_eq!(annotations.borrow().len(), 4);
+    assert!(*finished.borrow());
+}
+
+#[test]
+fn convert_round_trip() {
+    let format = tf_format();
+    let source = common_parser::resolve_test_path("tests/tfcsv-labels/tensorflow.csv");
+    let target = output_path("round_trip.csv");
+
+    let mut parser = TfObjectDetectionParser::new();
+    parser.init(&source).unwrap();
+    let mut serializer = TfObjectDetectionSerializer::new();
+    serializer.init(&target).unwrap();
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+    assert_eq!(report.annotations_read, report.annotations_written);
+
+    let mut expected = Vec::new();
+    let mut parser = TfObjectDetectionParser::new();
+    parser.init(&source).unwrap();
+    while parser.has_next() {
+        let annotation = parser.get_next().unwrap();
+        expected.push(Annotation {
+            source_file: Some(target.clone()),
+            ..annotation
+        });
+    }
+
+    let mut parser = TfObjectDetectionParser::new();
+    parser.init(&target).unwrap();
+    common_parser::test_annotation(&mut parser, expected);
+}
+
+#[test]
+fn abort_on_first_error() {
+    let format = yolo5txt_format();
+    let serializer = MemorySerializer::new();
+    let finished = serializer.finished.clone();
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    assert_eq!(pipeline.error_policy(), ErrorPolicy::Abort);
+    let error = pipeline
+        .convert(&mut faulty_parser(), serializer)
+        .unwrap_err();
+
+    match error.downcast::<ConversionError>().unwrap() {
+        ConversionError::Annotation(error) => {
+            assert_eq!(error.stage, ConversionStage::Parse);
+            assert_eq!(error.position, 1);
+            assert_eq!(error.source_file, Some(PathBuf::from("memory.txt")));
+        }
+        e => panic!("Expected a single annotation error, got {e}"),
+    }
+    assert!(!*finished.borrow());
+}
+
+#[test]
+fn skip_errors() {
+    let format = yolo5txt_format();
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.set_error_policy(ErrorPolicy::Skip);
+    let report = pipeline.convert(&mut faulty_parser(), serializer).unwrap();
+
+    assert_eq!(report.annotations_read, 3);
+    assert_eq!(report.annotations_written, 2);
+    assert_eq!(report.annotations_dropped, 1);
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(annotations.borrow().len(), 2);
+
+    let stages: Vec<(ConversionStage, usize)> = report
+        .errors
+        .iter()
+        .map(|error| (error.stage, error.position))
+        .collect();
+    assert_eq!(
+        stages,
+        vec![(ConversionStage::Parse, 1), (ConversionStage::Serialize, 2)]
+    );
+    assert_eq!(
+        report.errors[1].source_file,
+        Some(PathBuf::from("memory.txt"))
+    );
+}
+
+#[test]
+fn collect_errors() {
+    let format = yolo5txt_format();
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.set_error_policy(ErrorPolicy::Collect(2));
+    let report = pipeline
+        .convert(&mut faulty_parser(), MemorySerializer::new())
+        .unwrap();
+    assert_eq!(report.errors.len(), 2);
+
+    pipeline.set_error_policy(ErrorPolicy::Collect(1));
+    let error = pipeline
+        .convert(&mut faulty_parser(), MemorySerializer::new())
+        .unwrap_err();
+    match error.downcast::<ConversionError>().unwrap() {
+        ConversionError::TooManyErrors(errors) => assert_eq!(errors.len(), 2),
+        e => panic!("Expected too many errors, got {e}"),
+    }
+}
+
+#[test]
+fn report_unmatched_images() {
+    let source_format = yolo5txt_format();
+    let target_format = Format {
+        image_path: ImagePath::ContainsPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        ..yolo5txt_format()
+    };
+
+    let image_directory = output_path("no-images");
+    std::fs::create_dir_all(&image_directory).unwrap();
+
+    let mut parser = Yolo5TxtParser::new();
+    let source_directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+    parser.init(&source_directory).unwrap();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(image_directory));
+    pipeline.set_error_policy(ErrorPolicy::Skip);
+    let report = pipeline.convert(&mut parser, MemorySerializer::new()).unwrap();
+
+    assert_eq!(report.annotations_read, 4);
+    assert_eq!(report.annotations_transformed, 0);
+    assert_eq!(report.annotations_dropped, 4);
+    assert_eq!(
+        report.applied_transformations,
+        vec![RequiredTransformations::LookupImage]
+    );
+    assert_eq!(
+        report.unmatched_images,
+        vec![source_directory.join("001.txt"), source_directory.join("002.txt")]
+    );
+
+    let json = report.to_json().unwrap();
+    let parsed: ConversionReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, report);
+}
+
+#[test]
+fn plan_lists_missing_inputs() {
+    let source_format = yolo5txt_format();
+    let target_format = tf_format();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    let plan = pipeline.plan().unwrap();
+    let steps: Vec<(RequiredTransformations, Vec<PipelineInput>)> = plan
+        .steps
+        .iter()
+        .map(|step| (step.transformation, step.missing_inputs.clone()))
+        .collect();
+    assert_eq!(
+        steps,
+        vec![
+            (
+                RequiredTransformations::LookupImage,
+                vec![PipelineInput::ImageDirectory]
+            ),
+            (
+                RequiredTransformations::Denormalize,
+                vec![PipelineInput::ImageDirectory]
+            ),
+            (
+                RequiredTransformations::MapToName,
+                vec![PipelineInput::ClassMapping]
+            ),
+        ]
+    );
+    assert_eq!(
+        plan.steps[1].reason,
+        "YOLO v5 TXT uses normalized coordinates, Tensorflow Object Detection CSV does not"
+    );
+    assert_eq!(
+        plan.missing_inputs(),
+        vec![PipelineInput::ImageDirectory, PipelineInput::ClassMapping]
+    );
+
+    let error = pipeline
+        .convert(&mut faulty_parser(), MemorySerializer::new())
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast::<ConversionError>().unwrap(),
+        ConversionError::IncompletePlan(_)
+    ));
+
+    pipeline.set_image_directory(Some(output_path("plan-images")));
+    pipeline.set_mapping(Some(ClassMap::new()));
+    let plan = pipeline.plan().unwrap();
+    assert!(plan.is_ready());
+    assert_eq!(plan.steps.len(), 3);
+}
+
+#[test]
+fn normalize_with_stored_dimensions() {
+    // TF CSV stores the image dimensions, so no image directory is needed
+    let source_format = tf_format();
+    let target_format = yolo5txt_format();
+    let annotation = |image: Image| {
+        Ok(Annotation {
+            class: ClassRepresentation::ClassName(String::from("dog")),
+            source_file: Some(PathBuf::from("memory.csv")),
+            image,
+            ..Annotation::from_min_max(10.0, 30.0, 10.0, 20.0)
+        })
+    };
+    let mut parser = MemoryParser {
+        results: VecDeque::from([
+            annotation(Image {
+                width: Some(100),
+                height: Some(50),
+                ..Image::new_with_path("001.png")
+            }),
+            // Unless an annotation lacks them
+            annotation(Image::new_with_path("002.png")),
+        ]),
+    };
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_mapping(Some(ClassMap::from_names(["dog"]).unwrap()));
+    pipeline.set_error_policy(ErrorPolicy::Skip);
+    assert!(pipeline.plan().unwrap().is_ready());
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_written, 1);
+    assert_eq!(report.annotations_dropped, 1);
+    assert!(report.errors[0]
+        .message
+        .contains("no image directory was given"));
+    assert_eq!(
+        annotations.borrow()[0].corners(),
+        Annotation::from_min_max(0.1, 0.3, 0.2, 0.4).corners()
+    );
+}
+
+#[test]
+fn read_dimensions_for_target() {
+    // Pixel coordinates without dimensions, like YOLO v5 OBB, into a format
+    // that stores them
+    let source_format = Format {
+        image_dimensions: ImageDimensions::NoDimensions,
+        ..tf_format()
+    };
+    let target_format = tf_format();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    let plan = pipeline.plan().unwrap();
+    assert_eq!(plan.steps.len(), 1);
+    assert_eq!(
+        plan.steps[0].transformation,
+        RequiredTransformations::ReadDimensions
+    );
+    assert_eq!(plan.missing_inputs(), vec![PipelineInput::ImageDirectory]);
+
+    let image_directory = output_path("dimension-images");
+    std::fs::create_dir_all(&image_directory).unwrap();
+    image::RgbImage::new(640, 480)
+        .save(image_directory.join("001.png"))
+        .unwrap();
+    pipeline.set_image_directory(Some(image_directory));
+
+    let mut parser = MemoryParser {
+        results: VecDeque::from([Ok(Annotation {
+            class: ClassRepresentation::ClassName(String::from("dog")),
+            image: Image::new_with_path("001.png"),
+            ..Annotation::from_min_max(10.0, 30.0, 10.0, 20.0)
+        })]),
+    };
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_written, 1);
+    let annotations = annotations.borrow();
+    assert_eq!(annotations[0].image.width, Some(640));
+    assert_eq!(annotations[0].image.height, Some(480));
+    // The coordinates are left as they are
+    assert_eq!(annotations[0].x2, 30.0);
+}
+
+#[test]
+fn lookup_image_before_denormalize() {
+    let source_format = yolo5txt_format();
+    let target_format = tf_format();
+    let source_directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+
+    // Images are looked up by file stem, the labels next to them are ignored
+    let image_directory = output_path("yolo-images");
+    std::fs::create_dir_all(&image_directory).unwrap();
+    image::RgbImage::new(1000, 466)
+        .save(image_directory.join("001.png"))
+        .unwrap();
+    image::RgbImage::new(500, 233)
+        .save(image_directory.join("002.png"))
+        .unwrap();
+    std::fs::write(image_directory.join("001.txt"), "").unwrap();
+
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&source_directory).unwrap();
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(image_directory.clone()));
+    let mut mapping = ClassMap::new();
+    mapping.insert(1, "helmet").unwrap();
+    pipeline.set_mapping(Some(mapping));
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(
+        report.applied_transformations,
+        vec![
+            RequiredTransformations::LookupImage,
+            RequiredTransformations::Denormalize,
+            RequiredTransformations::MapToName,
+        ]
+    );
+    assert_eq!(report.annotations_written, 4);
+    assert_eq!(report.class_counts.get("helmet"), Some(&4));
+    assert!(report.unmatched_images.is_empty());
+
+    // Image paths are relative to the image directory
+    let annotations = annotations.borrow();
+    let first = &annotations[0];
+    assert_eq!(first.image.path, Some(PathBuf::from("001.png")));
+    assert_eq!(first.image.width, Some(1000));
+    assert!(first.x1 > 1.0);
+
+    let last = annotations.last().unwrap();
+    assert_eq!(last.image.path, Some(PathBuf::from("002.png")));
+    assert_eq!(last.image.height, Some(233));
+}
+
+#[test]
+fn relative_image_directory() {
+    let source_format = yolo5txt_format();
+    let target_format = tf_format();
+    let source_directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+
+    let image_directory = output_path("relative-images");
+    std::fs::create_dir_all(&image_directory).unwrap();
+    image::RgbImage::new(1000, 466)
+        .save(image_directory.join("001.png"))
+        .unwrap();
+    image::RgbImage::new(500, 233)
+        .save(image_directory.join("002.png"))
+        .unwrap();
+
+    // The same directory, relative to the current one
+    let current_directory = std::env::current_dir().unwrap();
+    let mut relative_directory: PathBuf = current_directory
+        .components()
+        .skip(1)
+        .map(|_| "..")
+        .collect();
+    relative_directory.push(image_directory.strip_prefix("/").unwrap());
+    assert!(relative_directory.is_relative());
+
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&source_directory).unwrap();
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(relative_directory));
+    let mut mapping = ClassMap::new();
+    mapping.insert(1, "helmet").unwrap();
+    pipeline.set_mapping(Some(mapping));
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_written, 4);
+    let annotations = annotations.borrow();
+    assert_eq!(annotations[0].image.path, Some(PathBuf::from("001.png")));
+    assert_eq!(annotations[0].image.width, Some(1000));
+}
+
+#[test]
+fn convert_yolo_to_coco() {
+    let source_format = yolo5txt_format();
+    let target_format = coco_format();
+    let source_directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+
+    let image_directory = output_path("coco-images");
+    std::fs::create_dir_all(&image_directory).unwrap();
+    image::RgbImage::new(1000, 466)
+        .save(image_directory.join("001.png"))
+        .unwrap();
+    image::RgbImage::new(500, 233)
+        .save(image_directory.join("002.png"))
+        .unwrap();
+
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&source_directory).unwrap();
+    let destination = output_path("yolo-to-coco.json");
+    let mut serializer = CocoJsonSerializer::new();
+    serializer.init(&destination).unwrap();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(image_directory));
+    let mut mapping = ClassMap::new();
+    mapping.insert(1, "helmet").unwrap();
+    pipeline.set_mapping(Some(mapping));
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert!(report
+        .applied_transformations
+        .contains(&RequiredTransformations::AssignImageId));
+    assert_eq!(report.annotations_written, 4);
+
+    let mut parser = CocoJsonParser::new();
+    parser.init(&destination).unwrap();
+    let mut ids: Vec<(String, u32)> = parser
+        .images()
+        .map(|image| {
+            let path = image.path.as_ref().unwrap();
+            (path.display().to_string(), image.id.unwrap())
+        })
+        .collect();
+    ids.sort();
+    assert_eq!(
+        ids,
+        vec![(String::from("001.png"), 0), (String::from("002.png"), 1)]
+    );
+}
+
+#[test]
+fn generate_class_mapping() {
+    let source_format = class_format(ClassFormat::Name);
+    let target_format = class_format(ClassFormat::Id);
+    let names = ["dog", "cat", "dog", "bird"];
+    let mut parser = MemoryParser {
+        results: names
+            .iter()
+            .map(|name| {
+                let class = ClassRepresentation::ClassName(String::from(*name));
+                Ok(annotation_with_class(class))
+            })
+            .collect(),
+    };
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    let output = output_path("generated-classes.txt");
+    let mut generator = MappingGenerator::new(ClassOrder::Alphabetical);
+    generator.set_output(Some(output.clone()));
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_mapping_generator(Some(generator));
+    assert!(pipeline.plan().unwrap().is_ready());
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.generated_classes, vec!["bird", "cat", "dog"]);
+    assert_eq!(report.annotations_written, 4);
+    let annotations = annotations.borrow();
+    let ids: Vec<Option<&str>> = annotations
+        .iter()
+        .map(|annotation| annotation.class.id())
+        .collect();
+    assert_eq!(ids, vec![Some("2"), Some("1"), Some("2"), Some("0")]);
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "bird\ncat\ndog\n"
+    );
+
+    // Names can not be generated from ids
+    let mut pipeline = ConversionPipeline::new(&target_format, &source_format);
+    pipeline.set_mapping_generator(Some(MappingGenerator::new(ClassOrder::Alphabetical)));
+    assert_eq!(
+        pipeline.plan().unwrap().missing_inputs(),
+        vec![PipelineInput::ClassMapping]
+    );
+}
+
+#[test]
+fn generated_class_order() {
+    let found = vec![
+        String::from("dog"),
+        String::from("cat"),
+        String::from("bird"),
+    ];
+
+    let generator = MappingGenerator::new(ClassOrder::FirstAppearance);
+    assert_eq!(generator.class_list(found.clone()), found);
+
+    let seed = vec![String::from("cat"), String::from("person")];
+    let generator = MappingGenerator::new(ClassOrder::Seeded(seed));
+    let classes = generator.class_list(found);
+    assert_eq!(classes, vec!["cat", "person", "dog", "bird"]);
+    assert_eq!(MappingGenerator::mapping(&classes).unwrap().id("dog"), Some(2));
+}
+
+#[test]
+fn user_transforms_drop_annotations() {
+    let format = yolo5txt_format();
+    let mut parser = Yolo5TxtParser::new();
+    parser
+        .init(common_parser::resolve_test_path("tests/yolo5txt-labels"))
+        .unwrap();
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.add_transform(min_width_filter("after", 0.0, &log), TransformStage::AfterRequired);
+    pipeline.add_transform(min_width_filter("before", 0.1, &log), TransformStage::BeforeRequired);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    // Only the annotation 0.07 wide is dropped, the second filter never sees it
+    assert_eq!(report.annotations_read, 4);
+    assert_eq!(report.annotations_filtered, 1);
+    assert_eq!(report.filter_reasons.get("Too narrow"), Some(&1));
+    assert_eq!(report.annotations_transformed, 3);
+    assert_eq!(report.annotations_written, 3);
+    assert_eq!(report.annotations_dropped, 0);
+    assert_eq!(annotations.borrow().len(), 3);
+    assert_eq!(
+        *log.borrow(),
+        vec!["before", "after", "before", "after", "before", "after", "before"]
+    );
+}
+
+#[test]
+fn user_transforms_respect_dependencies() {
+    let source_format = yolo5txt_format();
+    let target_format = Format {
+        image_path: ImagePath::ContainsPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        ..yolo5txt_format()
+    };
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(output_path("dependency-images")));
+    std::fs::create_dir_all(output_path("dependency-images")).unwrap();
+
+    // Needs the image path filled in by LookupImage, but has to run before it
+    let mut filter = min_width_filter("needs-image", 0.0, &log);
+    filter.requires = vec![AnnotationField::ImagePath];
+    pipeline.add_transform(filter, TransformStage::BeforeRequired);
+
+    let error = pipeline
+        .convert(&mut faulty_parser(), MemorySerializer::new())
+        .unwrap_err();
+    match error.downcast::<ConversionError>().unwrap() {
+        ConversionError::UnorderedTransforms(names) => {
+            assert_eq!(names[0], "needs-image");
+            assert!(names[1].ends_with("LookupImage"));
+        }
+        e => panic!("Expected unordered transforms, got {e}"),
+    }
+    assert!(log.borrow().is_empty());
+}
+
+#[test]
+fn chained_class_remaps() {
+    let format = class_format(ClassFormat::Name);
+    let rename = |from: &str, to: &str| {
+        let rules = ClassRules {
+            rules: vec![ClassRule::Rename {
+                from: String::from(from),
+                to: String::from(to),
+            }],
+        };
+        Box::new(ClassRemap::new(rules, None).unwrap())
+    };
+    let mut parser = MemoryParser {
+        results: VecDeque::from([Ok(annotation_with_class(ClassRepresentation::ClassName(
+            String::from("person"),
+        )))]),
+    };
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    // Both remaps read and modify the class name, the second one sees the
+    // names given by the first one
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.add_transform(rename("person", "pedestrian"), TransformStage::AfterRequired);
+    pipeline.add_transform(rename("pedestrian", "walker"), TransformStage::AfterRequired);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_written, 1);
+    assert_eq!(annotations.borrow()[0].class.name(), Some("walker"));
+}
+
+#[test]
+fn user_transforms_keep_their_order() {
+    let format = class_format(ClassFormat::Name);
+    let rules = ClassRules {
+        rules: vec![ClassRule::Rename {
+            from: String::from("person"),
+            to: String::from("pedestrian"),
+        }],
+    };
+    let mut parser = MemoryParser {
+        results: ["person", "dog"]
+            .into_iter()
+            .map(|name| Ok(annotation_with_class(ClassRepresentation::ClassName(name.into()))))
+            .collect(),
+    };
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+
+    // The filter reads the class the remap modifies, but it was added first
+    // so it sees the original names
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    let filter = AnnotationFilter::new("class in [person]", FilterAction::Keep).unwrap();
+    pipeline.add_transform(Box::new(filter), TransformStage::AfterRequired);
+    let remap = ClassRemap::new(rules, None).unwrap();
+    pipeline.add_transform(Box::new(remap), TransformStage::AfterRequired);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_filtered, 1);
+    assert_eq!(report.annotations_written, 1);
+    assert_eq!(annotations.borrow()[0].class.name(), Some("pedestrian"));
+}
+
+#[test]
+fn user_transforms_replace_annotations() {
+    let format = yolo5txt_format();
+    let source_directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&source_directory).unwrap();
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.add_transform(
+        Box::new(SplitHalves {
+            ignored_source: source_directory.join("002.txt"),
+        }),
+        TransformStage::BeforeRequired,
+    );
+    // Halves of the annotation 0.114 wide are dropped, the other ones kept
+    pipeline.add_transform(min_width_filter("filter", 0.06, &log), TransformStage::AfterRequired);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+
+    assert_eq!(report.annotations_read, 4);
+    assert_eq!(report.annotations_replaced, 4);
+    assert_eq!(report.annotations_filtered, 2);
+    assert_eq!(report.annotations_transformed, 2);
+    assert_eq!(report.annotations_written, 2);
+    assert_eq!(report.class_counts.get("half"), Some(&2));
+    assert_eq!(log.borrow().len(), 4);
+
+    // Replacements are written in the order they were returned
+    let annotations = annotations.borrow();
+    for annotation in annotations.iter() {
+        assert!((annotation.x2 - annotation.x1 - 0.078).abs() < 1e-9);
+        assert_eq!(
+            annotation.source_file,
+            Some(source_directory.join("001.txt"))
+        );
+    }
+    assert!((annotations[0].x1 - 0.016).abs() < 1e-9);
+    assert!((annotations[1].x1 - 0.094).abs() < 1e-9);
+}
+
+#[test]
+fn convert_parallel_matches_convert() {
+    let source_format = yolo5txt_format();
+    let target_format = tf_format();
+
+    // More files than a single batch holds, each with its own image
+    let directory = output_path("parallel-dataset");
+    std::fs::create_dir_all(&directory).unwrap();
+    for index in 0..300 {
+        let mut labels = format!("1 0.5 0.5 0.2 0.2\n0 0.25 0.{index:03} 0.1 0.1\n");
+        if index == 150 {
+            labels.push_str("malformed line\n");
+        }
+        std::fs::write(directory.join(format!("{index:03}.txt")), labels).unwrap();
+        image::RgbImage::new(100 + index, 50)
+            .save(directory.join(format!("{index:03}.png")))
+            .unwrap();
+    }
+
+    let mapping = ClassMap::from_names(["person", "helmet"]).unwrap();
+    let convert = |parallel: bool| {
+        let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+        pipeline.set_image_directory(Some(directory.clone()));
+        pipeline.set_mapping(Some(mapping.clone()));
+        pipeline.set_error_policy(ErrorPolicy::Skip);
+        pipeline.set_threads(4);
+
+        let mut parser = Yolo5TxtParser::new();
+        parser.init(&directory).unwrap();
+        let serializer = MemorySerializer::new();
+        let annotations = serializer.annotations.clone();
+        let report = if parallel {
+            pipeline.convert_parallel(&parser, serializer).unwrap()
+        } else {
+            pipeline.convert(&mut parser, serializer).unwrap()
+        };
+        (report, annotations.take())
+    };
+
+    let (report, annotations) = convert(false);
+    let (parallel_report, parallel_annotations) = convert(true);
+
+    assert_eq!(report.annotations_written, 600);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].position, 302);
+    assert_eq!(
+        report.errors[0].source_file,
+        Some(directory.join("150.txt"))
+    );
+    assert_eq!(parallel_report, report);
+    assert_eq!(parallel_annotations, annotations);
+    assert_eq!(annotations[599].image.width, Some(399));
+}
+
+#[test]
+fn convert_parallel_to_coco_is_deterministic() {
+    let source_format = yolo5txt_format();
+    let target_format = coco_format();
+
+    let directory = output_path("deterministic-dataset");
+    std::fs::create_dir_all(&directory).unwrap();
+    for index in 0..40 {
+        let labels = format!("{} 0.5 0.5 0.2 0.2\n", index % 2);
+        std::fs::write(directory.join(format!("{index:03}.txt")), labels).unwrap();
+        image::RgbImage::new(10 + index, 10)
+            .save(directory.join(format!("{index:03}.png")))
+            .unwrap();
+    }
+
+    let convert = |name: &str| {
+        let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+        pipeline.set_image_directory(Some(directory.clone()));
+        pipeline.set_mapping(Some(ClassMap::from_names(["person", "helmet"]).unwrap()));
+        pipeline.set_threads(4);
+
+        let mut parser = Yolo5TxtParser::new();
+        parser.init(&directory).unwrap();
+        let destination = output_path(name);
+        let mut serializer = CocoJsonSerializer::new();
+        serializer.init(&destination).unwrap();
+        pipeline.convert_parallel(&parser, serializer).unwrap();
+
+        // Only the creation dates change between conversions
+        std::fs::read_to_string(destination)
+            .unwrap()
+            .lines()
+            .filter(|line| {
+                !line.contains("\"date_captured\"") && !line.contains("\"data_created\"")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    assert_eq!(
+        convert("deterministic-1.json"),
+        convert("deterministic-2.json")
+    );
+}
+
+#[test]
+fn convert_keeps_empty_label_files() {
+    let format = yolo5txt_format();
+    let directory = output_path("empty-label-files");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("001.txt"), "").unwrap();
+    std::fs::write(directory.join("002.txt"), "1 0.5 0.5 0.2 0.2\n").unwrap();
+    std::fs::write(directory.join("003.txt"), "").unwrap();
+
+    let convert = |parallel: bool| {
+        let mut pipeline = ConversionPipeline::new(&format, &format);
+        let mut parser = Yolo5TxtParser::new();
+        parser.init(&directory).unwrap();
+        if parallel {
+            pipeline.convert_parallel(&parser, MemorySerializer::new()).unwrap()
+        } else {
+            pipeline.convert(&mut parser, MemorySerializer::new()).unwrap()
+        }
+    };
+
+    let report = convert(false);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.annotations_written, 1);
+    assert_eq!(report.empty_images_written, 2);
+    assert_eq!(convert(true), report);
+}
+
+/// A YOLO dataset whose second label file is empty, along with its images.
+fn yolo_with_empty_label_file(name: &str) -> (PathBuf, PathBuf) {
+    let label_directory = output_path(&format!("{name}-labels"));
+    let image_directory = output_path(&format!("{name}-images"));
+    std::fs::create_dir_all(&label_directory).unwrap();
+    std::fs::create_dir_all(&image_directory).unwrap();
+    std::fs::write(label_directory.join("001.txt"), "1 0.5 0.5 0.2 0.2\n").unwrap();
+    std::fs::write(label_directory.join("002.txt"), "").unwrap();
+    image::RgbImage::new(100, 50)
+        .save(image_directory.join("001.png"))
+        .unwrap();
+    image::RgbImage::new(200, 100)
+        .save(image_directory.join("002.png"))
+        .unwrap();
+    (label_directory, image_directory)
+}
+
+#[test]
+fn convert_empty_label_files_to_coco() {
+    let source_format = yolo5txt_format();
+    let target_format = coco_format();
+    let (label_directory, image_directory) = yolo_with_empty_label_file("empty-to-coco");
+
+    let destination = output_path("empty-to-coco.json");
+    let mut serializer = CocoJsonSerializer::new();
+    serializer.init(&destination).unwrap();
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&label_directory).unwrap();
+
+    // The empty image needs a path, an id and its dimensions
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(image_directory));
+    pipeline.set_mapping(Some(ClassMap::from_names(["person", "helmet"]).unwrap()));
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+    assert_eq!(report.annotations_written, 1);
+    assert_eq!(report.empty_images_written, 1);
+
+    let mut parser = CocoJsonParser::new();
+    parser.init(&destination).unwrap();
+    let mut images: Vec<_> = parser
+        .images()
+        .map(|image| (image.path.clone().unwrap(), image.id, image.width))
+        .collect();
+    images.sort();
+    assert_eq!(
+        images,
+        vec![
+            (PathBuf::from("001.png"), Some(0), Some(100)),
+            (PathBuf::from("002.png"), Some(1), Some(200)),
+        ]
+    );
+}
+
+#[test]
+fn convert_empty_label_files_to_voc() {
+    let source_format = yolo5txt_format();
+    let target_format = Format {
+        name: String::from("Pascal VOC XML"),
+        id: String::from("pascalvoc"),
+        source_type: SourceType::MultipleFiles,
+        ..tf_format()
+    };
+    let (label_directory, image_directory) = yolo_with_empty_label_file("empty-to-voc");
+    // Without an image, the empty label file can not be written
+    std::fs::write(label_directory.join("003.txt"), "").unwrap();
+
+    let destination = output_path("empty-to-voc");
+    let mut serializer = PascalVocSerializer::new();
+    serializer.init(&destination).unwrap();
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&label_directory).unwrap();
+
+    let mut pipeline = ConversionPipeline::new(&source_format, &target_format);
+    pipeline.set_image_directory(Some(image_directory));
+    pipeline.set_mapping(Some(ClassMap::from_names(["person", "helmet"]).unwrap()));
+    pipeline.set_error_policy(ErrorPolicy::Skip);
+    let report = pipeline.convert(&mut parser, serializer).unwrap();
+    assert_eq!(report.annotations_written, 1);
+    assert_eq!(report.annotations_dropped, 0);
+    assert_eq!(report.empty_images_written, 1);
+    assert_eq!(report.empty_images_dropped, 1);
+    assert_eq!(
+        report.unmatched_images,
+        vec![label_directory.join("003.txt")]
+    );
+
+    let written = std::fs::read_to_string(destination.join("002.xml")).unwrap();
+    assert!(written.contains("<filename>002.png</filename>"));
+    assert!(written.contains("<width>200</width>"));
+    assert!(!written.contains("<object>"));
+}
+
+#[test]
+fn multi_file_parser_matches_format_parser() {
+    let directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&directory).unwrap();
+
+    let files = parser.source_files().unwrap();
+    assert_eq!(files, vec![directory.join("001.txt"), directory.join("002.txt")]);
+
+    let parsed: Vec<Annotation> = files
+        .iter()
+        .flat_map(|file| parser.parse_file(file))
+        .map(|annotation| annotation.unwrap())
+        .collect();
+    common_parser::test_annotation(&mut parser, parsed);
+}
+
+#[test]
+fn report_progress() {
+    let format = yolo5txt_format();
+    let directory = common_parser::resolve_test_path("tests/yolo5txt-labels");
+    let updates = Rc::new(RefCell::new(Vec::new()));
+
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    let log = updates.clone();
+    pipeline.set_progress_callback(move |progress| log.borrow_mut().push(*progress));
+
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&directory).unwrap();
+    pipeline.convert(&mut parser, MemorySerializer::new()).unwrap();
+
+    let files: Vec<(usize, usize)> = updates
+        .borrow()
+        .iter()
+        .map(|progress| (progress.files_processed, progress.annotations_processed))
+        .collect();
+    assert_eq!(files, vec![(0, 1), (0, 2), (1, 3), (1, 4), (2, 4)]);
+    assert!(updates.borrow().iter().all(|p| p.total_annotations.is_none()));
+    assert!(updates.borrow().iter().all(|p| p.total_files == Some(2)));
+    assert_eq!(updates.borrow().last().unwrap().fraction(), Some(1.0));
+
+    // Files without annotations are counted once the parser is done
+    let empty_directory = output_path("progress-labels");
+    std::fs::create_dir_all(&empty_directory).unwrap();
+    std::fs::write(empty_directory.join("001.txt"), "1 0.5 0.5 0.1 0.1\n").unwrap();
+    std::fs::write(empty_directory.join("002.txt"), "").unwrap();
+    updates.borrow_mut().clear();
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&empty_directory).unwrap();
+    pipeline.convert(&mut parser, MemorySerializer::new()).unwrap();
+    let last = *updates.borrow().last().unwrap();
+    assert_eq!((last.files_processed, last.total_files), (2, Some(2)));
+
+    updates.borrow_mut().clear();
+    let mut parser = Yolo5TxtParser::new();
+    parser.init(&directory).unwrap();
+    pipeline.convert_parallel(&parser, MemorySerializer::new()).unwrap();
+    assert_eq!(
+        *updates.borrow(),
+        vec![ConversionProgress {
+            files_processed: 2,
+            total_files: Some(2),
+            annotations_processed: 4,
+            total_annotations: None,
+        }]
+    );
+    assert_eq!(updates.borrow()[0].fraction(), Some(1.0));
+}
+
+#[test]
+fn cancel_conversion() {
+    let format = yolo5txt_format();
+    let token = CancellationToken::new();
+
+    // Cancelled once the second annotation has been converted
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.set_error_policy(ErrorPolicy::Skip);
+    pipeline.set_cancellation_token(Some(token.clone()));
+    let callback_token = token.clone();
+    pipeline.set_progress_callback(move |progress| {
+        if progress.annotations_processed == 2 {
+            callback_token.cancel();
+        }
+    });
+
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+    let finished = serializer.finished.clone();
+    let report = pipeline.convert(&mut faulty_parser(), serializer).unwrap();
+
+    assert!(report.cancelled);
+    assert_eq!(report.annotations_read, 1);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(annotations.borrow().len(), 1);
+    assert!(*finished.borrow());
+
+    // Nothing is read by a cancelled token
+    let mut parser = Yolo5TxtParser::new();
+    parser
+        .init(common_parser::resolve_test_path("tests/yolo5txt-labels"))
+        .unwrap();
+    let serializer = MemorySerializer::new();
+    let finished = serializer.finished.clone();
+    let report = pipeline.convert_parallel(&parser, serializer).unwrap();
+    assert!(report.cancelled);
+    assert_eq!(report.annotations_read, 0);
+    assert!(*finished.borrow());
+
+    // Cancelled while a batch is parsed, the files left are skipped and the
+    // batch is not written
+    let token = CancellationToken::new();
+    let parser = CancellingParser {
+        files: 100,
+        cancel_at: 10,
+        token: token.clone(),
+        parsed: AtomicUsize::new(0),
+    };
+    let mut pipeline = ConversionPipeline::new(&format, &format);
+    pipeline.set_cancellation_token(Some(token));
+    pipeline.set_threads(1);
+    let serializer = MemorySerializer::new();
+    let annotations = serializer.annotations.clone();
+    let report = pipeline.convert_parallel(&parser, serializer).unwrap();
+    assert!(report.cancelled);
+    assert_eq!(parser.parsed.load(Ordering::Relaxed), 11);
+    assert!(annotations.borrow().is_empty());
+}
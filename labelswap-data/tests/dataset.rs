@@ -0,0 +1,122 @@
+use labelswap_data::models::{
+    annotation::ClassRepresentation, Annotation, Dataset, Image, ImageRecord,
+};
+use labelswap_data::parser::*;
+use labelswap_data::serializer::*;
+use std::path::PathBuf;
+
+fn output_path(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("labelswap-dataset-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).expect("Could not create test output directory");
+    directory.join(name)
+}
+
+fn annotation_on(image: Image, class: &str) -> Annotation {
+    Annotation {
+        image,
+        class: ClassRepresentation::ClassName(String::from(class)),
+        ..Annotation::from_top_left_corner(1.0, 1.0, 10.0, 10.0)
+    }
+}
+
+#[test]
+fn group_annotations_by_image() {
+    let first = Image {
+        id: Some(1),
+        ..Image::new_with_path("a.jpg")
+    };
+    let second = Image::new_with_path("b.jpg");
+
+    let mut dataset = Dataset::from_annotations([
+        annotation_on(first.clone(), "cat"),
+        annotation_on(second.clone(), "dog"),
+        annotation_on(first.clone(), "dog"),
+    ]);
+    dataset.push_image(ImageRecord::new(Image::new_with_path("c.jpg"), None));
+    // An image that is already in the dataset is not added twice
+    dataset.push_image(ImageRecord::new(second.clone(), None));
+
+    assert_eq!(dataset.len(), 3);
+    let images = dataset.images();
+    assert_eq!(images[0].image, first);
+    assert_eq!(images[0].annotations.len(), 2);
+    assert_eq!(images[1].image, second);
+    assert_eq!(images[1].annotations.len(), 1);
+    assert_eq!(dataset.annotations().count(), 3);
+
+    let empty: Vec<_> = dataset.empty_images().collect();
+    assert_eq!(empty.len(), 1);
+    assert_eq!(empty[0].image, Image::new_with_path("c.jpg"));
+}
+
+#[test]
+fn keep_empty_label_files() {
+    let source = output_path("yolo5obb-source");
+    let destination = output_path("yolo5obb-destination");
+    std::fs::create_dir_all(&source).unwrap();
+    let labels = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/yolo5obb-labels");
+    std::fs::copy(labels.join("001.txt"), source.join("001.txt")).unwrap();
+    std::fs::write(source.join("002.txt"), "").unwrap();
+
+    let mut parser = Yolo5ObbParser::new();
+    parser.init(&source).unwrap();
+    let dataset = Dataset::read(&mut parser).unwrap();
+
+    assert_eq!(dataset.len(), 2);
+    assert_eq!(dataset.annotations().count(), 3);
+    let empty: Vec<_> = dataset.empty_images().collect();
+    assert_eq!(empty.len(), 1);
+    assert_eq!(empty[0].source_file, Some(source.join("002.txt")));
+
+    let mut serializer = Yolo5ObbSerializer::new();
+    serializer.init(&destination).unwrap();
+    dataset.write(serializer).unwrap();
+
+    let empty_file = std::fs::read_to_string(destination.join("002.txt")).unwrap();
+    assert!(empty_file.is_empty());
+    let labels = std::fs::read_to_string(destination.join("001.txt")).unwrap();
+    assert_eq!(labels.lines().count(), 3);
+}
+
+#[test]
+fn keep_empty_coco_images() {
+    let source = output_path("coco-source.json");
+    let destination = output_path("coco-destination.json");
+    let document = serde_json::json!({
+        "categories": [{"id": 1, "name": "helmet"}],
+        "images": [
+            {"id": 3, "file_name": "0001.jpg", "width": 640, "height": 480},
+            {"id": 7, "file_name": "0002.jpg", "width": 320, "height": 240},
+        ],
+        "annotations": [
+            {"id": 0, "image_id": 3, "category_id": 1, "bbox": [10, 10, 20, 20]},
+        ],
+    });
+    std::fs::write(&source, document.to_string()).unwrap();
+
+    let mut parser = CocoJsonParser::new();
+    parser.init(&source).unwrap();
+    let dataset = Dataset::read(&mut parser).unwrap();
+
+    assert_eq!(dataset.len(), 2);
+    let empty: Vec<_> = dataset.empty_images().collect();
+    assert_eq!(empty.len(), 1);
+    assert_eq!(
+        empty[0].image,
+        Image {
+            width: Some(320),
+            height: Some(240),
+            path: Some(PathBuf::from("0002.jpg")),
+            id: Some(7),
+        }
+    );
+
+    let mut serializer = CocoJsonSerializer::new();
+    serializer.init(&destination).unwrap();
+    dataset.write(serializer).unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&destination).unwrap()).unwrap();
+    assert_eq!(written["images"].as_array().unwrap().len(), 2);
+    assert_eq!(written["annotations"].as_array().unwrap().len(), 1);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use labelswap_data::models::{annotation::ClassRepresentation, format::*, Annotation, Image};
+use labelswap_data::transforms::{
+    AnnotationFilter, FilterAction, Predicate, PredicateError, Transform, TransformOutcome,
+    TransformStage,
+};
+
+fn dummy_format(is_normalized: bool) -> Format {
+    Format {
+        is_normalized,
+        image_path: ImagePath::ContainsPath,
+        ..common::dummy_format()
+    }
+}
+
+fn annotation(class: &str, width: f64, height: f64, difficulty: bool) -> Annotation {
+    Annotation {
+        class: ClassRepresentation::ClassName(String::from(class)),
+        difficulty,
+        image: Image {
+            path: Some("images/train/0001.jpg".into()),
+            ..Image::new_with_dimensions(100, 50)
+        },
+        ..Annotation::from_top_left_corner(10.0, 10.0, width, height)
+    }
+}
+
+fn matches(expression: &str, annotation: &Annotation) -> bool {
+    let predicate: Predicate = expression.parse().unwrap();
+    predicate.matches(annotation, false).unwrap()
+}
+
+#[test]
+fn evaluate_predicates() {
+    let person = annotation("person", 10.0, 8.0, false);
+    let dog = annotation("dog", 4.0, 4.0, true);
+
+    let expression = "class in [person, dog] and area_px > 64 and not difficult";
+    assert!(matches(expression, &person));
+    assert!(!matches(expression, &dog));
+
+    assert!(matches("class == dog or width_px >= 10", &person));
+    assert!(matches("class != 'cat' and class not in [bird]", &dog));
+    assert!(matches("not (difficult or aspect < 1)", &person));
+    assert!(matches("height_px == 4 and difficulty", &dog));
+
+    // Normalized measures are fractions of the 100x50 image
+    assert!(matches("width == 0.1 and height > 0.15", &person));
+    assert!(matches("area < 0.01", &dog));
+
+    assert!(matches("image matches \"images/train/*\"", &person));
+    assert!(!matches("image matches *.png", &person));
+}
+
+#[test]
+fn reject_invalid_expressions() {
+    let parse = |expression: &str| expression.parse::<Predicate>().err();
+
+    assert_eq!(
+        parse("size > 3"),
+        Some(PredicateError::UnknownField(String::from("size")))
+    );
+    assert_eq!(
+        parse("area > big"),
+        Some(PredicateError::InvalidNumber(String::from("big")))
+    );
+    assert!(matches!(
+        parse("class in [person"),
+        Some(PredicateError::Unexpected { .. })
+    ));
+    assert!(matches!(
+        parse("difficult difficult"),
+        Some(PredicateError::Unexpected { .. })
+    ));
+    assert!(matches!(
+        parse("image matches \"[\""),
+        Some(PredicateError::InvalidPattern(_))
+    ));
+}
+
+#[test]
+fn filter_annotations() {
+    let source = dummy_format(false);
+    let target = dummy_format(true);
+
+    let mut keep = AnnotationFilter::new("area_px > 64", FilterAction::Keep).unwrap();
+    keep.set_stage(TransformStage::BeforeRequired);
+    let mut drop = AnnotationFilter::new("area_px > 64", FilterAction::Drop).unwrap();
+    drop.set_stage(TransformStage::BeforeRequired);
+
+    let mut person = annotation("person", 10.0, 8.0, false);
+    assert_eq!(
+        keep.apply(&mut person, &source, &target).unwrap(),
+        TransformOutcome::Keep
+    );
+    assert_eq!(
+        drop.apply(&mut person, &source, &target).unwrap(),
+        TransformOutcome::Drop(String::from("Filter: drop if area_px > 64"))
+    );
+
+    // After the required transforms the coordinates are normalized
+    let mut keep = AnnotationFilter::new("area_px > 64", FilterAction::Keep).unwrap();
+    keep.set_stage(TransformStage::AfterRequired);
+    let mut normalized = Annotation {
+        image: Image::new_with_dimensions(100, 50),
+        ..Annotation::from_top_left_corner(0.1, 0.2, 0.1, 0.16)
+    };
+    assert_eq!(
+        keep.apply(&mut normalized, &source, &target).unwrap(),
+        TransformOutcome::Keep
+    );
+
+    // Converting units needs the image dimensions
+    let mut no_dimensions = Annotation::from_top_left_corner(0.1, 0.2, 0.1, 0.16);
+    assert!(keep.apply(&mut no_dimensions, &source, &target).is_err());
+}
@@ -0,0 +1,201 @@
+mod common_parser;
+
+use labelswap_data::models::{annotation::ClassRepresentation, *};
+use labelswap_data::parser::*;
+use labelswap_data::resolve_relative_path;
+use labelswap_data::serializer::*;
+use std::path::PathBuf;
+
+fn voc_annotation(
+    class: &str,
+    source_file: Option<PathBuf>,
+    flags: (bool, bool),
+    bounds: (f64, f64, f64, f64),
+) -> Annotation {
+    let (difficulty, truncated) = flags;
+    let (x_min, x_max, y_min, y_max) = bounds;
+    Annotation {
+        class: ClassRepresentation::ClassName(String::from(class)),
+        source_file,
+        difficulty,
+        truncated,
+        image: Image {
+            width: Some(640),
+            height: Some(480),
+            ..Image::new_with_path("001.jpg")
+        },
+        ..Annotation::from_min_max(x_min, x_max, y_min, y_max)
+    }
+}
+
+fn parse_all(path: &PathBuf) -> (Vec<Annotation>, Vec<ImageRecord>) {
+    let mut parser = PascalVocParser::new();
+    parser.init(path).unwrap();
+    let mut annotations = Vec::new();
+    while parser.has_next() {
+        annotations.push(parser.get_next().unwrap());
+    }
+    (annotations, parser.take_empty_images())
+}
+
+fn output_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("labelswap-pascalvoc-{name}-{}", std::process::id()))
+}
+
+#[test]
+fn test_io() {
+    let mut parser = PascalVocParser::new();
+    let source_directory = common_parser::resolve_test_path("tests/pascalvoc-labels");
+    parser.init(&source_directory).unwrap();
+
+    // The 1-based first pixels are moved back, the last ones are kept
+    let first = resolve_relative_path(&source_directory, "001.xml").ok();
+    common_parser::test_annotation(
+        &mut parser,
+        vec![
+            voc_annotation(
+                "dog",
+                first.clone(),
+                (false, false),
+                (48.0, 300.0, 100.0, 400.0),
+            ),
+            voc_annotation("person", first, (true, true), (0.0, 120.0, 20.0, 480.0)),
+        ],
+    );
+
+    // A file without objects is an image without annotations
+    let empty_images = parser.take_empty_images();
+    assert_eq!(empty_images.len(), 1);
+    assert!(empty_images[0].is_empty());
+    assert_eq!(
+        empty_images[0].image,
+        Image {
+            width: Some(320),
+            height: Some(240),
+            ..Image::new_with_path("002.png")
+        }
+    );
+}
+
+#[test]
+fn test_invalid_files() {
+    let directory = output_directory("invalid");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("001.xml"),
+        "<annotation><filename>001.jpg</filename><object><name>dog</name></object></annotation>",
+    )
+    .unwrap();
+
+    let mut parser = PascalVocParser::new();
+    parser.init(&directory).unwrap();
+    assert!(parser.has_next());
+    // An object without a box
+    assert!(parser.get_next().is_err());
+    assert!(!parser.has_next());
+}
+
+#[test]
+fn test_class_name_whitespace() {
+    // Padding around plain text is trimmed by the XML reader, not in CDATA
+    let directory = output_directory("whitespace");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("001.xml"),
+        "<annotation><filename>001.jpg</filename><object><name><![CDATA[ dog\t]]></name>\
+        <bndbox><xmin>1</xmin><ymin>1</ymin><xmax>2</xmax><ymax>2</ymax></bndbox>\
+        </object></annotation>",
+    )
+    .unwrap();
+
+    let (annotations, _) = parse_all(&directory);
+    assert_eq!(
+        annotations[0].class,
+        ClassRepresentation::ClassName(String::from("dog"))
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let source_directory = common_parser::resolve_test_path("tests/pascalvoc-labels");
+    let destination = output_directory("round-trip");
+
+    let (annotations, empty_images) = parse_all(&source_directory);
+    let mut serializer = PascalVocSerializer::new();
+    serializer.init(&destination).unwrap();
+    for annotation in annotations {
+        serializer.push(annotation).unwrap();
+    }
+    for image in empty_images {
+        serializer.push_image(image).unwrap();
+    }
+    serializer.finish().unwrap();
+
+    let without_source = |(annotations, empty_images): (Vec<Annotation>, Vec<ImageRecord>)| {
+        let annotations: Vec<_> = annotations
+            .into_iter()
+            .map(|annotation| Annotation {
+                source_file: None,
+                ..annotation
+            })
+            .collect();
+        let images: Vec<_> = empty_images
+            .into_iter()
+            .map(|record| record.image)
+            .collect();
+        (annotations, images)
+    };
+    assert_eq!(
+        without_source(parse_all(&destination)),
+        without_source(parse_all(&source_directory))
+    );
+}
+
+#[test]
+fn test_pixel_convention() {
+    let destination = output_directory("pixels");
+    let annotation = Annotation {
+        class: ClassRepresentation::Both {
+            name: String::from("cat"),
+            id: String::from("3"),
+        },
+        difficulty: true,
+        image: Image {
+            width: Some(100),
+            height: Some(50),
+            ..Image::new_with_path("images/003.jpg")
+        },
+        ..Annotation::from_min_max(-0.2, 10.4, 9.6, 20.5)
+    };
+
+    let mut serializer = PascalVocSerializer::new();
+    serializer.init(&destination).unwrap();
+    serializer.push(annotation).unwrap();
+    serializer.finish().unwrap();
+
+    // The box covers pixels 0 to 9 and 10 to 20, starting from 0. It
+    // touches the border, but it is not marked as truncated
+    let written = std::fs::read_to_string(destination.join("003.xml")).unwrap();
+    assert!(written.contains("<folder>images</folder>"));
+    assert!(written.contains("<filename>003.jpg</filename>"));
+    assert!(written.contains("<name>cat</name>"));
+    assert!(written.contains("<truncated>0</truncated>"));
+    assert!(written.contains("<difficult>1</difficult>"));
+    assert!(written.contains(
+        "<xmin>1</xmin>\n            <ymin>11</ymin>\n            \
+         <xmax>10</xmax>\n            <ymax>21</ymax>"
+    ));
+
+    // VOC requires the size of the image
+    let mut serializer = PascalVocSerializer::new();
+    serializer.init(&destination).unwrap();
+    let without_size = Annotation {
+        class: ClassRepresentation::ClassName(String::from("cat")),
+        image: Image::new_with_path("004.jpg"),
+        ..Annotation::from_min_max(1.0, 2.0, 1.0, 2.0)
+    };
+    assert!(matches!(
+        serializer.push(without_size),
+        Err(SerializerError::MissingImageDimensions(_))
+    ));
+}
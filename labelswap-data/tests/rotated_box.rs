@@ -0,0 +1,71 @@
+use labelswap_data::models::{AngleRange, AngleUnit, Annotation, RotatedBox};
+
+/// The box as (center_x, center_y, width, height, angle in degrees),
+/// rounded to avoid floating point errors.
+fn rounded(rotated_box: &RotatedBox) -> [f64; 5] {
+    [
+        rotated_box.center_x,
+        rotated_box.center_y,
+        rotated_box.width,
+        rotated_box.height,
+        rotated_box.angle(AngleUnit::Degrees),
+    ]
+    .map(|value| (value * 1000.0).round() / 1000.0)
+}
+
+#[test]
+fn axis_aligned_box() {
+    let annotation = Annotation::from_min_max(10.0, 30.0, 10.0, 20.0);
+
+    let le90 = annotation.to_rotated_box(AngleRange::Le90);
+    assert_eq!(rounded(&le90), [20.0, 15.0, 20.0, 10.0, 0.0]);
+    let le135 = annotation.to_rotated_box(AngleRange::Le135);
+    assert_eq!(rounded(&le135), [20.0, 15.0, 20.0, 10.0, 0.0]);
+    // OpenCV does not use 0°, the sides are swapped instead
+    let oc = annotation.to_rotated_box(AngleRange::Oc);
+    assert_eq!(rounded(&oc), [20.0, 15.0, 10.0, 20.0, 90.0]);
+
+    let corners = Annotation::from_rotated_box(&oc).corners();
+    let rounded_corners = corners.map(|(x, y)| (x.round(), y.round()));
+    assert_eq!(rounded_corners, annotation.corners());
+}
+
+#[test]
+fn angle_conventions() {
+    let tall = RotatedBox::new(50.0, 40.0, 20.0, 40.0, 30.0, AngleUnit::Degrees);
+    let annotation = Annotation::from_rotated_box(&tall);
+
+    assert_eq!(
+        rounded(&annotation.to_rotated_box(AngleRange::Le90)),
+        [50.0, 40.0, 40.0, 20.0, -60.0]
+    );
+    assert_eq!(
+        rounded(&annotation.to_rotated_box(AngleRange::Le135)),
+        [50.0, 40.0, 40.0, 20.0, 120.0]
+    );
+    assert_eq!(
+        rounded(&annotation.to_rotated_box(AngleRange::Oc)),
+        [50.0, 40.0, 20.0, 40.0, 30.0]
+    );
+
+    let radians = RotatedBox::new(0.0, 0.0, 2.0, 1.0, 1.0, AngleUnit::Radians);
+    assert_eq!(radians.angle(AngleUnit::Radians), 1.0);
+    assert_eq!(radians.angle(AngleUnit::Degrees), 1.0_f64.to_degrees());
+}
+
+#[test]
+fn fit_min_area_rectangle() {
+    // A trapezoid fits in its bounding box
+    let trapezoid = Annotation::new(0.0, 4.0, 3.0, 1.0, 0.0, 0.0, 2.0, 2.0);
+    assert_eq!(
+        rounded(&trapezoid.to_rotated_box(AngleRange::Le90)),
+        [2.0, 1.0, 4.0, 2.0, 0.0]
+    );
+
+    // Collinear points give a box without height
+    let line = RotatedBox::min_area(&[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (1.0, 1.0)]);
+    assert_eq!(
+        rounded(&line.normalized(AngleRange::Le90)),
+        [1.0, 1.0, 2.0_f64.sqrt() * 2.0, 0.0, 45.0].map(|value| (value * 1000.0).round() / 1000.0)
+    );
+}
@@ -0,0 +1,308 @@
+mod common;
+
+use labelswap_data::conversion_report::{ConversionReport, GeometryChange};
+use labelswap_data::models::{
+    format::*,
+    keypoints::{Keypoint, Keypoints, Visibility},
+    segmentation::Segmentation,
+    Annotation, Image, Winding,
+};
+use labelswap_data::transforms::{
+    AnnotationField, Sanitize, Transform, TransformOutcome, TransformStage,
+};
+
+fn dummy_format(is_normalized: bool) -> Format {
+    Format {
+        is_normalized,
+        ..common::dummy_format()
+    }
+}
+
+fn with_image(annotation: Annotation) -> Annotation {
+    Annotation {
+        image: Image::new_with_dimensions(100, 50),
+        ..annotation
+    }
+}
+
+fn corners(annotation: &Annotation) -> [f64; 8] {
+    [
+        annotation.x1,
+        annotation.x2,
+        annotation.x3,
+        annotation.x4,
+        annotation.y1,
+        annotation.y2,
+        annotation.y3,
+        annotation.y4,
+    ]
+}
+
+fn changes(sanitize: &Sanitize) -> Vec<GeometryChange> {
+    let mut report = ConversionReport::default();
+    sanitize.report(&mut report);
+    report.geometry_fixes.iter().map(|fix| fix.change).collect()
+}
+
+#[test]
+fn clip_to_image_bounds() {
+    let format = dummy_format(false);
+    let mut sanitize = Sanitize::new();
+
+    let mut annotation = with_image(Annotation::from_min_max(-5.0, 120.0, 10.0, 60.0));
+    let outcome = sanitize.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(outcome, TransformOutcome::Keep);
+    assert_eq!(
+        corners(&annotation),
+        corners(&Annotation::from_min_max(0.0, 100.0, 10.0, 50.0))
+    );
+
+    // Normalized coordinates are clipped between 0 and 1
+    let normalized = dummy_format(true);
+    let mut annotation = Annotation::from_min_max(0.5, 1.5, -0.2, 0.5);
+    sanitize
+        .apply(&mut annotation, &normalized, &normalized)
+        .unwrap();
+    assert_eq!(
+        corners(&annotation),
+        corners(&Annotation::from_min_max(0.5, 1.0, 0.0, 0.5))
+    );
+
+    // Inside the image nothing changes
+    let mut annotation = with_image(Annotation::from_min_max(1.0, 2.0, 1.0, 2.0));
+    sanitize.apply(&mut annotation, &format, &format).unwrap();
+
+    assert_eq!(
+        changes(&sanitize),
+        vec![GeometryChange::Clipped, GeometryChange::Clipped]
+    );
+}
+
+#[test]
+fn fix_non_finite_coordinates() {
+    let format = dummy_format(false);
+    let mut sanitize = Sanitize::new();
+
+    let mut annotation = with_image(Annotation::from_min_max(10.0, 20.0, 10.0, f64::INFINITY));
+    annotation.x1 = f64::NAN;
+    sanitize.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(
+        corners(&annotation),
+        corners(&Annotation::from_min_max(10.0, 20.0, 10.0, 50.0))
+    );
+
+    // Both corners of an edge are NaN
+    let mut annotation = with_image(Annotation::from_min_max(10.0, 20.0, 10.0, 20.0));
+    annotation.y3 = f64::NAN;
+    annotation.y4 = f64::NAN;
+    assert!(matches!(
+        sanitize.apply(&mut annotation, &format, &format).unwrap(),
+        TransformOutcome::Drop(_)
+    ));
+
+    // Without dimensions, infinite pixel coordinates can not be clipped
+    let mut annotation = Annotation::from_min_max(10.0, f64::INFINITY, 10.0, 20.0);
+    assert!(matches!(
+        sanitize.apply(&mut annotation, &format, &format).unwrap(),
+        TransformOutcome::Drop(_)
+    ));
+
+    assert_eq!(
+        changes(&sanitize),
+        vec![
+            GeometryChange::FixedNonFinite,
+            GeometryChange::Clipped,
+            GeometryChange::FixedNonFinite,
+            GeometryChange::DroppedNonFinite,
+            GeometryChange::DroppedNonFinite,
+        ]
+    );
+}
+
+#[test]
+fn drop_small_boxes() {
+    let format = dummy_format(false);
+    let normalized = dummy_format(true);
+    let mut sanitize = Sanitize::new();
+    sanitize.set_stage(TransformStage::BeforeRequired);
+    sanitize.set_min_size(4.0);
+
+    // Entirely outside the image, it becomes a line when clipped
+    let mut annotation = with_image(Annotation::from_min_max(110.0, 120.0, 10.0, 20.0));
+    assert_eq!(
+        sanitize
+            .apply(&mut annotation, &format, &normalized)
+            .unwrap(),
+        TransformOutcome::Drop(String::from("Sanitize: zero size"))
+    );
+
+    let mut annotation = with_image(Annotation::from_min_max(10.0, 13.0, 10.0, 20.0));
+    assert!(matches!(
+        sanitize
+            .apply(&mut annotation, &format, &normalized)
+            .unwrap(),
+        TransformOutcome::Drop(_)
+    ));
+
+    // 0.04 x 0.1 of a 100x50 image is 4x5 pixels
+    let mut sanitize = Sanitize::new();
+    sanitize.set_min_size(4.0);
+    let mut annotation = with_image(Annotation::from_min_max(0.1, 0.14, 0.1, 0.2));
+    assert_eq!(
+        sanitize
+            .apply(&mut annotation, &format, &normalized)
+            .unwrap(),
+        TransformOutcome::Keep
+    );
+    assert!(changes(&sanitize).is_empty());
+}
+
+#[test]
+fn oriented_boxes() {
+    let format = Format {
+        box_type: BoxType::Oriented,
+        ..dummy_format(false)
+    };
+    let mut sanitize = Sanitize::new();
+
+    // The corners are in the order of the file, so NaN can not be fixed
+    let mut annotation = with_image(Annotation::new(
+        f64::NAN,
+        50.0,
+        60.0,
+        10.0,
+        10.0,
+        10.0,
+        40.0,
+        40.0,
+    ));
+    assert_eq!(
+        sanitize.apply(&mut annotation, &format, &format).unwrap(),
+        TransformOutcome::Drop(String::from("Sanitize: non-finite coordinates"))
+    );
+
+    // Rotated boxes inside the image are kept as they are
+    let inside = || {
+        with_image(Annotation::new(
+            50.0, 70.0, 50.0, 30.0, 10.0, 20.0, 30.0, 20.0,
+        ))
+    };
+    let mut annotation = inside();
+    assert_eq!(
+        sanitize.apply(&mut annotation, &format, &format).unwrap(),
+        TransformOutcome::Keep
+    );
+    assert_eq!(corners(&annotation), corners(&inside()));
+
+    // The ones crossing the bounds are replaced with a box around their
+    // part inside the image, in the same winding
+    let mut annotation = with_image(Annotation::new(
+        50.0, 110.0, 50.0, -10.0, -10.0, 20.0, 50.0, 20.0,
+    ));
+    assert_eq!(
+        sanitize.apply(&mut annotation, &format, &format).unwrap(),
+        TransformOutcome::Keep
+    );
+    assert_eq!(
+        corners(&annotation),
+        [0.0, 100.0, 100.0, 0.0, 0.0, 0.0, 50.0, 50.0]
+    );
+    assert_eq!(annotation.winding(), Some(Winding::Clockwise));
+
+    // Only the part inside the image is kept
+    let mut annotation = with_image(Annotation::new(
+        90.0, 120.0, 110.0, 80.0, 10.0, 25.0, 45.0, 30.0,
+    ));
+    sanitize.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(
+        corners(&annotation),
+        [80.0, 100.0, 100.0, 80.0, 10.0, 10.0, 40.0, 40.0]
+    );
+
+    // And dropped if they are entirely outside the image
+    let mut annotation = with_image(Annotation::new(
+        160.0, 220.0, 160.0, 100.0, -10.0, 20.0, 50.0, 20.0,
+    ));
+    assert!(matches!(
+        sanitize.apply(&mut annotation, &format, &format).unwrap(),
+        TransformOutcome::Drop(_)
+    ));
+
+    // Axis aligned boxes are clipped in any corner order
+    let mut annotation = with_image(Annotation::new(
+        120.0, 10.0, 10.0, 120.0, 10.0, 10.0, 60.0, 60.0,
+    ));
+    sanitize.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(
+        corners(&annotation),
+        [100.0, 10.0, 10.0, 100.0, 10.0, 10.0, 50.0, 50.0]
+    );
+
+    assert_eq!(
+        changes(&sanitize),
+        vec![
+            GeometryChange::DroppedNonFinite,
+            GeometryChange::Clipped,
+            GeometryChange::Clipped,
+            GeometryChange::DroppedDegenerate,
+            GeometryChange::Clipped,
+        ]
+    );
+}
+
+#[test]
+fn clip_segmentation_and_keypoints() {
+    let format = dummy_format(false);
+    let mut sanitize = Sanitize::new();
+
+    let mut annotation = with_image(Annotation {
+        segmentation: Some(Segmentation::Polygons(vec![
+            vec![(80.0, 10.0), (120.0, 10.0), (120.0, 30.0), (80.0, 30.0)],
+            vec![(150.0, 10.0), (160.0, 10.0), (160.0, 30.0)],
+        ])),
+        keypoints: Some(Keypoints::new(
+            vec![
+                Keypoint::new(90.0, 20.0, Visibility::Visible),
+                Keypoint::new(110.0, 20.0, Visibility::Occluded),
+                Keypoint::new(f64::NAN, 20.0, Visibility::Visible),
+            ],
+            None,
+        )),
+        ..Annotation::from_min_max(80.0, 120.0, 10.0, 30.0)
+    });
+    sanitize.apply(&mut annotation, &format, &format).unwrap();
+
+    // The polygon outside the image is removed
+    assert_eq!(
+        annotation.segmentation,
+        Some(Segmentation::Polygons(vec![vec![
+            (80.0, 10.0),
+            (100.0, 10.0),
+            (100.0, 30.0),
+            (80.0, 30.0),
+        ]]))
+    );
+    let not_labeled = Keypoint::new(0.0, 0.0, Visibility::NotLabeled);
+    assert_eq!(
+        annotation.keypoints.unwrap().points,
+        vec![
+            Keypoint::new(90.0, 20.0, Visibility::Visible),
+            not_labeled,
+            not_labeled,
+        ]
+    );
+    assert_eq!(
+        changes(&sanitize),
+        vec![GeometryChange::FixedNonFinite, GeometryChange::Clipped]
+    );
+}
+
+#[test]
+fn required_fields() {
+    // Only the transforms after the required ones can rely on the
+    // dimensions read from the images
+    let mut sanitize = Sanitize::new();
+    assert_eq!(sanitize.requires(), &[AnnotationField::ImageDimensions]);
+    sanitize.set_stage(TransformStage::BeforeRequired);
+    assert!(sanitize.requires().is_empty());
+}
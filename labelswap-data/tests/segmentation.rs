@@ -0,0 +1,70 @@
+use labelswap_data::models::{Annotation, RleCounts, RleMask, Segmentation};
+
+fn mask(counts: RleCounts) -> Segmentation {
+    Segmentation::Rle(RleMask {
+        height: 80,
+        width: 100,
+        counts,
+    })
+}
+
+#[test]
+fn polygon_area_and_box() {
+    let mut segmentation = Segmentation::Polygons(vec![
+        vec![(10.0, 10.0), (30.0, 10.0), (30.0, 30.0), (10.0, 30.0)],
+        vec![(40.0, 10.0), (50.0, 10.0), (45.0, 20.0)],
+    ]);
+    assert_eq!(segmentation.area(), 450.0);
+    assert_eq!(segmentation.bounding_box(), Some((10.0, 50.0, 10.0, 30.0)));
+
+    segmentation.scale(0.1, 0.5);
+    assert_eq!(segmentation.bounding_box(), Some((1.0, 5.0, 5.0, 15.0)));
+
+    assert_eq!(Segmentation::Polygons(Vec::new()).bounding_box(), None);
+}
+
+#[test]
+fn mask_area_and_box() {
+    // Two columns of 4 pixels, starting at (2, 2)
+    let runs = vec![162, 4, 76, 4, 7754];
+    let uncompressed = mask(RleCounts::Uncompressed(runs.clone()));
+    assert_eq!(uncompressed.area(), 8.0);
+    assert_eq!(uncompressed.bounding_box(), Some((2.0, 4.0, 2.0, 6.0)));
+
+    // A run that wraps to the next column covers it entirely
+    let wrapping = mask(RleCounts::Uncompressed(vec![78, 4, 7918]));
+    assert_eq!(wrapping.bounding_box(), Some((0.0, 2.0, 0.0, 80.0)));
+
+    let empty = mask(RleCounts::Uncompressed(vec![8000]));
+    assert_eq!(empty.area(), 0.0);
+    assert_eq!(empty.bounding_box(), None);
+}
+
+#[test]
+fn compressed_counts() {
+    let compressed = RleCounts::compress(&[330, 6, 74, 6, 7584]);
+    assert_eq!(compressed, RleCounts::Compressed(String::from("Z:6Z20fZ7")));
+    assert_eq!(compressed.runs(), vec![330, 6, 74, 6, 7584]);
+
+    let segmentation = mask(compressed);
+    assert_eq!(segmentation.area(), 12.0);
+    assert_eq!(segmentation.bounding_box(), Some((4.0, 6.0, 10.0, 16.0)));
+
+    // Large runs need several characters and may be smaller than the one
+    // two places before them
+    let runs = vec![0, 5, 3, 2, 1_000_000, 1, 7];
+    assert_eq!(RleCounts::compress(&runs).runs(), runs);
+}
+
+#[test]
+fn annotation_from_segmentation() {
+    let segmentation = Segmentation::Polygons(vec![vec![(4.0, 2.0), (8.0, 6.0), (1.0, 5.0)]]);
+    let annotation = Annotation::from_segmentation(segmentation.clone()).unwrap();
+    assert_eq!(
+        annotation.corners(),
+        Annotation::from_min_max(1.0, 8.0, 2.0, 6.0).corners()
+    );
+    assert_eq!(annotation.segmentation, Some(segmentation));
+
+    assert!(Annotation::from_segmentation(mask(RleCounts::Uncompressed(vec![8000]))).is_none());
+}
@@ -21,6 +21,7 @@ fn test_io() {
                     path: Some(PathBuf::from("000001.jpg")),
                     width: Some(500),
                     height: Some(375),
+                    id: None,
                 },
                 class: ClassRepresentation::ClassName(String::from("helmet")),
                 source_file: Some(PathBuf::from(&source_file)),
@@ -32,6 +33,7 @@ fn test_io() {
                     path: Some(PathBuf::from("000002.jpg")),
                     width: Some(250),
                     height: Some(450),
+                    id: None,
                 },
                 class: ClassRepresentation::ClassName(String::from("helmet")),
                 source_file: Some(PathBuf::from(&source_file)),
@@ -43,6 +45,7 @@ fn test_io() {
                     path: Some(PathBuf::from("000003.jpg")),
                     width: Some(1123),
                     height: Some(543),
+                    id: None,
                 },
                 class: ClassRepresentation::ClassName(String::from("helmet")),
                 source_file: Some(PathBuf::from(&source_file)),
@@ -54,6 +57,7 @@ fn test_io() {
                     path: Some(PathBuf::from("000006.jpg")),
                     width: Some(500),
                     height: Some(466),
+                    id: None,
                 },
                 class: ClassRepresentation::ClassName(String::from("helmet")),
                 source_file: Some(PathBuf::from(&source_file)),
@@ -1,6 +1,42 @@
+mod common;
+
 use std::collections::HashSet;
 
-use labelswap_data::{models::format::*, transforms::RequiredTransformations};
+use labelswap_data::{
+    conversion_report::ConversionReport,
+    models::{
+        format::*, AngleUnit, Annotation, Image, Keypoint, Keypoints, RleCounts, RleMask,
+        RotatedBox, Segmentation, Visibility,
+    },
+    transforms::{
+        scheduler::{schedule, ScheduleError},
+        AlignBoxes, AnnotationField, AssignImageId, BoxPolicy, Denormalize, DropSegmentation,
+        Normalize, RequiredTransformations, Transform,
+    },
+};
+
+fn dummy_format(image_id: ImageId) -> Format {
+    Format {
+        image_path: ImagePath::ContainsPath,
+        image_id,
+        ..common::dummy_format()
+    }
+}
+
+fn image_ids(transform: &mut AssignImageId, paths: &[&str]) -> Vec<Option<u32>> {
+    let format = dummy_format(ImageId::ContainsId);
+    paths
+        .iter()
+        .map(|path| {
+            let mut annotation = Annotation {
+                image: Image::new_with_path(path),
+                ..Annotation::from_top_left_corner(0.0, 0.0, 1.0, 1.0)
+            };
+            transform.apply(&mut annotation, &format, &format).unwrap();
+            annotation.image.id
+        })
+        .collect()
+}
 
 #[test]
 pub fn normalize_compatibility() {
@@ -10,6 +46,10 @@ pub fn normalize_compatibility() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::NoMapping,
         class_format: ClassFormat::Name,
         source_type: SourceType::SingleFile,
@@ -21,6 +61,10 @@ pub fn normalize_compatibility() {
         file_extension: None,
         is_normalized: true,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::NoMapping,
         class_format: ClassFormat::Name,
         source_type: SourceType::SingleFile,
@@ -32,6 +76,10 @@ pub fn normalize_compatibility() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::NoMapping,
         class_format: ClassFormat::Name,
         source_type: SourceType::SingleFile,
@@ -62,6 +110,10 @@ pub fn mapping_name_to_id() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::NoMapping,
         class_format: ClassFormat::Name,
         source_type: SourceType::SingleFile,
@@ -73,6 +125,10 @@ pub fn mapping_name_to_id() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::ContainsMapping,
         class_format: ClassFormat::Id,
         source_type: SourceType::SingleFile,
@@ -90,6 +146,10 @@ pub fn mapping_name_to_id() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::ContainsMapping,
         class_format: ClassFormat::Both,
         source_type: SourceType::SingleFile,
@@ -107,6 +167,10 @@ pub fn mapping_name_to_id() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::ContainsMapping,
         class_format: ClassFormat::Name,
         source_type: SourceType::SingleFile,
@@ -127,6 +191,10 @@ pub fn mapping_id_to_name() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::NoMapping,
         class_format: ClassFormat::Id,
         source_type: SourceType::SingleFile,
@@ -138,6 +206,10 @@ pub fn mapping_id_to_name() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::ContainsMapping,
         class_format: ClassFormat::Name,
         source_type: SourceType::SingleFile,
@@ -154,6 +226,10 @@ pub fn mapping_id_to_name() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::ContainsMapping,
         class_format: ClassFormat::Both,
         source_type: SourceType::SingleFile,
@@ -170,6 +246,10 @@ pub fn mapping_id_to_name() {
         file_extension: None,
         is_normalized: false,
         image_path: ImagePath::NoPath,
+        image_id: ImageId::NoId,
+        image_dimensions: ImageDimensions::NoDimensions,
+        box_type: BoxType::AxisAligned,
+        segmentation_type: SegmentationType::NoSegmentation,
         class_mapping: ClassMapping::ContainsMapping,
         class_format: ClassFormat::Id,
         source_type: SourceType::SingleFile,
@@ -181,3 +261,288 @@ pub fn mapping_id_to_name() {
     let compatibility = source_mapping.check_compatibility(&target_both);
     assert_eq!(compatibility, HashSet::new());
 }
+
+#[test]
+pub fn schedule_by_dependencies() {
+    // Denormalize needs the image path that LookupImage fills in
+    let transformations = [
+        RequiredTransformations::Denormalize,
+        RequiredTransformations::LookupImage,
+        RequiredTransformations::MapToName,
+    ];
+    let dependencies: Vec<_> = transformations
+        .iter()
+        .map(|t| (t.requires(), t.provides()))
+        .collect();
+    assert_eq!(schedule(&dependencies), Ok(vec![1, 0, 2]));
+
+    // Independent transforms keep their order
+    assert_eq!(schedule(&[(&[], &[]), (&[], &[])]), Ok(vec![0, 1]));
+    assert_eq!(schedule(&[]), Ok(vec![]));
+}
+
+#[test]
+pub fn schedule_modified_fields() {
+    // Transforms modifying the class name run in the order they were given,
+    // and after the transforms providing it that come before them
+    let class_name = [AnnotationField::ClassName];
+    let remap = [AnnotationField::ClassName, AnnotationField::ClassId];
+    let dependencies: [(&[AnnotationField], &[AnnotationField]); 4] = [
+        (&class_name, &remap),
+        (&class_name, &remap),
+        (&[], &class_name),
+        (&class_name, &remap),
+    ];
+    assert_eq!(schedule(&dependencies), Ok(vec![0, 1, 2, 3]));
+
+    // Transforms only reading a field still wait for every provider
+    let dependencies: [(&[AnnotationField], &[AnnotationField]); 2] =
+        [(&class_name, &[]), (&class_name, &remap)];
+    assert_eq!(schedule(&dependencies), Ok(vec![1, 0]));
+}
+
+#[test]
+pub fn schedule_cycle() {
+    let image_path = [AnnotationField::ImagePath];
+    let class_name = [AnnotationField::ClassName];
+    let dependencies: [(&[AnnotationField], &[AnnotationField]); 3] = [
+        (&[], &[AnnotationField::SourceFile]),
+        (&image_path, &class_name),
+        (&class_name, &image_path),
+    ];
+
+    assert_eq!(
+        schedule(&dependencies),
+        Err(ScheduleError {
+            unscheduled: vec![1, 2]
+        })
+    );
+}
+
+#[test]
+pub fn image_id_compatibility() {
+    let source = dummy_format(ImageId::NoId);
+    let target = dummy_format(ImageId::ContainsId);
+
+    let mut expected = HashSet::new();
+    expected.insert(RequiredTransformations::AssignImageId);
+    assert_eq!(source.check_compatibility(&target), expected);
+    assert_eq!(target.check_compatibility(&source), HashSet::new());
+}
+
+#[test]
+pub fn assign_image_ids() {
+    let mut transform = AssignImageId::new();
+    assert_eq!(
+        image_ids(
+            &mut transform,
+            &["a/1.jpg", "a/2.jpg", "a/1.jpg", "b/1.jpg"]
+        ),
+        vec![Some(0), Some(1), Some(0), Some(2)]
+    );
+
+    // Ids from the COCO file are kept, new images get higher ones
+    let coco = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/cocojson-labels/coco.json"
+    );
+    let mut transform = AssignImageId::from_coco(coco).unwrap();
+    assert_eq!(
+        image_ids(&mut transform, &["0002.jpg", "0001.jpg", "images/0001.jpg"]),
+        vec![Some(1), Some(0), Some(2)]
+    );
+
+    // Images are told apart by their whole path, not their file name
+    let coco = std::env::temp_dir().join(format!("labelswap-ids-{}.json", std::process::id()));
+    let images = r#"{"images": [
+        {"id": 7, "file_name": "a/0001.jpg", "width": 10, "height": 10},
+        {"id": 3, "file_name": "b/0001.jpg", "width": 10, "height": 10}
+    ], "annotations": [], "categories": []}"#;
+    std::fs::write(&coco, images).unwrap();
+    let mut transform = AssignImageId::from_coco(&coco).unwrap();
+    assert_eq!(
+        image_ids(&mut transform, &["b/0001.jpg", "a/0001.jpg", "0001.jpg"]),
+        vec![Some(3), Some(7), Some(8)]
+    );
+}
+
+#[test]
+pub fn box_type_compatibility() {
+    let oriented = Format {
+        box_type: BoxType::Oriented,
+        ..dummy_format(ImageId::NoId)
+    };
+    let axis_aligned = dummy_format(ImageId::NoId);
+
+    let mut expected = HashSet::new();
+    expected.insert(RequiredTransformations::AlignBoxes);
+    assert_eq!(oriented.check_compatibility(&axis_aligned), expected);
+    assert_eq!(axis_aligned.check_compatibility(&oriented), HashSet::new());
+}
+
+#[test]
+pub fn align_boxes() {
+    let format = dummy_format(ImageId::NoId);
+    // A 2x2 square rotated 45°, its corners are 1.414 away from the center
+    let rotated = || {
+        Annotation::from_rotated_box(&RotatedBox::new(
+            10.0,
+            10.0,
+            2.0,
+            2.0,
+            45.0,
+            AngleUnit::Degrees,
+        ))
+    };
+    let rounded_extents = |annotation: &Annotation| {
+        [
+            annotation.get_xmin(),
+            annotation.get_xmax(),
+            annotation.get_ymin(),
+            annotation.get_ymax(),
+        ]
+        .map(|value| (value * 1000.0).round() / 1000.0)
+    };
+
+    let mut enclosing = AlignBoxes::new(BoxPolicy::Enclosing);
+    let mut annotation = rotated();
+    enclosing.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(rounded_extents(&annotation), [8.586, 11.414, 8.586, 11.414]);
+
+    let mut inscribed = AlignBoxes::new(BoxPolicy::Inscribed);
+    let mut annotation = rotated();
+    inscribed.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(rounded_extents(&annotation), [9.293, 10.707, 9.293, 10.707]);
+
+    // Axis aligned boxes are kept even when refusing oriented ones
+    let mut refuse = AlignBoxes::new(BoxPolicy::Refuse);
+    let mut annotation = Annotation::from_min_max(1.0, 2.0, 1.0, 2.0);
+    refuse.apply(&mut annotation, &format, &format).unwrap();
+    assert!(refuse.apply(&mut rotated(), &format, &format).is_err());
+
+    // A NaN corner, as read from `nan 10 50 10 50 60 10 60 head 0`
+    let mut annotation = Annotation::new(f64::NAN, 50.0, 50.0, 10.0, 10.0, 10.0, 60.0, 60.0);
+    assert_eq!(annotation.get_xmin(), 10.0);
+    assert_eq!(annotation.get_xmax(), 50.0);
+    assert!(enclosing.apply(&mut annotation, &format, &format).is_err());
+
+    let mut report = ConversionReport::default();
+    enclosing.report(&mut report);
+    let alignment = report.box_alignment.unwrap();
+    assert_eq!(alignment.boxes, 1);
+    assert_eq!(alignment.area_change_percent().round(), 100.0);
+
+    let mut report = ConversionReport::default();
+    inscribed.report(&mut report);
+    assert_eq!(
+        report.box_alignment.unwrap().area_change_percent().round(),
+        -50.0
+    );
+}
+
+#[test]
+pub fn segmentation_compatibility() {
+    let with_segmentation = |segmentation_type| Format {
+        segmentation_type,
+        ..dummy_format(ImageId::NoId)
+    };
+    let boxes = dummy_format(ImageId::NoId);
+    let polygons = with_segmentation(SegmentationType::Polygons);
+    let masks = with_segmentation(SegmentationType::PolygonsAndMasks);
+
+    let mut expected = HashSet::new();
+    expected.insert(RequiredTransformations::DropSegmentation);
+    assert_eq!(polygons.check_compatibility(&boxes), expected);
+    assert_eq!(masks.check_compatibility(&polygons), expected);
+    assert_eq!(boxes.check_compatibility(&polygons), HashSet::new());
+    assert_eq!(polygons.check_compatibility(&masks), HashSet::new());
+}
+
+#[test]
+pub fn drop_segmentation() {
+    let source = Format {
+        segmentation_type: SegmentationType::PolygonsAndMasks,
+        ..dummy_format(ImageId::NoId)
+    };
+    let target = Format {
+        segmentation_type: SegmentationType::Polygons,
+        ..dummy_format(ImageId::NoId)
+    };
+    let polygon = Segmentation::Polygons(vec![vec![(1.0, 1.0), (4.0, 2.0), (2.0, 5.0)]]);
+    let mask = Segmentation::Rle(RleMask {
+        height: 10,
+        width: 10,
+        counts: RleCounts::Uncompressed(vec![12, 3, 85]),
+    });
+
+    let mut transform = DropSegmentation::new();
+    let mut annotation = Annotation::from_segmentation(polygon.clone()).unwrap();
+    transform.apply(&mut annotation, &source, &target).unwrap();
+    assert_eq!(annotation.segmentation, Some(polygon));
+
+    // The mask is dropped and its bounding box is kept
+    let mut annotation = Annotation::from_segmentation(mask).unwrap();
+    transform.apply(&mut annotation, &source, &target).unwrap();
+    assert_eq!(annotation.segmentation, None);
+    assert_eq!(
+        annotation.corners(),
+        Annotation::from_min_max(1.0, 2.0, 2.0, 5.0).corners()
+    );
+
+    let mut report = ConversionReport::default();
+    transform.report(&mut report);
+    assert_eq!(report.dropped_segmentations, 1);
+}
+
+#[test]
+pub fn normalize_keypoints_and_segmentation() {
+    let format = dummy_format(ImageId::NoId);
+    let keypoints = |points: &[(f64, f64)]| {
+        Keypoints::new(
+            points
+                .iter()
+                .map(|&(x, y)| Keypoint::new(x, y, Visibility::Visible))
+                .collect(),
+            None,
+        )
+    };
+
+    let mut annotation = Annotation {
+        image: Image::new_with_dimensions(200, 100),
+        keypoints: Some(keypoints(&[(50.0, 25.0), (100.0, 100.0)])),
+        ..Annotation::from_segmentation(Segmentation::Polygons(vec![vec![
+            (50.0, 25.0),
+            (100.0, 25.0),
+            (100.0, 100.0),
+        ]]))
+        .unwrap()
+    };
+
+    let mut normalize = Normalize::new(None).unwrap();
+    normalize.apply(&mut annotation, &format, &format).unwrap();
+    assert_eq!(
+        annotation.keypoints,
+        Some(keypoints(&[(0.25, 0.25), (0.5, 1.0)]))
+    );
+    assert_eq!(
+        annotation.segmentation,
+        Some(Segmentation::Polygons(vec![vec![
+            (0.25, 0.25),
+            (0.5, 0.25),
+            (0.5, 1.0)
+        ]]))
+    );
+    assert_eq!(
+        annotation.corners(),
+        Annotation::from_min_max(0.25, 0.5, 0.25, 1.0).corners()
+    );
+
+    let mut denormalize = Denormalize::new(None).unwrap();
+    denormalize
+        .apply(&mut annotation, &format, &format)
+        .unwrap();
+    assert_eq!(
+        annotation.keypoints,
+        Some(keypoints(&[(50.0, 25.0), (100.0, 100.0)]))
+    );
+}
@@ -1,7 +1,6 @@
 use labelswap_data::models::{annotation::ClassRepresentation, Annotation, Image};
 use labelswap_data::parser::*;
 use labelswap_data::resolve_relative_path;
-use std::path::{Path, PathBuf};
 
 mod common_parser;
 
@@ -16,6 +15,7 @@ fn test_io() {
             Annotation {
                 class: ClassRepresentation::ClassName(String::from("small-vehicle")),
                 difficulty: false,
+                truncated: false,
                 source_file: resolve_relative_path(&source_directory, "001.txt").ok(),
                 image: Image::new(),
                 x1: 287.0,
@@ -26,10 +26,13 @@ fn test_io() {
                 y3: 279.0,
                 x4: 287.0,
                 y4: 279.0,
+                segmentation: None,
+                keypoints: None,
             },
             Annotation {
                 class: ClassRepresentation::ClassName(String::from("large-vehicle")),
                 difficulty: false,
+                truncated: false,
                 source_file: resolve_relative_path(&source_directory, "001.txt").ok(),
                 image: Image::new(),
                 x1: 212.00000000000006,
@@ -40,10 +43,13 @@ fn test_io() {
                 y3: 293.0,
                 x4: 212.00000000000006,
                 y4: 293.0,
+                segmentation: None,
+                keypoints: None,
             },
             Annotation {
                 class: ClassRepresentation::ClassName(String::from("large-vehicle")),
                 difficulty: false,
+                truncated: false,
                 source_file: resolve_relative_path(&source_directory, "001.txt").ok(),
                 image: Image::new(),
                 x1: 167.0,
@@ -54,10 +60,13 @@ fn test_io() {
                 y3: 158.0,
                 x4: 167.0,
                 y4: 158.0,
+                segmentation: None,
+                keypoints: None,
             },
             Annotation {
                 class: ClassRepresentation::ClassName(String::from("large-vehicle")),
                 difficulty: false,
+                truncated: false,
                 source_file: resolve_relative_path(&source_directory, "002.txt").ok(),
                 image: Image::new(),
                 x1: 167.0,
@@ -68,6 +77,8 @@ fn test_io() {
                 y3: 158.0,
                 x4: 167.0,
                 y4: 158.0,
+                segmentation: None,
+                keypoints: None,
             },
         ],
     );
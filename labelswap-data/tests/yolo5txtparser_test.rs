@@ -2,7 +2,6 @@ mod common_parser;
 
 use labelswap_data::models::{annotation::ClassRepresentation, *};
 use labelswap_data::parser::*;
-use std::path::{Path, PathBuf};
 use labelswap_data::resolve_relative_path;
 
 #[test]
@@ -1,7 +1,9 @@
-use labelswap_data::models::{annotation::ClassRepresentation, Annotation, Image};
+use labelswap_data::class_map::ClassMap;
+use labelswap_data::models::{annotation::ClassRepresentation, Annotation, Image, ImageRecord};
 use labelswap_data::parser::{YoloDarknetParser, FormatParser};
-use std::path::Path;
 use labelswap_data::resolve_relative_path;
+use labelswap_data::serializer::{FormatSerializer, YoloDarknetSerializer};
+use std::path::PathBuf;
 
 mod common_parser;
 #[test]
@@ -48,3 +50,120 @@ fn test_io() {
         ],
     );
 }
+
+fn parse_all(path: &PathBuf) -> Vec<Annotation> {
+    let mut parser = YoloDarknetParser::new();
+    parser.init(path).unwrap();
+    let mut annotations = Vec::new();
+    while parser.has_next() {
+        annotations.push(parser.get_next().unwrap());
+    }
+    annotations
+}
+
+fn output_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "labelswap-yolodarknet-{name}-{}",
+        std::process::id()
+    ))
+}
+
+fn class(name: &str, id: &str) -> ClassRepresentation<String> {
+    ClassRepresentation::Both {
+        name: String::from(name),
+        id: String::from(id),
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let source_directory = common_parser::resolve_test_path("tests/yolodarknet-labels");
+    let destination = output_directory("round-trip");
+
+    let mut serializer = YoloDarknetSerializer::new();
+    serializer.init(&destination).unwrap();
+    for annotation in parse_all(&source_directory) {
+        serializer.push(annotation).unwrap();
+    }
+    serializer.finish().unwrap();
+
+    let written = parse_all(&destination);
+    let annotations = parse_all(&source_directory);
+    assert_eq!(written.len(), annotations.len());
+    for (written, annotation) in written.iter().zip(annotations.iter()) {
+        assert_eq!(written.class, annotation.class);
+        assert_eq!(
+            written.source_file.as_ref().unwrap().file_name(),
+            annotation.source_file.as_ref().unwrap().file_name()
+        );
+        for (a, b) in written.corners().iter().zip(annotation.corners().iter()) {
+            assert!((a.0 - b.0).abs() < 1e-12 && (a.1 - b.1).abs() < 1e-12);
+        }
+    }
+}
+
+#[test]
+fn test_serializer_classes_and_empty_images() {
+    let destination = output_directory("classes");
+
+    // The classes of the class map are listed even without annotations
+    let mut class_map = ClassMap::new();
+    class_map.insert(0, "head").unwrap();
+    class_map.insert(1, "helmet").unwrap();
+
+    let mut serializer = YoloDarknetSerializer::new();
+    serializer.set_class_map(class_map);
+    serializer.init(&destination).unwrap();
+
+    // Label files are named after the image
+    serializer
+        .push(Annotation {
+            class: class("vest", "2"),
+            source_file: Some(PathBuf::from("labels.json")),
+            image: Image::new_with_path("images/004.jpg"),
+            ..Annotation::from_min_max(0.25, 0.75, 0.5, 1.0)
+        })
+        .unwrap();
+    serializer
+        .push_image(ImageRecord::new(Image::new_with_path("images/005.png"), None))
+        .unwrap();
+
+    // A class whose id belongs to another one
+    let conflicting = Annotation {
+        class: class("vest", "0"),
+        image: Image::new_with_path("images/004.jpg"),
+        ..Annotation::from_min_max(0.25, 0.75, 0.5, 1.0)
+    };
+    assert!(serializer.push(conflicting).is_err());
+
+    // An image whose label file would overwrite the one of another image
+    let same_name = Annotation {
+        class: class("head", "0"),
+        image: Image::new_with_path("val/004.jpg"),
+        ..Annotation::from_min_max(0.25, 0.75, 0.5, 1.0)
+    };
+    assert!(serializer.push(same_name).is_err());
+    assert!(serializer
+        .push_image(ImageRecord::new(Image::new_with_path("val/005.png"), None))
+        .is_err());
+    serializer.finish().unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(destination.join("004.txt")).unwrap(),
+        "2 0.5 0.75 0.5 0.5\n"
+    );
+    assert_eq!(std::fs::read_to_string(destination.join("005.txt")).unwrap(), "");
+    assert_eq!(
+        std::fs::read_to_string(destination.join("darknet.labels")).unwrap(),
+        "head\nhelmet\nvest\n"
+    );
+
+    // The line of a class is its id, so every id needs a name
+    let mut class_map = ClassMap::new();
+    class_map.insert(0, "head").unwrap();
+    class_map.insert(2, "vest").unwrap();
+    let mut serializer = YoloDarknetSerializer::new();
+    serializer.set_class_map(class_map);
+    serializer.init(output_directory("missing-class")).unwrap();
+    assert!(serializer.finish().is_err());
+}
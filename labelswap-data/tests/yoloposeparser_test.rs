@@ -0,0 +1,120 @@
+mod common_parser;
+
+use labelswap_data::models::{annotation::ClassRepresentation, *};
+use labelswap_data::parser::*;
+use labelswap_data::resolve_relative_path;
+use labelswap_data::serializer::*;
+use std::path::PathBuf;
+
+fn parse_all(parser: &mut YoloPoseParser, path: &PathBuf) -> Vec<Annotation> {
+    parser.init(path).unwrap();
+    let mut annotations = Vec::new();
+    while parser.has_next() {
+        annotations.push(parser.get_next().unwrap());
+    }
+    annotations
+}
+
+fn output_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("labelswap-yolopose-{name}-{}", std::process::id()))
+}
+
+#[test]
+fn test_io() {
+    let mut parser = YoloPoseParser::new();
+    let source_directory = common_parser::resolve_test_path("tests/yolopose-labels");
+    parser.init(&source_directory).unwrap();
+    common_parser::test_annotation(
+        &mut parser,
+        vec![
+            Annotation {
+                class: ClassRepresentation::ClassId(String::from("0")),
+                source_file: resolve_relative_path(&source_directory, "001.txt").ok(),
+                image: Image::empty(),
+                keypoints: Some(Keypoints::new(
+                    vec![
+                        Keypoint::new(0.5, 0.35, Visibility::Visible),
+                        Keypoint::new(0.45, 0.4, Visibility::Occluded),
+                        Keypoint::new(0.0, 0.0, Visibility::NotLabeled),
+                    ],
+                    None,
+                )),
+                ..Annotation::from_centers(0.5, 0.5, 0.25, 0.5)
+            },
+            // A line without keypoints
+            Annotation {
+                class: ClassRepresentation::ClassId(String::from("1")),
+                source_file: resolve_relative_path(&source_directory, "002.txt").ok(),
+                image: Image::empty(),
+                ..Annotation::from_centers(0.25, 0.75, 0.125, 0.25)
+            },
+        ],
+    );
+}
+
+#[test]
+fn test_without_visibility() {
+    let directory = output_directory("two-dims");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("001.txt"),
+        "0 0.5 0.5 0.2 0.4 0.5 0.35 0 0\n",
+    )
+    .unwrap();
+
+    // With a visibility, the 2 values left are not a keypoint
+    let mut parser = YoloPoseParser::new();
+    parser.init(&directory).unwrap();
+    assert!(parser.has_next());
+    assert!(parser.get_next().is_err());
+
+    let mut parser = YoloPoseParser::new();
+    parser.set_visibility(false);
+    let annotations = parse_all(&mut parser, &directory);
+    assert_eq!(
+        annotations[0].keypoints.as_ref().unwrap().points,
+        vec![
+            Keypoint::new(0.5, 0.35, Visibility::Visible),
+            Keypoint::new(0.0, 0.0, Visibility::NotLabeled),
+        ]
+    );
+}
+
+#[test]
+fn test_round_trip() {
+    let source_directory = common_parser::resolve_test_path("tests/yolopose-labels");
+    let destination = output_directory("round-trip");
+    let annotations = parse_all(&mut YoloPoseParser::new(), &source_directory);
+
+    let mut serializer = YoloPoseSerializer::new();
+    serializer.init(&destination).unwrap();
+    for annotation in parse_all(&mut YoloPoseParser::new(), &source_directory) {
+        serializer.push(annotation).unwrap();
+    }
+    serializer.finish().unwrap();
+
+    // Lines with fewer keypoints are completed with unlabeled ones
+    assert_eq!(
+        std::fs::read_to_string(destination.join("002.txt")).unwrap(),
+        "1 0.25 0.75 0.125 0.25 0 0 0 0 0 0 0 0 0\n"
+    );
+
+    let written = parse_all(&mut YoloPoseParser::new(), &destination);
+    assert_eq!(written[0].keypoints, annotations[0].keypoints);
+    assert_eq!(written[0].corners(), annotations[0].corners());
+    assert_eq!(written[1].keypoints.as_ref().unwrap().labeled(), 0);
+
+    // Without visibility, only the coordinates are written
+    let destination = output_directory("two-dims-output");
+    let mut serializer = YoloPoseSerializer::new();
+    serializer.set_visibility(false);
+    serializer.init(&destination).unwrap();
+    for annotation in annotations.into_iter().take(1) {
+        serializer.push(annotation).unwrap();
+    }
+    serializer.finish().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(destination.join("001.txt")).unwrap(),
+        "0 0.5 0.5 0.25 0.5 0.5 0.35 0.45 0.4 0 0\n"
+    );
+}
@@ -0,0 +1,191 @@
+mod common_parser;
+
+use labelswap_data::models::{annotation::ClassRepresentation, *};
+use labelswap_data::parser::*;
+use labelswap_data::resolve_relative_path;
+use labelswap_data::serializer::*;
+use std::path::PathBuf;
+
+fn polygon_annotation(
+    class: &str,
+    source_file: Option<PathBuf>,
+    polygon: &[(f64, f64)],
+) -> Annotation {
+    Annotation {
+        class: ClassRepresentation::ClassId(String::from(class)),
+        source_file,
+        image: Image::empty(),
+        ..Annotation::from_segmentation(Segmentation::Polygons(vec![polygon.to_vec()])).unwrap()
+    }
+}
+
+fn parse_all(path: &PathBuf) -> Vec<Annotation> {
+    let mut parser = YoloSegParser::new();
+    parser.init(path).unwrap();
+    let mut annotations = Vec::new();
+    while parser.has_next() {
+        annotations.push(parser.get_next().unwrap());
+    }
+    annotations
+}
+
+fn output_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("labelswap-yoloseg-{name}-{}", std::process::id()))
+}
+
+#[test]
+fn test_io() {
+    let mut parser = YoloSegParser::new();
+    let source_directory = common_parser::resolve_test_path("tests/yoloseg-labels");
+    parser.init(&source_directory).unwrap();
+
+    let first = resolve_relative_path(&source_directory, "001.txt").ok();
+    let second = resolve_relative_path(&source_directory, "002.txt").ok();
+    common_parser::test_annotation(
+        &mut parser,
+        vec![
+            polygon_annotation("0", first.clone(), &[(0.1, 0.2), (0.4, 0.2), (0.3, 0.6)]),
+            polygon_annotation(
+                "2",
+                first,
+                &[(0.5, 0.5), (0.75, 0.5), (0.75, 0.75), (0.5, 0.75)],
+            ),
+            polygon_annotation(
+                "1",
+                second,
+                &[(0.25, 0.1), (0.9, 0.3), (0.6, 0.8), (0.2, 0.7), (0.1, 0.4)],
+            ),
+        ],
+    );
+
+    // The corners are the bounding box of the polygon
+    let annotations = parse_all(&source_directory);
+    assert_eq!(
+        annotations[0].corners(),
+        Annotation::from_min_max(0.1, 0.4, 0.2, 0.6).corners()
+    );
+}
+
+#[test]
+fn test_invalid_lines() {
+    let directory = output_directory("invalid");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("001.txt"),
+        "0 0.1 0.2 0.3 0.4\n0 0.1 0.2 0.3 0.4 0.5\n",
+    )
+    .unwrap();
+
+    let mut parser = YoloSegParser::new();
+    parser.init(&directory).unwrap();
+    assert!(parser.has_next());
+    // Two points are not a polygon
+    assert!(parser.get_next().is_err());
+    // An x without its y
+    assert!(parser.get_next().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unreadable_file() {
+    let directory = output_directory("unreadable");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::os::unix::fs::symlink(directory.join("missing.txt"), directory.join("001.txt")).unwrap();
+    std::fs::write(directory.join("002.txt"), "0 0.1 0.2 0.3 0.4 0.5 0.6\n").unwrap();
+
+    // The file that can not be opened is reported, not skipped
+    let mut parser = YoloSegParser::new();
+    parser.init(&directory).unwrap();
+    assert!(parser.has_next());
+    assert!(matches!(parser.get_next(), Err(ParserError::Io(_))));
+    assert_eq!(
+        parser.current_source_file(),
+        Some(directory.join("001.txt"))
+    );
+    assert!(parser.has_next());
+    assert!(parser.get_next().is_ok());
+    assert!(!parser.has_next());
+}
+
+#[test]
+fn test_round_trip() {
+    let source_directory = common_parser::resolve_test_path("tests/yoloseg-labels");
+    let destination = output_directory("round-trip");
+
+    let mut serializer = YoloSegSerializer::new();
+    serializer.init(&destination).unwrap();
+    for annotation in parse_all(&source_directory) {
+        serializer.push(annotation).unwrap();
+    }
+    serializer.finish().unwrap();
+
+    let without_source = |annotations: Vec<Annotation>| -> Vec<_> {
+        annotations
+            .into_iter()
+            .map(|annotation| (annotation.class, annotation.segmentation))
+            .collect()
+    };
+    assert_eq!(
+        without_source(parse_all(&destination)),
+        without_source(parse_all(&source_directory))
+    );
+}
+
+#[test]
+fn test_serializer_fallbacks() {
+    let destination = output_directory("fallbacks");
+    let source_file = Some(PathBuf::from("001.txt"));
+
+    // Without a polygon, the corners of the box are written
+    let mut boxed = Annotation {
+        class: ClassRepresentation::ClassId(String::from("3")),
+        source_file: source_file.clone(),
+        ..Annotation::from_min_max(0.1, 0.2, 0.3, 0.4)
+    };
+    boxed.image = Image::empty();
+
+    // An object split in two parts is written as a single polygon
+    let split = Annotation {
+        class: ClassRepresentation::Both {
+            name: String::from("person"),
+            id: String::from("0"),
+        },
+        source_file,
+        ..Annotation::from_segmentation(Segmentation::Polygons(vec![
+            vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            vec![(3.0, 3.0), (2.0, 1.0), (3.0, 1.0)],
+        ]))
+        .unwrap()
+    };
+
+    let mut serializer = YoloSegSerializer::new();
+    serializer.init(&destination).unwrap();
+    serializer.push(boxed).unwrap();
+    serializer.push(split).unwrap();
+    serializer.finish().unwrap();
+
+    let written = std::fs::read_to_string(destination.join("001.txt")).unwrap();
+    assert_eq!(
+        written,
+        "3 0.1 0.3 0.2 0.3 0.2 0.4 0.1 0.4\n\
+         0 0 0 1 0 1 1 2 1 3 1 3 3 2 1 1 1\n"
+    );
+}
+
+#[test]
+fn test_serializer_errors() {
+    let destination = output_directory("errors");
+    let annotation = Annotation {
+        source_file: Some(PathBuf::from("..")),
+        ..polygon_annotation("0", None, &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)])
+    };
+
+    // A label file without a name fails instead of being skipped
+    let mut serializer = YoloSegSerializer::new();
+    serializer.init(&destination).unwrap();
+    serializer.push(annotation).unwrap();
+    assert!(matches!(
+        serializer.finish(),
+        Err(SerializerError::Other(_))
+    ));
+}
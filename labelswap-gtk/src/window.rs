@@ -24,7 +24,7 @@ use adw::subclass::prelude::*;
 use gtk::{gio, glib};
 
 mod imp {
-    use labelswap_data::models::{format::{ClassFormat, SourceType}, Format};
+    use labelswap_data::models::{format::BoxType, Format};
 
     use crate::models::FormatObject;
 
@@ -58,14 +58,8 @@ mod imp {
 
             let formats = [
                 Format {
-                    name: String::from("YOLO v5 Oriented Bounding Boxes"),
-                    id: String::from("yolo5obb"),
-                    file_extension: None,
-                    is_normalized: false,
-                    image_path: labelswap_data::models::format::ImagePath::NoPath,
-                    class_mapping: labelswap_data::models::format::ClassMapping::NoMapping,
-                    class_format: ClassFormat::Name,
-                    source_type: SourceType::SingleFile,
+                    box_type: BoxType::Oriented,
+                    ..Format::new("YOLO v5 Oriented Bounding Boxes", "yolo5obb")
                 }
             ];
         }